@@ -1,40 +1,27 @@
 // build.rs - The build script.
 // This file is created by Fan Jinhao.
 
-extern crate bindgen;
 extern crate cc;
 
-// use std::env;
-use std::path::PathBuf;
+use std::env;
 
 fn main() {
-    // NOTE: Running bindgen in every build is quite time-consuming.
-    // The solution is to comment out the following line after the first time you've generated the bindings.
-    run_bindgen();
-
     run_cc();
 }
 
-// Run bindgen to genernate C bindings in portable.h to Rust.
-fn run_bindgen() {
-    let bindings = bindgen::Builder::default()
-        .header("portable/portable.h")
-        .whitelist_function(".+Port.+")
-        .whitelist_type("^[A-Z].+_t")
-        .generate()
-        .expect("Unable to generate bindings");
-
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from("./src/");
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
-}
-
 // Run cc to compile portable files.
+// The port itself is written in Rust (see src/posix.rs), only the default heap
+// is still provided by C.  It is not needed if one of the heaps in src/heap.rs
+// is picked.
 fn run_cc() {
+    let rust_heap = ["1", "2", "4", "5"]
+        .iter()
+        .any(|scheme| env::var_os(format!("CARGO_FEATURE_PORTHEAP_{}", scheme)).is_some());
+    if rust_heap {
+        return;
+    }
+
     cc::Build::new()
-        .file("portable/port.c")
         .file("portable/heap_3.c")
         .compile("libport.a");
 }
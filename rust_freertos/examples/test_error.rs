@@ -0,0 +1,93 @@
+// Run with `cargo run --example test_error`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use queue_api::Queue;
+use queue_h::QueueError;
+use rust_freertos::*;
+use semaphore::Semaphore;
+use simplelog::*;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::sync::Arc;
+
+// Queue, semaphore and task functions all fail with FreeRtosError, so `?`
+// works across them.
+fn pass_on(
+    queue: &Queue<u32>,
+    mutex: &Semaphore,
+    recursive_mutex: &Semaphore,
+) -> Result<u32, FreeRtosError> {
+    mutex.semaphore_down(pdMS_TO_TICKS!(10))?;
+    recursive_mutex.down_recursive(0)?;
+    recursive_mutex.down_recursive(0)?;
+    let item = queue.receive(0)?;
+    queue.send(item + 1, 0)?;
+    recursive_mutex.up_recursive()?;
+    recursive_mutex.up_recursive()?;
+    mutex.semaphore_up()?;
+    Ok(item)
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let queue0 = Arc::new(Queue::<u32>::new(1));
+    let queue1 = Arc::clone(&queue0);
+
+    let worker = move || {
+        let mutex = Semaphore::new_mutex();
+        let recursive_mutex = Semaphore::create_recursive_mutex();
+
+        queue1.send(1, 0).unwrap();
+        assert_eq!(pass_on(&queue1, &mutex, &recursive_mutex), Ok(1));
+        assert_eq!(queue1.receive(0), Ok(2));
+        // The queue is empty now, which stops pass_on() at the receive.
+        assert_eq!(
+            pass_on(&queue1, &mutex, &recursive_mutex),
+            Err(FreeRtosError::QueueEmpty)
+        );
+
+        // Misuse is reported rather than asserted.
+        assert_eq!(
+            kernel::task_resume_all(),
+            Err(FreeRtosError::SchedulerNotSuspended)
+        );
+        assert_eq!(
+            Semaphore::create_recursive_mutex().up_recursive(),
+            Err(FreeRtosError::NotMutexHolder)
+        );
+
+        // The error can be boxed along with any other error.
+        let error: Box<dyn Error> = Box::new(FreeRtosError::QueueFull);
+        trace!("Boxed error: {}", error);
+
+        // Code written against the old queue error converts both ways.
+        assert_eq!(
+            FreeRtosError::from(QueueError::DelayAborted),
+            FreeRtosError::DelayAborted
+        );
+        assert_eq!(
+            QueueError::try_from(FreeRtosError::QueueFull),
+            Ok(QueueError::QueueFull)
+        );
+        assert_eq!(
+            QueueError::try_from(FreeRtosError::OutOfMemory),
+            Err(FreeRtosError::OutOfMemory)
+        );
+
+        trace!("Test ERROR COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    task_control::TCB::new()
+        .name("Worker")
+        .priority(2)
+        .initialise(worker)
+        .unwrap();
+
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,57 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use event_groups::EventGroup;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::Arc;
+
+const BIT_0: event_groups::EventBits = 1 << 0;
+const BIT_1: event_groups::EventBits = 1 << 1;
+const BIT_2: event_groups::EventBits = 1 << 2;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let event_group0 = Arc::new(EventGroup::new());
+    let event_group1 = Arc::clone(&event_group0);
+
+    let waiter = move || {
+        // Nothing has been set yet, so this times out.
+        let bits = event_group0.wait_bits(BIT_0 | BIT_1, true, true, pdMS_TO_TICKS!(5));
+        assert_eq!(bits & (BIT_0 | BIT_1), 0);
+
+        // Block until the setter sets both bits; they are cleared on exit.
+        let bits = event_group0.wait_bits(BIT_0 | BIT_1, true, true, pdMS_TO_TICKS!(100));
+        trace!("Waiter got bits {:#x}", bits);
+        assert_eq!(bits & (BIT_0 | BIT_1), BIT_0 | BIT_1);
+        assert_eq!(event_group0.get_bits() & (BIT_0 | BIT_1), 0);
+
+        // Rendezvous with the setter.
+        let bits = event_group0.sync(BIT_2, BIT_1 | BIT_2, pdMS_TO_TICKS!(100));
+        assert_eq!(bits & (BIT_1 | BIT_2), BIT_1 | BIT_2);
+        trace!("Test EVENT GROUP COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let setter = move || {
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        event_group1.set_bits(BIT_0);
+        task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        event_group1.set_bits(BIT_1);
+
+        event_group1.sync(BIT_1, BIT_1 | BIT_2, pdMS_TO_TICKS!(100));
+        loop {}
+    };
+
+    let _waiter = task_control::TCB::new()
+        .name("Waiter")
+        .priority(3)
+        .initialise(waiter);
+    let _setter = task_control::TCB::new()
+        .name("Setter")
+        .priority(2)
+        .initialise(setter);
+    kernel::task_start_scheduler();
+}
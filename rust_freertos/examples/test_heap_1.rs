@@ -0,0 +1,28 @@
+// Run with `cargo run --example test_heap_1 --features portHEAP_1`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let total = port::port_get_free_heap_size();
+
+    // Blocks have no header, they are only rounded up to the alignment.
+    let a = port::port_malloc(1000).unwrap();
+    let b = port::port_malloc(1001).unwrap();
+    assert_eq!(b as usize, a as usize + 1000);
+    assert_eq!(port::port_get_free_heap_size(), total - 2008);
+
+    // Freeing gives nothing back.
+    port::port_free(a);
+    assert_eq!(port::port_get_free_heap_size(), total - 2008);
+    assert_eq!(port::port_get_minimum_ever_free_heap_size(), total - 2008);
+
+    assert!(port::port_malloc(total).is_err());
+
+    trace!("Test HEAP 1 COMPLETE!");
+}
@@ -0,0 +1,38 @@
+// Run with `cargo run --example test_heap_2 --features portHEAP_2`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let total = port::port_get_free_heap_size();
+
+    // Each block costs its size plus a 16 byte header.
+    let a = port::port_malloc(1000).unwrap();
+    let b = port::port_malloc(1000).unwrap();
+    let c = port::port_malloc(1000).unwrap();
+    assert_eq!(port::port_get_free_heap_size(), total - 3 * 1016);
+
+    // The two freed blocks are not merged, so the larger block has to be
+    // taken from the rest of the heap.
+    port::port_free(a);
+    port::port_free(b);
+    let d = port::port_malloc(2000).unwrap();
+    assert_ne!(d, a);
+    assert_ne!(d, b);
+
+    // The smallest block that fits is used.
+    let e = port::port_malloc(500).unwrap();
+    assert_eq!(e, a);
+
+    port::port_free(c);
+    port::port_free(d);
+    port::port_free(e);
+    assert_eq!(port::port_get_free_heap_size(), total);
+
+    trace!("Test HEAP 2 COMPLETE!");
+}
@@ -0,0 +1,48 @@
+// Run with `cargo run --example test_heap_3`, without any of the portHEAP_*
+// features.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let before = port::heap_stats();
+
+    let a = port::port_malloc(1000).unwrap();
+    let b = port::port_malloc(1000).unwrap();
+    port::port_free(a);
+    port::port_free(b);
+    // Freeing nothing is not counted.
+    port::port_free(std::ptr::null_mut());
+
+    // malloc() does not tell how much of its heap is free, but the calls are
+    // counted all the same.
+    assert_eq!(
+        port::heap_stats(),
+        port::HeapStats {
+            available_heap_space: None,
+            largest_free_block: None,
+            smallest_free_block: None,
+            number_of_free_blocks: None,
+            minimum_ever_free: None,
+            successful_allocations: before.successful_allocations + 2,
+            successful_frees: before.successful_frees + 2,
+            failed_allocations: before.failed_allocations,
+        }
+    );
+
+    // Task stacks go through port_malloc() too.
+    let _task = task_control::TCB::new()
+        .name("Task")
+        .initialise(|| {})
+        .unwrap();
+    assert_eq!(
+        port::heap_stats().successful_allocations,
+        before.successful_allocations + 3
+    );
+
+    trace!("Test HEAP 3 COMPLETE!");
+}
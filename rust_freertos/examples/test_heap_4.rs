@@ -0,0 +1,64 @@
+// Run with `cargo run --example test_heap_4 --features portHEAP_4`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let total = port::port_get_free_heap_size();
+    assert!(total <= configTOTAL_HEAP_SIZE!());
+
+    // Each block costs its size plus a 16 byte header.
+    let a = port::port_malloc(1000).unwrap();
+    let b = port::port_malloc(1000).unwrap();
+    let c = port::port_malloc(1000).unwrap();
+    assert_eq!(port::port_get_free_heap_size(), total - 3 * 1016);
+
+    // The two freed blocks are merged, so the larger block fits where they
+    // were.
+    port::port_free(a);
+    port::port_free(b);
+    let stats = port::heap_stats();
+    assert_eq!(stats.number_of_free_blocks, Some(2));
+    assert_eq!(stats.smallest_free_block, Some(2032));
+    let d = port::port_malloc(2000).unwrap();
+    assert_eq!(d, a);
+
+    port::port_free(c);
+    port::port_free(d);
+    assert_eq!(port::port_get_free_heap_size(), total);
+    assert_eq!(
+        port::port_get_minimum_ever_free_heap_size(),
+        total - 3 * 1016
+    );
+
+    assert!(port::port_malloc(total).is_err());
+    assert_eq!(
+        port::heap_stats(),
+        port::HeapStats {
+            available_heap_space: Some(total),
+            largest_free_block: Some(total),
+            smallest_free_block: Some(total),
+            number_of_free_blocks: Some(1),
+            minimum_ever_free: Some(total - 3 * 1016),
+            successful_allocations: 4,
+            successful_frees: 4,
+            failed_allocations: 1,
+        }
+    );
+
+    // Task stacks come from the same heap, and are never smaller than the
+    // port needs.
+    let _task = task_control::TCB::new()
+        .name("Task")
+        .stacksize(256)
+        .initialise(|| {})
+        .unwrap();
+    assert!(port::port_get_free_heap_size() <= total - port::portMINIMAL_STACK_BYTES);
+
+    trace!("Test HEAP 4 COMPLETE!");
+}
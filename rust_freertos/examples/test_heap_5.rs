@@ -0,0 +1,38 @@
+// Run with `cargo run --example test_heap_5 --features portHEAP_5`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn region(size: usize) -> &'static mut [u8] {
+    Box::leak(vec![0u8; size].into_boxed_slice())
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    // There is no memory until the regions are defined.
+    assert!(port::port_malloc(16).is_err());
+
+    port::port_define_heap_regions(vec![region(4096), region(2048)]);
+    let total = port::port_get_free_heap_size();
+    assert!(total > 6000 && total <= 6144);
+
+    // There is enough free memory in total for the second block, but not in
+    // a single region.
+    let a = port::port_malloc(3000).unwrap();
+    assert!(port::port_malloc(3000).is_err());
+    let b = port::port_malloc(2000).unwrap();
+
+    port::port_free(a);
+    port::port_free(b);
+    assert_eq!(port::port_get_free_heap_size(), total);
+    assert_eq!(
+        port::port_get_minimum_ever_free_heap_size(),
+        total - 3016 - 2048
+    );
+
+    trace!("Test HEAP 5 COMPLETE!");
+}
@@ -0,0 +1,78 @@
+// Run with `cargo run --example test_heap_accounting --features portHEAP_ACCOUNTING`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use heap_accounting::{AccountingAllocator, TaskHeapUsage};
+use rust_freertos::*;
+use simplelog::*;
+
+#[global_allocator]
+static ALLOCATOR: AccountingAllocator = AccountingAllocator;
+
+fn rust_bytes_of(name: &str) -> usize {
+    heap_accounting::rust_usage_by_task()
+        .iter()
+        .find(|task_usage| task_usage.task_name == Some(name.to_owned()))
+        .map_or(0, |task_usage| task_usage.bytes)
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let worker = || {
+        // The stack of the child is allocated by the worker, rounded up to the
+        // smallest stack a task runs on.
+        let _child = task_control::TCB::new()
+            .name("Child")
+            .priority(1)
+            .stacksize(128)
+            .initialise(|| loop {
+                task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+            })
+            .unwrap();
+
+        let kept = port::port_malloc(100).unwrap();
+        let freed = port::port_malloc(50).unwrap();
+        let _leaked = port::port_malloc(28).unwrap();
+        port::port_free(freed);
+        port::port_free(kept);
+
+        let usage = heap_accounting::usage_by_task();
+        assert!(usage.contains(&TaskHeapUsage {
+            task_name: Some("Worker".to_owned()),
+            blocks: 2,
+            bytes: port::portMINIMAL_STACK_BYTES + 28,
+        }));
+        // The stacks of the tasks created before the scheduler was started.
+        assert!(usage
+            .iter()
+            .any(|task_usage| task_usage.task_name.is_none()));
+        assert!(usage
+            .iter()
+            .all(|task_usage| task_usage.task_name != Some("Child".to_owned())));
+
+        // Rust allocations are accounted for apart from port_malloc().
+        let buffer = vec![0u8; 1024 * 1024];
+        assert!(rust_bytes_of("Worker") >= buffer.len());
+        drop(buffer);
+        assert!(rust_bytes_of("Worker") < 1024 * 1024);
+
+        let mut report = String::new();
+        heap_accounting::leak_report(&mut report).unwrap();
+        trace!("Outstanding blocks:\n{}", report);
+        report.clear();
+        heap_accounting::rust_leak_report(&mut report).unwrap();
+        trace!("Outstanding Rust blocks:\n{}", report);
+
+        trace!("Test HEAP ACCOUNTING COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(2)
+        .initialise(worker);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,59 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let idle_count0 = Arc::new(AtomicUsize::new(0));
+    let idle_count1 = Arc::clone(&idle_count0);
+    let tick_count0 = Arc::new(AtomicUsize::new(0));
+    let tick_count1 = Arc::clone(&tick_count0);
+    let malloc_failed0 = Arc::new(AtomicUsize::new(0));
+    let malloc_failed1 = Arc::clone(&malloc_failed0);
+
+    // A watchdog that is kicked whenever the system is idle.
+    kernel::set_idle_hook(Some(Box::new(move || {
+        idle_count1.fetch_add(1, Ordering::SeqCst);
+    })));
+    kernel::set_tick_hook(Some(Box::new(move || {
+        tick_count1.fetch_add(1, Ordering::SeqCst);
+    })));
+    kernel::set_malloc_failed_hook(Some(Box::new(move || {
+        malloc_failed1.fetch_add(1, Ordering::SeqCst);
+    })));
+
+    let monitor = move || {
+        // Sleeping leaves the processor to the idle task.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert!(idle_count0.load(Ordering::SeqCst) > 0);
+        assert!(tick_count0.load(Ordering::SeqCst) >= 10);
+
+        assert_eq!(
+            port::port_malloc(usize::max_value()),
+            Err(FreeRtosError::OutOfMemory)
+        );
+        assert_eq!(malloc_failed0.load(Ordering::SeqCst), 1);
+
+        // Removing a hook stops the calls.
+        kernel::set_tick_hook(None);
+        let ticks = tick_count0.load(Ordering::SeqCst);
+        task_timemanager::task_delay(pdMS_TO_TICKS!(5));
+        assert_eq!(tick_count0.load(Ordering::SeqCst), ticks);
+
+        trace!("Test HOOKS COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _monitor = task_control::TCB::new()
+        .name("Monitor")
+        .priority(3)
+        .initialise(monitor);
+    kernel::task_start_scheduler();
+}
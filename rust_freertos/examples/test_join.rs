@@ -0,0 +1,63 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+
+fn factorial(n: u64) -> u64 {
+    (1..=n).product()
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let supervisor = move || {
+        // Fan out: one task per factorial, of a lower priority than ours.
+        let mut join_handles: Vec<_> = (1..9)
+            .map(|n| {
+                let (_, join_handle) = task_control::TCB::new()
+                    .name("Factorial")
+                    .priority(1)
+                    .spawn(move || {
+                        task_timemanager::task_delay(pdMS_TO_TICKS!(n));
+                        factorial(n)
+                    })
+                    .unwrap();
+                join_handle
+            })
+            .collect();
+
+        // None of them has run yet.
+        assert_eq!(join_handles[0].join(0), Err(FreeRtosError::Timeout));
+
+        // Fan in.
+        let results: Vec<u64> = join_handles
+            .iter_mut()
+            .map(|join_handle| join_handle.join(pdMS_TO_TICKS!(100)).unwrap())
+            .collect();
+        assert_eq!(results, (1..9).map(factorial).collect::<Vec<_>>());
+        // The value can only be taken once.
+        assert_eq!(join_handles[0].join(0), Err(FreeRtosError::TaskNotFound));
+
+        // A task deleted before it returns has no value to give.
+        let (task, mut join_handle) = task_control::TCB::new()
+            .name("Deleted")
+            .priority(1)
+            .spawn(|| String::from("never returned"))
+            .unwrap();
+        task_control::task_delete(Some(task));
+        assert_eq!(join_handle.join(0), Err(FreeRtosError::TaskNotFound));
+
+        trace!("Test JOIN COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(2)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,60 @@
+// Run with `cargo run --example test_mock_port --features portMOCK`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use mock::PortCall;
+use rust_freertos::*;
+use simplelog::*;
+use task_control::task_state;
+
+fn count(calls: &[PortCall], call: PortCall) -> usize {
+    calls.iter().filter(|c| **c == call).count()
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    // The task never runs, the port only prepares its stack.
+    let worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(1)
+        .initialise(|| loop {
+            taskYIELD!();
+        })
+        .unwrap();
+    let calls = mock::take_calls();
+    assert_eq!(count(&calls, PortCall::InitialiseStack), 1);
+    assert!(calls.contains(&PortCall::AddTaskHandle("Worker".to_owned())));
+    assert_eq!(
+        count(&calls, PortCall::EnterCritical),
+        count(&calls, PortCall::ExitCritical)
+    );
+    assert_eq!(mock::critical_nesting(), 0);
+
+    // Returns straight away, the timer task has the highest priority and is
+    // the one the kernel started.
+    kernel::task_start_scheduler();
+    let calls = mock::take_calls();
+    assert!(calls.contains(&PortCall::ConfigureTimerForRunTimeStats));
+    assert_eq!(calls.last(), Some(&PortCall::StartScheduler));
+    assert!(mock::interrupts_enabled());
+    let timer_task = task_api::task_get_handle("Tmr Svc").unwrap();
+    assert_eq!(timer_task.state(), task_state::running);
+    assert_eq!(worker.state(), task_state::ready);
+
+    // Dropping the priority of the running task below Worker's makes the
+    // kernel yield to Worker.
+    task_api::task_priority_set(Some(timer_task.clone()), 0);
+    let calls = mock::take_calls();
+    assert_eq!(count(&calls, PortCall::Yield), 1);
+    assert_eq!(mock::critical_nesting(), 0);
+    assert_eq!(worker.state(), task_state::running);
+    assert_eq!(timer_task.state(), task_state::ready);
+
+    kernel::task_end_scheduler();
+    let calls = mock::take_calls();
+    assert!(calls.ends_with(&[PortCall::DisableInterrupts, PortCall::EndScheduler]));
+
+    trace!("Test MOCK PORT COMPLETE!");
+}
@@ -0,0 +1,56 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use port::UBaseType;
+use queue_api::Queue;
+use rust_freertos::*;
+use simplelog::*;
+
+const QUEUE_LENGTH: UBaseType = 4;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let task = move || {
+        let queue = Queue::<u32>::new(QUEUE_LENGTH);
+
+        // Every round moves the read and write positions on by a whole queue,
+        // so items sent to either end wrap around the storage.
+        for round in 0..5 {
+            let base = round * 10;
+            queue.send_to_back(base + 2, 0).unwrap();
+            queue.send_to_back(base + 3, 0).unwrap();
+            queue.send_to_front(base + 1, 0).unwrap();
+            queue.send_to_front(base, 0).unwrap();
+            assert!(queue.send_to_front(base + 4, 0).is_err());
+
+            for item in base..base + 4 {
+                assert_eq!(queue.receive(0).unwrap(), item);
+            }
+            assert!(queue.receive(0).is_err());
+        }
+
+        // Items that have not been read yet stay where they are while others
+        // are sent to the front and read.
+        queue.send_to_back(1, 0).unwrap();
+        queue.send_to_back(2, 0).unwrap();
+        queue.send_to_front(0, 0).unwrap();
+        assert_eq!(queue.receive(0).unwrap(), 0);
+        queue.send_to_back(3, 0).unwrap();
+        queue.send_to_front(0, 0).unwrap();
+        for item in 0..4 {
+            assert_eq!(queue.receive(0).unwrap(), item);
+        }
+
+        trace!("Test QUEUE ORDER COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _task = task_control::TCB::new()
+        .name("Task")
+        .priority(2)
+        .initialise(task);
+    kernel::task_start_scheduler();
+}
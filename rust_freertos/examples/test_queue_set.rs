@@ -0,0 +1,95 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use port::UBaseType;
+use projdefs::FreeRtosError;
+use queue_api::{Queue, QueueSet};
+use rust_freertos::*;
+use semaphore::Semaphore;
+use simplelog::*;
+use std::sync::Arc;
+
+const COMMAND_QUEUE_LENGTH: UBaseType = 4;
+const DATA_QUEUE_LENGTH: UBaseType = 4;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let commands0 = Arc::new(Queue::<u32>::new(COMMAND_QUEUE_LENGTH));
+    let commands1 = Arc::clone(&commands0);
+    let data0 = Arc::new(Queue::<u32>::new(DATA_QUEUE_LENGTH));
+    let data1 = Arc::clone(&data0);
+    let shutdown0 = Arc::new(Semaphore::create_binary());
+    let shutdown1 = Arc::clone(&shutdown0);
+
+    // Room for every item of both queues plus the semaphore.
+    let set = QueueSet::new(COMMAND_QUEUE_LENGTH + DATA_QUEUE_LENGTH + 1);
+
+    // A semaphore can only be added while it cannot be taken.
+    shutdown0.semaphore_down(0).unwrap();
+    let command_handle = set.add(&*commands0).unwrap();
+    let data_handle = set.add(&*data0).unwrap();
+    let shutdown_handle = set.add(&*shutdown0).unwrap();
+    // A member can only belong to one set.
+    assert_eq!(set.add(&*commands0), Err(FreeRtosError::AlreadyInSet));
+
+    let gateway = move || {
+        let mut commands_seen = 0;
+        let mut data_seen = 0;
+        loop {
+            match set.select(pdMS_TO_TICKS!(100)) {
+                Ok(handle) if handle == command_handle => {
+                    let command = commands0.receive(0).unwrap();
+                    trace!("Gateway got command {}", command);
+                    commands_seen += 1;
+                }
+                Ok(handle) if handle == data_handle => {
+                    let value = data0.receive(0).unwrap();
+                    trace!("Gateway got data {}", value);
+                    data_seen += 1;
+                }
+                Ok(handle) if handle == shutdown_handle => {
+                    shutdown0.semaphore_down(0).unwrap();
+                    trace!("Gateway shutting down");
+                    break;
+                }
+                Ok(handle) => panic!("Unknown queue set member {:?}", handle),
+                Err(error) => panic!("Gateway failed to wait for the queue set: {}", error),
+            }
+        }
+        assert_eq!(commands_seen, 2);
+        assert_eq!(data_seen, 3);
+
+        // Every event has been consumed, so the members can leave the set.
+        set.remove(&*commands0).unwrap();
+        set.remove(&*data0).unwrap();
+        set.remove(&*shutdown0).unwrap();
+        assert_eq!(set.remove(&*data0), Err(FreeRtosError::NotInSet));
+        assert_eq!(set.select(0), Err(FreeRtosError::QueueEmpty));
+        trace!("Test QUEUE SET COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let producer = move || {
+        commands1.send(1, pdMS_TO_TICKS!(10)).unwrap();
+        data1.send(100, pdMS_TO_TICKS!(10)).unwrap();
+        data1.send(101, pdMS_TO_TICKS!(10)).unwrap();
+        task_timemanager::task_delay(pdMS_TO_TICKS!(5));
+        commands1.send(2, pdMS_TO_TICKS!(10)).unwrap();
+        data1.send(102, pdMS_TO_TICKS!(10)).unwrap();
+        task_timemanager::task_delay(pdMS_TO_TICKS!(5));
+        shutdown1.semaphore_up().unwrap();
+        loop {}
+    };
+
+    let _gateway = task_control::TCB::new()
+        .name("Gateway")
+        .priority(3)
+        .initialise(gateway);
+    let _producer = task_control::TCB::new()
+        .name("Producer")
+        .priority(2)
+        .initialise(producer);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,72 @@
+// Run with `cargo run --example test_sim --features portVIRTUAL_TIME`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<(port::TickType, &'static str)>>>;
+
+// Record the tick at which the task runs, every `period` ticks.
+fn periodic(log: &Log, name: &'static str, period: port::TickType) -> impl Fn() {
+    let log = Arc::clone(log);
+    move || loop {
+        log.lock()
+            .unwrap()
+            .push((kernel::task_get_tick_count(), name));
+        task_timemanager::task_delay(period);
+    }
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let log: Log = Arc::new(Mutex::new(Vec::new()));
+    task_control::TCB::new()
+        .name("Fast")
+        .priority(2)
+        .initialise(periodic(&log, "Fast", 2))
+        .unwrap();
+    task_control::TCB::new()
+        .name("Slow")
+        .priority(1)
+        .initialise(periodic(&log, "Slow", 3))
+        .unwrap();
+
+    // Returns once both tasks are blocked, no time has passed yet.
+    kernel::task_start_scheduler();
+    assert_eq!(*log.lock().unwrap(), vec![(0, "Fast"), (0, "Slow")]);
+
+    sim::advance_ticks(6);
+    assert_eq!(sim::ticks(), 6);
+    assert_eq!(kernel::task_get_tick_count(), 6);
+    // Both wake up at tick 6, the one of the higher priority runs first.
+    assert_eq!(
+        log.lock().unwrap()[2..],
+        [
+            (2, "Fast"),
+            (3, "Slow"),
+            (4, "Fast"),
+            (6, "Fast"),
+            (6, "Slow")
+        ]
+    );
+
+    // A task created by the driver runs before the driver goes on.
+    task_control::TCB::new()
+        .name("Stopper")
+        .priority(3)
+        .initialise(|| {
+            task_timemanager::task_delay(1);
+            kernel::task_end_scheduler();
+        })
+        .unwrap();
+
+    // The clock stops with the scheduler.
+    sim::advance_ticks(5);
+    assert_eq!(sim::ticks(), 7);
+    assert_eq!(log.lock().unwrap().len(), 7);
+    trace!("Test SIM COMPLETE!");
+}
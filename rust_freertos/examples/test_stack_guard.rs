@@ -0,0 +1,78 @@
+// Run with `cargo run --example test_stack_guard --features portSTACK_GUARD_PAGE`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::FromRawFd;
+use std::ptr;
+
+// Every call keeps a buffer on the stack, so the recursion eventually runs
+// into the guard page.
+fn recurse(depth: u64) -> u64 {
+    if depth == u64::max_value() {
+        return 0;
+    }
+    let buffer = [depth; 128];
+    let first = unsafe { ptr::read_volatile(&buffer[0]) };
+    recurse(depth + 1) + first
+}
+
+// The process cannot go on after hitting the guard page, so the task runs in
+// a child process.
+fn run_recursive() -> ! {
+    let recursive = move || {
+        let result = recurse(0);
+        panic!("Recursion returned {}", result);
+    };
+
+    task_control::TCB::new()
+        .name("Recursive")
+        .priority(3)
+        .stacksize(16 * 1024)
+        .initialise(recursive)
+        .unwrap();
+    kernel::task_start_scheduler();
+    unreachable!();
+}
+
+fn main() {
+    // Fork before any thread is started, with the stderr of the child going
+    // to a pipe.
+    let mut pipe = [0; 2];
+    assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+    let child = unsafe { libc::fork() };
+    assert!(child >= 0);
+    if child == 0 {
+        unsafe {
+            libc::dup2(pipe[1], libc::STDERR_FILENO);
+            libc::close(pipe[0]);
+            libc::close(pipe[1]);
+        }
+        run_recursive();
+    }
+    unsafe {
+        libc::close(pipe[1]);
+    }
+
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let mut report = String::new();
+    let mut child_stderr = unsafe { File::from_raw_fd(pipe[0]) };
+    child_stderr.read_to_string(&mut report).unwrap();
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+    trace!(
+        "The child ended with status {:#x} and wrote {:?}",
+        status,
+        report
+    );
+
+    assert!(report.contains("Task Recursive hit the guard page of its stack.\n"));
+    assert!(libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGABRT);
+
+    trace!("Test STACK GUARD COMPLETE!");
+}
@@ -0,0 +1,149 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// A whole number of pages, so the stack is not made any larger.
+const STACK_WORDS: usize = 16 * 1024;
+// The words every call keeps on the stack.
+const BUFFER_WORDS: usize = 128;
+const DEPTH: u64 = 32;
+
+// The exit status of the child whose task overflows its stack.
+const OVERFLOW_EXIT_CODE: i32 = 42;
+
+// Every call keeps a buffer on the stack, so the deeper the recursion the
+// more of the stack is used, until it overflows.
+fn recurse(depth: u64) -> u64 {
+    if depth == 0 {
+        return 0;
+    }
+    let buffer = [depth; BUFFER_WORDS];
+    let first = unsafe { ptr::read_volatile(&buffer[0]) };
+    recurse(depth - 1) + first
+}
+
+// Yields at every level, so the overflow is checked for on the way down.
+// The frames are small, so little is written below the stack before the
+// check notices.
+fn recurse_and_yield(depth: u64) -> u64 {
+    if depth == 0 {
+        return 0;
+    }
+    let buffer = [depth; 4];
+    let first = unsafe { ptr::read_volatile(&buffer[0]) };
+    taskYIELD!();
+    recurse_and_yield(depth - 1) + first
+}
+
+// The process cannot go on after a task has overflowed its stack, so the
+// overflow happens in a child process, which the hook ends.
+fn run_overflow() -> ! {
+    kernel::set_stack_overflow_hook(Some(Box::new(|task| {
+        if task.get_name() != "Overflow" {
+            std::process::abort();
+        }
+        unsafe { libc::_exit(OVERFLOW_EXIT_CODE) }
+    })));
+
+    let overflow = || {
+        let result = recurse_and_yield(u64::max_value());
+        panic!("Recursion returned {}", result);
+    };
+    task_control::TCB::new()
+        .name("Overflow")
+        .priority(1)
+        .initialise(overflow)
+        .unwrap();
+    kernel::task_start_scheduler();
+    unreachable!();
+}
+
+fn main() {
+    // Fork before any thread is started.
+    let child = unsafe { libc::fork() };
+    assert!(child >= 0);
+    if child == 0 {
+        run_overflow();
+    }
+
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+    let exited = libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == OVERFLOW_EXIT_CODE;
+    // Hitting the guard page aborts the process instead.
+    let aborted = cfg!(feature = "portSTACK_GUARD_PAGE")
+        && libc::WIFSIGNALED(status)
+        && libc::WTERMSIG(status) == libc::SIGABRT;
+    trace!("The overflowing child ended with status {:#x}", status);
+    assert!(exited || aborted);
+
+    let overflowed0 = Arc::new(AtomicBool::new(false));
+    let overflowed1 = Arc::clone(&overflowed0);
+    kernel::set_stack_overflow_hook(Some(Box::new(move |task| {
+        error!("Task {} overflowed its stack!", task.get_name());
+        overflowed1.store(true, Ordering::SeqCst);
+    })));
+
+    let go_deep0 = Arc::new(AtomicBool::new(false));
+    let go_deep1 = Arc::clone(&go_deep0);
+    let result0 = Arc::new(AtomicUsize::new(0));
+    let result1 = Arc::clone(&result0);
+    let worker = move || {
+        while !go_deep1.load(Ordering::SeqCst) {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        }
+        result1.store(recurse(DEPTH) as usize, Ordering::SeqCst);
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        }
+    };
+
+    let worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(2)
+        .stacksize(STACK_WORDS as port::UBaseType)
+        .initialise(worker)
+        .unwrap();
+
+    let monitor = move || {
+        // Let the worker go through a few context switches.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        let shallow = worker.stack_high_water_mark();
+        trace!("Worker stack high water mark: {} words", shallow);
+        assert!(shallow > 0 && (shallow as usize) < STACK_WORDS);
+
+        // The recursion uses up at least its buffers on the stack.
+        go_deep0.store(true, Ordering::SeqCst);
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert_eq!(
+            result0.load(Ordering::SeqCst) as u64,
+            DEPTH * (DEPTH + 1) / 2
+        );
+        let deep = worker.stack_high_water_mark();
+        trace!("Worker stack high water mark: {} words", deep);
+        assert!(deep > 0 && deep < shallow);
+        assert!(deep as usize + DEPTH as usize * BUFFER_WORDS <= STACK_WORDS);
+        assert_eq!(
+            task_api::task_get_stack_high_water_mark(Some(worker.clone())),
+            deep
+        );
+        assert!(!overflowed0.load(Ordering::SeqCst));
+
+        trace!("Test STACK OVERFLOW COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _monitor = task_control::TCB::new()
+        .name("Monitor")
+        .priority(3)
+        .initialise(monitor);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,108 @@
+// Run with `cargo run --example test_static_allocation --features configSUPPORT_STATIC_ALLOCATION`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use port::StackType;
+use projdefs::FreeRtosError;
+use queue_api::Queue;
+use queue_h::QueueType;
+use rust_freertos::*;
+use semaphore::{Semaphore, SemaphoreSlot};
+use simplelog::*;
+use std::mem;
+use std::sync::Arc;
+
+// The tasks run on these stacks, so they are as large as the port needs.
+const STACK_SIZE: usize = port::portMINIMAL_STACK_BYTES / mem::size_of::<StackType>();
+
+// Every buffer is handed out before the scheduler starts, as an application
+// would put them in static memory.
+fn stack(size: usize) -> &'static mut [StackType] {
+    Box::leak(vec![0; size].into_boxed_slice())
+}
+
+fn semaphore_slots(count: usize) -> &'static mut [SemaphoreSlot] {
+    Box::leak((0..count).map(|_| SemaphoreSlot::EMPTY).collect())
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    kernel::set_idle_task_stack(stack(STACK_SIZE));
+    timers::set_timer_task_stack(stack(STACK_SIZE));
+
+    let queue0 = Arc::new(Queue::<u32>::new_with_storage(Box::leak(Box::new([0; 4]))));
+    let queue1 = Arc::clone(&queue0);
+    let done0 = Arc::new(Semaphore::new_with_storage(
+        QueueType::BinarySemaphore,
+        semaphore_slots(1),
+    ));
+    let done1 = Arc::clone(&done0);
+    let mutex = Semaphore::new_with_storage(QueueType::Mutex, semaphore_slots(1));
+    let counting = Semaphore::new_with_storage(QueueType::CountingSemaphore, semaphore_slots(3));
+
+    // Taken until the consumer has seen every item.
+    done0.semaphore_down(0).unwrap();
+
+    let producer = move || {
+        // More items than the queue holds, so the producer has to wait for
+        // the consumer.
+        for item in 1..=10 {
+            queue0.send(item, pdMS_TO_TICKS!(100)).unwrap();
+        }
+        done0.semaphore_down(pdMS_TO_TICKS!(1000)).unwrap();
+
+        // The idle task runs on the stack it was given, which is still filled
+        // with the pattern task creation wrote beyond what the task used.
+        let idle_task = task_api::task_get_idle_task_handle();
+        let high_water_mark = task_api::task_get_stack_high_water_mark(Some(idle_task)) as usize;
+        assert!(high_water_mark > 0 && high_water_mark < STACK_SIZE);
+
+        trace!("Test STATIC ALLOCATION COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let consumer = move || {
+        let mut sum = 0;
+        for _ in 1..=10 {
+            let item = queue1.receive(pdMS_TO_TICKS!(100)).unwrap();
+            mutex.semaphore_down(pdMS_TO_TICKS!(10)).unwrap();
+            sum += item;
+            mutex.semaphore_up().unwrap();
+        }
+        assert_eq!(sum, 55);
+
+        // The counting semaphore holds as many counts as its buffer has
+        // slots.
+        for _ in 0..3 {
+            counting.semaphore_down(0).unwrap();
+        }
+        assert!(counting.semaphore_down(0).is_err());
+        counting.semaphore_up().unwrap();
+        counting.semaphore_down(0).unwrap();
+
+        // The consumer returns, its stack is left alone when it is deleted.
+        done1.semaphore_up().unwrap();
+    };
+
+    task_control::TCB::new()
+        .name("Producer")
+        .priority(2)
+        .initialise_with_stack(producer, stack(STACK_SIZE))
+        .unwrap();
+    task_control::TCB::new()
+        .name("Consumer")
+        .priority(2)
+        .initialise_with_stack(consumer, stack(STACK_SIZE))
+        .unwrap();
+
+    // A task cannot run on a stack smaller than the port needs.
+    let result = task_control::TCB::new()
+        .name("Small")
+        .initialise_with_stack(|| {}, stack(STACK_SIZE / 2));
+    assert_eq!(result.err(), Some(FreeRtosError::StackTooSmall));
+
+    kernel::task_start_scheduler();
+}
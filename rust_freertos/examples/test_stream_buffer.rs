@@ -0,0 +1,74 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::Arc;
+use stream_buffer::{MessageBuffer, StreamBuffer};
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    // The reader is only woken once 4 bytes are in the stream buffer.
+    let stream0 = Arc::new(StreamBuffer::new(16, 4));
+    let stream1 = Arc::clone(&stream0);
+    let messages0 = Arc::new(MessageBuffer::new(64));
+    let messages1 = Arc::clone(&messages0);
+
+    let reader = move || {
+        let mut bytes = [0u8; 16];
+        let received = stream0.receive(&mut bytes, pdMS_TO_TICKS!(100));
+        trace!("Reader got {} bytes from the stream buffer", received);
+        assert_eq!(&bytes[..received], b"abcd");
+
+        // Both frames were sent while the reader waited for the stream
+        // buffer.  They are received whole, one at a time.
+        let mut frame = [0u8; 32];
+        let length = messages0.receive(&mut frame, pdMS_TO_TICKS!(100));
+        assert_eq!(&frame[..length], b"first frame");
+        assert_eq!(messages0.next_length_bytes(), 2);
+        let length = messages0.receive(&mut frame, pdMS_TO_TICKS!(100));
+        assert_eq!(&frame[..length], b"ok");
+        assert!(messages0.is_empty());
+        // Nothing waits for the message buffer any more.
+        messages0.reset().unwrap();
+
+        trace!("Test STREAM BUFFER COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let writer = move || {
+        // The reader only waits for the stream buffer yet, so sending the
+        // frames does not wake it.
+        assert_eq!(messages1.send(b"first frame", 0), 11);
+        assert_eq!(messages1.send(b"ok", 0), 2);
+
+        // The reader is blocked on the stream buffer.
+        assert_eq!(stream1.reset(), Err(FreeRtosError::TasksBlocked));
+        assert_eq!(
+            stream1.set_trigger_level(17),
+            Err(FreeRtosError::InvalidTriggerLevel)
+        );
+        stream1.set_trigger_level(4).unwrap();
+
+        for byte in b"abcd" {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+            assert_eq!(stream1.send(&[*byte], 0), 1);
+        }
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        }
+    };
+
+    let _reader = task_control::TCB::new()
+        .name("Reader")
+        .priority(3)
+        .initialise(reader);
+    let _writer = task_control::TCB::new()
+        .name("Writer")
+        .priority(2)
+        .initialise(writer);
+    kernel::task_start_scheduler();
+}
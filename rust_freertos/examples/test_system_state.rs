@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use task_control::task_state;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let monitor = move || {
+        // Give the other tasks a chance to run first.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(5));
+
+        let (tasks, total_run_time) = kernel::task_get_system_state();
+        trace!("Total run time: {}", total_run_time);
+        for status in &tasks {
+            trace!(
+                "{:<10} #{} {:?} priority {}/{} run time {} stack {}",
+                status.name,
+                status.task_number,
+                status.current_state,
+                status.current_priority,
+                status.base_priority,
+                status.run_time_counter,
+                status.stack_high_water_mark
+            );
+            assert!(status.stack_high_water_mark > 0);
+        }
+
+        let find = |name: &str| tasks.iter().find(|status| status.name == name).unwrap();
+        match find("Monitor").current_state {
+            task_state::running => {}
+            state => panic!("Monitor is {:?}", state),
+        }
+        match find("Sleeper").current_state {
+            task_state::blocked => {}
+            state => panic!("Sleeper is {:?}", state),
+        }
+        match find("Idle").current_state {
+            task_state::ready => {}
+            state => panic!("Idle is {:?}", state),
+        }
+        assert_eq!(find("Sleeper").current_priority, 2);
+        assert_eq!(find("Idle").current_priority, 0);
+
+        trace!("Test SYSTEM STATE COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let sleeper = move || loop {
+        task_timemanager::task_delay(pdMS_TO_TICKS!(100));
+    };
+
+    let _monitor = task_control::TCB::new()
+        .name("Monitor")
+        .priority(3)
+        .initialise(monitor);
+    let _sleeper = task_control::TCB::new()
+        .name("Sleeper")
+        .priority(2)
+        .initialise(sleeper);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,130 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use queue_api::Queue;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use task_control::{task_abort_delay, task_state};
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let queue = Arc::new(Queue::<u32>::new(1));
+    let aborted0 = Arc::new(AtomicUsize::new(0));
+    let aborted1 = Arc::clone(&aborted0);
+    let aborted2 = Arc::clone(&aborted0);
+    let aborted3 = Arc::clone(&aborted0);
+    let aborted4 = Arc::clone(&aborted0);
+
+    let receiver = move || {
+        // Without the abort this would wait forever.
+        assert_eq!(
+            queue.receive(port::portMAX_DELAY),
+            Err(FreeRtosError::DelayAborted)
+        );
+        aborted1.fetch_add(1, Ordering::SeqCst);
+        // A later wait times out as usual.
+        assert_eq!(queue.receive(2), Err(FreeRtosError::QueueEmpty));
+        aborted1.fetch_add(1, Ordering::SeqCst);
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(100));
+        }
+    };
+    let notified = move || {
+        assert_eq!(
+            task_notify::task_notify_wait(0, 0, pdMS_TO_TICKS!(1000)),
+            Err(FreeRtosError::DelayAborted)
+        );
+        aborted2.fetch_add(1, Ordering::SeqCst);
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(100));
+        }
+    };
+    // Waiting for a notification without a timeout puts the task in the
+    // suspended list, with no event list item to tell it is blocked.
+    let listener = move || {
+        assert_eq!(
+            task_notify::task_notify_wait(0, 0, port::portMAX_DELAY),
+            Err(FreeRtosError::DelayAborted)
+        );
+        aborted4.fetch_add(1, Ordering::SeqCst);
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(100));
+        }
+    };
+    let sleeper = move || {
+        task_timemanager::task_delay(pdMS_TO_TICKS!(1000));
+        // The abort of the delay does not carry over to a later call that
+        // does not block.
+        assert_eq!(
+            task_notify::task_notify_wait(0, 0, 0),
+            Err(FreeRtosError::Timeout)
+        );
+        aborted3.fetch_add(1, Ordering::SeqCst);
+        loop {}
+    };
+
+    let receiver = task_control::TCB::new()
+        .name("Receiver")
+        .priority(2)
+        .initialise(receiver)
+        .unwrap();
+    let notified = task_control::TCB::new()
+        .name("Notified")
+        .priority(2)
+        .initialise(notified)
+        .unwrap();
+    let listener = task_control::TCB::new()
+        .name("Listener")
+        .priority(2)
+        .initialise(listener)
+        .unwrap();
+    let sleeper = task_control::TCB::new()
+        .name("Sleeper")
+        .priority(1)
+        .initialise(sleeper)
+        .unwrap();
+
+    let supervisor = move || {
+        // Let the other tasks block first.  The sleeper has the lowest
+        // priority, so it may take more than a tick.
+        while [&receiver, &notified, &listener, &sleeper]
+            .iter()
+            .any(|task| task.state() != task_state::blocked)
+        {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        }
+
+        // The calling task is running, not blocked.
+        let me = task_api::task_get_handle("Supervisor").unwrap();
+        assert_eq!(task_abort_delay(me), Err(FreeRtosError::NotBlocked));
+
+        task_abort_delay(receiver.clone()).unwrap();
+        task_abort_delay(notified.clone()).unwrap();
+        task_abort_delay(listener.clone()).unwrap();
+        task_abort_delay(sleeper.clone()).unwrap();
+        // None of them runs before we block, so none is blocked any more.
+        assert_eq!(receiver.state(), task_state::ready);
+        assert_eq!(
+            task_abort_delay(receiver.clone()),
+            Err(FreeRtosError::NotBlocked)
+        );
+
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert_eq!(aborted0.load(Ordering::SeqCst), 5);
+        assert_eq!(sleeper.state(), task_state::ready);
+
+        trace!("Test TASK ABORT DELAY COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(3)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+}
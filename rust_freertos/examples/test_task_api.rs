@@ -0,0 +1,83 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use port::{BaseType, CVoidPointer};
+use rust_freertos::*;
+use semaphore::Semaphore;
+use simplelog::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use task_api::*;
+
+fn tag_hook(_: CVoidPointer) -> BaseType {
+    42
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let mutex0 = Arc::new(Semaphore::new_mutex());
+    let mutex1 = Arc::clone(&mutex0);
+    let raised0 = Arc::new(AtomicBool::new(false));
+    let raised1 = Arc::clone(&raised0);
+
+    let controller = move || {
+        assert!(task_get_handle("Nobody").is_none());
+        let worker = task_get_handle("Worker").unwrap();
+        assert_eq!(worker.get_name(), "Worker");
+        assert_eq!(task_priority_get(Some(worker.clone())), 2);
+
+        let idle = task_get_idle_task_handle();
+        assert_eq!(idle.get_name(), "Idle");
+        assert_eq!(task_priority_get(Some(idle)), 0);
+
+        assert!(task_get_application_task_tag(None).is_none());
+        task_set_application_task_tag(None, Some(tag_hook));
+        assert!(task_get_application_task_tag(None).is_some());
+        let ret = task_call_application_task_hook(None, std::ptr::null_mut());
+        assert_eq!(ret, 42);
+
+        // Let the worker take the mutex, then block on it so the worker
+        // inherits our priority.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        mutex0.semaphore_down(pdMS_TO_TICKS!(100)).unwrap();
+        // The worker lowered its base priority while it held the mutex, so it
+        // dropped straight to the new priority when it gave the mutex back.
+        assert_eq!(task_priority_get(Some(worker.clone())), 1);
+        mutex0.semaphore_up().unwrap();
+
+        // Raising the worker above us preempts us immediately.
+        task_priority_set(Some(worker), 4);
+        assert!(raised0.load(Ordering::SeqCst));
+        trace!("Test TASK API COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let worker = move || {
+        mutex1.semaphore_down(pdMS_TO_TICKS!(10)).unwrap();
+        task_timemanager::task_delay(pdMS_TO_TICKS!(5));
+        // The controller is waiting for the mutex.
+        assert_eq!(task_priority_get(None), 3);
+        task_priority_set(None, 1);
+        // Only the base priority changed, the inherited one is kept.
+        assert_eq!(task_priority_get(None), 3);
+        mutex1.semaphore_up().unwrap();
+
+        // Only runs again once the controller raised our priority.
+        assert_eq!(task_priority_get(None), 4);
+        raised1.store(true, Ordering::SeqCst);
+        task_priority_set(None, 1);
+        loop {}
+    };
+
+    let _controller = task_control::TCB::new()
+        .name("Controller")
+        .priority(3)
+        .initialise(controller);
+    let _worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(2)
+        .initialise(worker);
+    kernel::task_start_scheduler();
+}
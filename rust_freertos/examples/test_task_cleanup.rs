@@ -0,0 +1,99 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use task_control::task_state;
+
+const WORKERS: usize = 8;
+
+// The threads of the process, every task runs on one of them.
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let supervisor = move || {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let tasks_before = kernel::task_get_system_state().0.len();
+        let threads_before = thread_count();
+
+        for _ in 0..WORKERS {
+            let finished = Arc::clone(&finished);
+            let worker = move || {
+                finished.fetch_add(1, Ordering::SeqCst);
+                task_control::task_delete(None);
+            };
+            // A higher priority, so each worker runs and deletes itself at once.
+            task_control::TCB::new()
+                .name("Worker")
+                .priority(3)
+                .initialise(worker)
+                .unwrap();
+        }
+        assert_eq!(finished.load(Ordering::SeqCst), WORKERS);
+
+        // Until the idle task runs, the workers wait for their clean up.
+        let (tasks, _) = kernel::task_get_system_state();
+        let deleted = tasks
+            .iter()
+            .filter(|status| status.current_state == task_state::deleted)
+            .count();
+        assert_eq!(deleted, WORKERS);
+
+        // Let the idle task free them.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert_eq!(kernel::task_get_system_state().0.len(), tasks_before);
+        assert!(task_api::task_get_handle("Worker").is_none());
+        // Their threads have exited as well.
+        assert_eq!(thread_count(), threads_before);
+
+        // A task deleted before it ever ran gives its closure back at once.
+        let token = Arc::new(());
+        let unstarted_token = Arc::clone(&token);
+        let unstarted = task_control::TCB::new()
+            .name("Unstarted")
+            .priority(1)
+            .initialise(move || {
+                let _token = unstarted_token;
+                loop {}
+            })
+            .unwrap();
+        assert_eq!(Arc::strong_count(&token), 2);
+        task_control::task_delete(Some(unstarted));
+        assert_eq!(Arc::strong_count(&token), 1);
+        assert_eq!(kernel::task_get_system_state().0.len(), tasks_before);
+
+        // A task deleted while it is blocked drops what it holds as its
+        // thread exits.
+        let blocked_token = Arc::clone(&token);
+        let blocked = task_control::TCB::new()
+            .name("Blocked")
+            .priority(3)
+            .initialise(move || {
+                let _token = blocked_token;
+                task_timemanager::task_delay(port::portMAX_DELAY);
+            })
+            .unwrap();
+        assert_eq!(Arc::strong_count(&token), 2);
+        task_control::task_delete(Some(blocked));
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert_eq!(Arc::strong_count(&token), 1);
+        assert_eq!(thread_count(), threads_before);
+
+        trace!("Test TASK CLEANUP COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(2)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+}
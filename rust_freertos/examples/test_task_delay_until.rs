@@ -0,0 +1,53 @@
+// Run with `cargo run --example test_task_delay_until --features portVIRTUAL_TIME`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use task_timemanager::task_delay_until;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let period = pdMS_TO_TICKS!(10);
+    let done0 = Arc::new(AtomicBool::new(false));
+    let done1 = Arc::clone(&done0);
+
+    // On virtual time no tick passes while the task runs, so the task wakes
+    // at exactly the tick it asked for.
+    let periodic = move || {
+        let mut last_wake_time = kernel::task_get_tick_count();
+        for i in 0..5 {
+            // Each cycle ends exactly one period after the previous one,
+            // however long the work in between took.
+            task_timemanager::task_delay(i);
+            assert!(task_delay_until(&mut last_wake_time, period));
+            assert_eq!(kernel::task_get_tick_count(), last_wake_time);
+            trace!("Periodic task woke at {}", last_wake_time);
+        }
+
+        // Overrunning the period makes the next call return immediately.
+        task_timemanager::task_delay(period + 2);
+        assert!(!task_delay_until(&mut last_wake_time, period));
+        trace!("Periodic task missed the deadline at {}", last_wake_time);
+
+        // The wake time still advances by whole periods, so the task catches
+        // up with its original schedule.
+        assert!(task_delay_until(&mut last_wake_time, period));
+        assert_eq!(kernel::task_get_tick_count(), last_wake_time);
+        done1.store(true, Ordering::SeqCst);
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _periodic = task_control::TCB::new()
+        .name("Periodic")
+        .priority(3)
+        .initialise(periodic);
+    kernel::task_start_scheduler();
+    sim::advance_ticks(20 * period);
+    assert!(done0.load(Ordering::SeqCst));
+    trace!("Test TASK DELAY UNTIL COMPLETE!");
+}
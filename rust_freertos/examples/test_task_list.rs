@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let monitor = move || {
+        // Let the worker burn some CPU time first.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(20));
+
+        let mut table = String::new();
+        kernel::task_list(&mut table).unwrap();
+        trace!("Task list:\n{}", table);
+        let monitor_line = table
+            .lines()
+            .find(|line| line.starts_with("Monitor"))
+            .unwrap();
+        let columns: Vec<&str> = monitor_line.split('\t').collect();
+        assert_eq!(columns[0].len(), configMAX_TASK_NAME_LEN!() - 1);
+        assert_eq!(columns[1], "X");
+        assert_eq!(columns[2], "3");
+        // The stack high water mark is measured on the stack the task runs
+        // on, in words.
+        let stack_words = port::portMINIMAL_STACK_BYTES / std::mem::size_of::<port::StackType>();
+        let high_water_mark: usize = columns[3].parse().unwrap();
+        assert!(high_water_mark > 0 && high_water_mark < stack_words);
+        assert!(table.lines().any(|line| line.starts_with("Worker")));
+        assert!(table.lines().any(|line| line.starts_with("Idle")));
+
+        let mut stats = String::new();
+        kernel::task_get_run_time_stats(&mut stats).unwrap();
+        trace!("Run time stats:\n{}", stats);
+        let worker_line = stats
+            .lines()
+            .find(|line| line.starts_with("Worker"))
+            .unwrap();
+        assert!(worker_line.ends_with('%'));
+
+        trace!("Test TASK LIST COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let worker = move || loop {
+        let mut tmp: u64 = 1;
+        for i in 1..1000 {
+            tmp = tmp.wrapping_mul(i);
+        }
+        trace!("Worker computed {}", tmp);
+    };
+
+    let _monitor = task_control::TCB::new()
+        .name("Monitor")
+        .priority(3)
+        .initialise(monitor);
+    let _worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(2)
+        .initialise(worker);
+    kernel::task_start_scheduler();
+}
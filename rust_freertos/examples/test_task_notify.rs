@@ -0,0 +1,64 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::{Arc, Mutex};
+use task_notify::NotifyAction;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let receiver_handle = Arc::new(Mutex::new(None));
+    let receiver_handle1 = Arc::clone(&receiver_handle);
+
+    let receiver = move || {
+        // Nothing has been sent yet, so this times out.
+        assert!(task_notify::task_notify_wait(0, 0, pdMS_TO_TICKS!(5)).is_err());
+
+        // Two gives are counted like a counting semaphore.
+        assert_eq!(task_notify::task_notify_take(false, pdMS_TO_TICKS!(100)), 2);
+        assert_eq!(task_notify::task_notify_take(true, pdMS_TO_TICKS!(100)), 1);
+
+        // Bits set by the sender are returned and cleared on exit.
+        let value = task_notify::task_notify_wait(0, 0xffffffff, pdMS_TO_TICKS!(100)).unwrap();
+        trace!("Receiver got notification value {:#x}", value);
+        assert_eq!(value, 0b101);
+
+        trace!("Test TASK NOTIFY COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let sender = move || {
+        let receiver: task_control::TaskHandle = receiver_handle1.lock().unwrap().clone().unwrap();
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        receiver.notify_give();
+        receiver.notify_give();
+
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        receiver.notify(0b001, NotifyAction::SetBits).unwrap();
+        receiver.notify(0b100, NotifyAction::SetBits).unwrap();
+        // The receiver has not read the value yet, so it is not overwritten.
+        assert_eq!(
+            receiver.notify(0, NotifyAction::SetValueWithoutOverwrite),
+            Err(FreeRtosError::NotificationPending)
+        );
+        loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        }
+    };
+
+    let receiver_task = task_control::TCB::new()
+        .name("Receiver")
+        .priority(2)
+        .initialise(receiver)
+        .unwrap();
+    *receiver_handle.lock().unwrap() = Some(receiver_task);
+    let _sender = task_control::TCB::new()
+        .name("Sender")
+        .priority(3)
+        .initialise(sender);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,89 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use task_control::{task_state, PanicPolicy};
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let panics = Arc::new(Mutex::new(Vec::new()));
+    let hook_panics = Arc::clone(&panics);
+    kernel::set_task_panic_hook(Some(Box::new(move |task, payload| {
+        let message = payload.downcast_ref::<&str>().unwrap();
+        hook_panics
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", task.get_name(), message));
+    })));
+
+    // Each task has a priority of its own, so they panic one after the
+    // other, in the order of their priorities.
+    let deleted = task_control::TCB::new()
+        .name("Deleted")
+        .priority(4)
+        .initialise(|| panic!("bug in logger"))
+        .unwrap();
+    let suspended = task_control::TCB::new()
+        .name("Suspended")
+        .priority(3)
+        .panic_policy(PanicPolicy::Suspend)
+        .initialise(|| panic!("inspect me"))
+        .unwrap();
+
+    let restarts = Arc::new(AtomicUsize::new(0));
+    let entry_restarts = Arc::clone(&restarts);
+    let restarted = task_control::TCB::new()
+        .name("Restarted")
+        .priority(2)
+        .panic_policy(PanicPolicy::Restart(Arc::new(move || {
+            // Fail once more, then get going.
+            if entry_restarts.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("still broken");
+            }
+        })))
+        .initialise(|| panic!("first run"))
+        .unwrap();
+
+    let supervisor_panics = Arc::clone(&panics);
+    let supervisor = move || {
+        // Let the other tasks run and panic.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+
+        assert_eq!(deleted.state(), task_state::deleted);
+        assert_eq!(suspended.state(), task_state::suspended);
+        assert_eq!(restarted.state(), task_state::deleted);
+        assert_eq!(restarts.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            *supervisor_panics.lock().unwrap(),
+            vec![
+                "Deleted: bug in logger",
+                "Suspended: inspect me",
+                "Restarted: first run",
+                "Restarted: still broken",
+            ]
+        );
+
+        // This one takes the whole scheduler down.
+        task_control::TCB::new()
+            .name("Fatal")
+            .priority(3)
+            .panic_policy(PanicPolicy::AbortScheduler)
+            .initialise(|| panic!("fatal"))
+            .unwrap();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(1)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+
+    assert_eq!(panics.lock().unwrap().last().unwrap(), "Fatal: fatal");
+    trace!("Test TASK PANIC COMPLETE!");
+}
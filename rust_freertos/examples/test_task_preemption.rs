@@ -0,0 +1,80 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Info, Config::default());
+    let waiter_handle = Arc::new(Mutex::new(None));
+    let waiter_handle1 = Arc::clone(&waiter_handle);
+    // Set while the waiter runs.  Only one task may run at a time, so the
+    // spinner must never see it set.
+    let waiter_running0 = Arc::new(AtomicBool::new(false));
+    let waiter_running1 = Arc::clone(&waiter_running0);
+    let spins0 = Arc::new(AtomicUsize::new(0));
+    let spins1 = Arc::clone(&spins0);
+
+    let waiter = move || {
+        let me = get_current_task_handle!();
+        let mut received = 0;
+        let mut timeouts = 0;
+        while received < 5 {
+            waiter_running0.store(false, Ordering::SeqCst);
+            let result = task_notify::task_notify_wait(0, 0xffffffff, pdMS_TO_TICKS!(2));
+            waiter_running0.store(true, Ordering::SeqCst);
+
+            // The tick or the give took the processor from the spinner, which
+            // must not have gone on running kernel code as another task.
+            assert!(get_current_task_handle!() == me);
+            match result {
+                Ok(value) => {
+                    assert_eq!(value, 1);
+                    received += 1;
+                }
+                Err(FreeRtosError::Timeout) => timeouts += 1,
+                Err(error) => panic!("Unexpected error {:?}", error),
+            }
+        }
+        assert!(timeouts > 0);
+        assert!(spins0.load(Ordering::SeqCst) > 0);
+
+        info!("Test TASK PREEMPTION COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let spinner = move || {
+        let waiter: task_control::TaskHandle = waiter_handle1.lock().unwrap().clone().unwrap();
+        let mut last_give = Instant::now();
+        loop {
+            // The spinner never blocks and only calls into the kernel to
+            // notify the waiter, it is preempted by the tick when the waiter
+            // times out.
+            assert!(!waiter_running1.load(Ordering::SeqCst));
+            spins1.fetch_add(1, Ordering::SeqCst);
+
+            if last_give.elapsed() >= Duration::from_millis(5) {
+                last_give = Instant::now();
+                waiter.notify_give();
+            }
+        }
+    };
+
+    let waiter_task = task_control::TCB::new()
+        .name("Waiter")
+        .priority(2)
+        .initialise(waiter)
+        .unwrap();
+    *waiter_handle.lock().unwrap() = Some(waiter_task);
+    let _spinner = task_control::TCB::new()
+        .name("Spinner")
+        .priority(1)
+        .initialise(spinner);
+    kernel::task_start_scheduler();
+}
@@ -0,0 +1,62 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use projdefs::FreeRtosError;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use task_control::task_state;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    let supervisor = move || {
+        let tasks_before = kernel::task_get_system_state().0.len();
+        let sum = Arc::new(AtomicUsize::new(0));
+
+        // Workers of a lower priority, so they only run once we block.
+        let workers: Vec<_> = (1..5)
+            .map(|i| {
+                let sum = Arc::clone(&sum);
+                task_control::TCB::new()
+                    .name("Worker")
+                    .priority(1)
+                    .initialise(move || {
+                        task_timemanager::task_delay(pdMS_TO_TICKS!(i));
+                        sum.fetch_add(i, Ordering::SeqCst);
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        // None of the workers has run yet.
+        assert_eq!(workers[0].wait_for_exit(0), Err(FreeRtosError::Timeout));
+
+        for worker in &workers {
+            assert_eq!(worker.wait_for_exit(pdMS_TO_TICKS!(100)), Ok(()));
+            assert_eq!(worker.state(), task_state::deleted);
+        }
+        assert_eq!(sum.load(Ordering::SeqCst), 1 + 2 + 3 + 4);
+        // Waiting for a task that has already ended returns at once.
+        assert_eq!(workers[0].wait_for_exit(0), Ok(()));
+
+        // Every worker has dropped its closure, and so its reference to `sum`.
+        assert_eq!(Arc::strong_count(&sum), 1);
+
+        // Let the idle task free what is left of them.
+        task_timemanager::task_delay(pdMS_TO_TICKS!(10));
+        assert_eq!(kernel::task_get_system_state().0.len(), tasks_before);
+
+        trace!("Test TASK RETURN COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(2)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+}
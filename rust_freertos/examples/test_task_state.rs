@@ -0,0 +1,93 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use queue_api::Queue;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::Arc;
+use task_control::task_state;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let queue0 = Arc::new(Queue::<u32>::new(1));
+    let queue1 = Arc::clone(&queue0);
+
+    let sleeper = move || loop {
+        task_timemanager::task_delay(pdMS_TO_TICKS!(100));
+    };
+    let waiter = move || loop {
+        // Waiting without a timeout puts the task in the suspended list.
+        queue1.receive(port::portMAX_DELAY).unwrap();
+    };
+    let listener = move || loop {
+        // Suspending the task ends the wait without a notification.
+        assert_eq!(
+            task_notify::task_notify_wait(0, 0, port::portMAX_DELAY),
+            Err(projdefs::FreeRtosError::Timeout)
+        );
+    };
+    let spinner = move || loop {};
+
+    let sleeper = task_control::TCB::new()
+        .name("Sleeper")
+        .priority(2)
+        .initialise(sleeper)
+        .unwrap();
+    let waiter = task_control::TCB::new()
+        .name("Waiter")
+        .priority(2)
+        .initialise(waiter)
+        .unwrap();
+    let listener = task_control::TCB::new()
+        .name("Listener")
+        .priority(2)
+        .initialise(listener)
+        .unwrap();
+    let spinner = task_control::TCB::new()
+        .name("Spinner")
+        .priority(1)
+        .initialise(spinner)
+        .unwrap();
+
+    let supervisor = move || {
+        // Let the other tasks block first.  A tick can end the delay before
+        // all of them got to run, so wait until none of them is left.
+        while [&sleeper, &waiter, &listener]
+            .iter()
+            .any(|task| task.state() != task_state::blocked)
+        {
+            task_timemanager::task_delay(1);
+        }
+
+        let me = task_api::task_get_handle("Supervisor").unwrap();
+        assert_eq!(me.state(), task_state::running);
+        assert_eq!(spinner.state(), task_state::ready);
+
+        task_control::suspend_task(spinner.clone());
+        assert_eq!(spinner.state(), task_state::suspended);
+        task_control::resume_task(spinner.clone());
+        assert_eq!(spinner.state(), task_state::ready);
+
+        // A suspended task is no longer waiting for its notification.
+        task_control::suspend_task(listener.clone());
+        assert_eq!(listener.state(), task_state::suspended);
+        listener.notify_give();
+        assert_eq!(listener.state(), task_state::suspended);
+        task_control::resume_task(listener.clone());
+        assert_eq!(listener.state(), task_state::ready);
+
+        task_control::task_delete(Some(sleeper.clone()));
+        assert_eq!(sleeper.state(), task_state::deleted);
+
+        trace!("Test TASK STATE COMPLETE!");
+        kernel::task_end_scheduler();
+        loop {}
+    };
+
+    let _supervisor = task_control::TCB::new()
+        .name("Supervisor")
+        .priority(3)
+        .initialise(supervisor);
+    kernel::task_start_scheduler();
+}
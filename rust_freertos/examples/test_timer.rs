@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use timers::Timer;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticks_in_callback = Arc::clone(&ticks);
+
+    // An auto-reload timer that fires every 10ms.
+    let periodic = Timer::new()
+        .name("Periodic")
+        .period(pdMS_TO_TICKS!(10))
+        .auto_reload(true)
+        .initialise(move |timer| {
+            let n = ticks_in_callback.fetch_add(1, Ordering::SeqCst) + 1;
+            trace!("{} expired {} times", timer.get_name(), n);
+            if n == 5 {
+                timer.stop(0).unwrap();
+            }
+        });
+
+    // A one-shot timer that checks the result after the periodic one stopped.
+    let checker = Timer::new()
+        .name("Checker")
+        .period(pdMS_TO_TICKS!(100))
+        .initialise(move |_| {
+            assert_eq!(ticks.load(Ordering::SeqCst), 5);
+            trace!("Test TIMER COMPLETE!");
+            kernel::task_end_scheduler();
+        });
+
+    // Commands sent before the scheduler starts are processed as soon as the
+    // timer daemon task runs.
+    periodic.start(0).unwrap();
+    checker.start(0).unwrap();
+
+    let _worker = task_control::TCB::new()
+        .name("Worker")
+        .priority(3)
+        .initialise(|| loop {
+            task_timemanager::task_delay(pdMS_TO_TICKS!(1));
+        });
+
+    kernel::task_start_scheduler();
+}
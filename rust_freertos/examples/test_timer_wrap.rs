@@ -0,0 +1,51 @@
+// Run with `cargo run --example test_timer_wrap --features portVIRTUAL_TIME`.
+#[macro_use]
+extern crate log;
+extern crate rust_freertos;
+
+use port::TickType;
+use rust_freertos::*;
+use simplelog::*;
+use std::sync::{Arc, Mutex};
+use timers::Timer;
+
+fn main() {
+    let _ = TermLogger::init(LevelFilter::Trace, Config::default());
+
+    // Start 16 ticks before the tick count overflows.
+    let start = TickType::max_value() - 15;
+    sim::start_at_tick(start);
+
+    let expiries = Arc::new(Mutex::new(Vec::new()));
+    let expiries_in_callback = Arc::clone(&expiries);
+    let reload = Timer::new()
+        .name("Reload")
+        .period(10)
+        .auto_reload(true)
+        .initialise(move |timer| {
+            let mut expiries = expiries_in_callback.lock().unwrap();
+            expiries.push(kernel::task_get_tick_count());
+            // Stopping the only active timer leaves the daemon with nothing
+            // to wait for, at a tick other than 0.
+            if expiries.len() == 4 {
+                timer.stop(0).unwrap();
+            }
+        });
+    reload.start(0).unwrap();
+
+    kernel::task_start_scheduler();
+    assert_eq!(kernel::task_get_tick_count(), start);
+
+    // The timer is reloaded across the overflow without losing a period.
+    sim::advance_ticks(40);
+    assert_eq!(kernel::task_get_tick_count(), 24);
+    assert_eq!(*expiries.lock().unwrap(), vec![start + 10, 4, 14, 24]);
+    assert!(!reload.is_timer_active());
+
+    // The daemon waits for the next command, and nothing expires any more.
+    sim::advance_ticks(20);
+    assert_eq!(kernel::task_get_tick_count(), 44);
+    assert_eq!(expiries.lock().unwrap().len(), 4);
+
+    trace!("Test TIMER WRAP COMPLETE!");
+}
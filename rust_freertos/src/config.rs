@@ -16,10 +16,17 @@ macro_rules! configMINIMAL_STACK_SIZE {
     };
 }
 
+/* The heap of portHEAP_1, portHEAP_2 and portHEAP_4 holds the stack of every
+task, and the POSIX port makes no stack smaller than
+port::portMINIMAL_STACK_BYTES (64 KiB).  The heap is therefore sized in
+stacks: 8 of them leave room for the idle task, the timer task and a handful
+of application tasks, with their TCBs and queues.  Creating more tasks than
+that fails with FreeRtosError::OutOfMemory, so raise the number of
+stacks for an application that needs more. */
 #[macro_export]
 macro_rules! configTOTAL_HEAP_SIZE {
     () => {
-        64 * 1024 as usize
+        8 * port::portMINIMAL_STACK_BYTES
     };
 }
 
@@ -37,6 +44,15 @@ macro_rules! configQUEUE_REGISTRY_SIZE {
     };
 }
 
+/* The method used to detect stack overflows when the
+configCHECK_FOR_STACK_OVERFLOW feature is enabled, either 1 or 2. */
+#[macro_export]
+macro_rules! configCHECK_FOR_STACK_OVERFLOW {
+    () => {
+        2
+    };
+}
+
 #[macro_export]
 macro_rules! configMAX_PRIORITIES {
     () => {
@@ -54,7 +70,32 @@ macro_rules! configTIMER_TASK_PRIORITY {
 #[macro_export]
 macro_rules! configTIMER_TASK_STACK_DEPTH {
     () => {
-        configMINIMAL_STACK_SIZE * 2
+        configMINIMAL_STACK_SIZE!() * 2
+    };
+}
+
+#[macro_export]
+macro_rules! configTIMER_QUEUE_LENGTH {
+    () => {
+        10
+    };
+}
+
+/* The tick count the scheduler starts at.  Virtual time can start at any
+tick, see sim::start_at_tick(). */
+#[cfg(not(feature = "portVIRTUAL_TIME"))]
+#[macro_export]
+macro_rules! configINITIAL_TICK_COUNT {
+    () => {
+        0 as port::TickType
+    };
+}
+
+#[cfg(feature = "portVIRTUAL_TIME")]
+#[macro_export]
+macro_rules! configINITIAL_TICK_COUNT {
+    () => {
+        $crate::sim::start_tick()
     };
 }
 
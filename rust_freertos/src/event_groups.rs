@@ -0,0 +1,603 @@
+// event_groups.rs - Event groups.
+// Functions defined in this file are adapted from event_groups.c.
+//
+// An event group is a set of event bits that tasks can set, clear and wait on.
+// Tasks waiting for bits are placed on an unordered event list owned by the
+// event group, with the bits they wait for (and how they wait for them) stored
+// in the item value of their event list item.
+use crate::kernel;
+use crate::list;
+use crate::list::ListLink;
+use crate::port::*;
+use crate::sync::RwLock;
+use crate::task_queue::*;
+use crate::*;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(all(
+    feature = "configUSE_TIMERS",
+    feature = "INCLUDE_xTimerPendFunctionCall"
+))]
+use crate::projdefs::FreeRtosError;
+
+/// The type that holds event bits.  The number of bits it holds depends on
+/// configUSE_16_BIT_TICKS (8 bits if set, 24 bits otherwise) as the top byte
+/// of the value is used by the kernel itself.
+pub type EventBits = TickType;
+
+/* The following bit fields convey control information in a task's event list
+item value.  It is important they don't clash with the
+taskEVENT_LIST_ITEM_VALUE_IN_USE definition. */
+#[cfg(feature = "configUSE_16_BIT_TICKS")]
+const eventCLEAR_EVENTS_ON_EXIT_BIT: EventBits = 0x0100;
+#[cfg(feature = "configUSE_16_BIT_TICKS")]
+const eventUNBLOCKED_DUE_TO_BIT_SET: EventBits = 0x0200;
+#[cfg(feature = "configUSE_16_BIT_TICKS")]
+const eventWAIT_FOR_ALL_BITS: EventBits = 0x0400;
+#[cfg(feature = "configUSE_16_BIT_TICKS")]
+const eventEVENT_BITS_CONTROL_BYTES: EventBits = 0xff00;
+
+#[cfg(not(feature = "configUSE_16_BIT_TICKS"))]
+const eventCLEAR_EVENTS_ON_EXIT_BIT: EventBits = 0x01000000;
+#[cfg(not(feature = "configUSE_16_BIT_TICKS"))]
+const eventUNBLOCKED_DUE_TO_BIT_SET: EventBits = 0x02000000;
+#[cfg(not(feature = "configUSE_16_BIT_TICKS"))]
+const eventWAIT_FOR_ALL_BITS: EventBits = 0x04000000;
+#[cfg(not(feature = "configUSE_16_BIT_TICKS"))]
+const eventEVENT_BITS_CONTROL_BYTES: EventBits = 0xff000000;
+
+struct EventGroupDefinition {
+    event_bits: EventBits,
+    /* List of tasks waiting for a bit to be set. */
+    tasks_waiting_for_bits: ListLink,
+}
+
+pub struct EventGroup(UnsafeCell<EventGroupDefinition>);
+unsafe impl Send for EventGroup {}
+unsafe impl Sync for EventGroup {}
+
+impl Default for EventGroup {
+    fn default() -> Self {
+        EventGroup::new()
+    }
+}
+
+impl EventGroup {
+    /// # Description
+    /// Create a new event group with all of its event bits cleared.
+    ///
+    /// * C implementation: event_groups.c 168-203
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// The created event group.
+    pub fn new() -> Self {
+        let event_group = EventGroup(UnsafeCell::new(EventGroupDefinition {
+            event_bits: 0,
+            tasks_waiting_for_bits: Arc::new(RwLock::new(Default::default())),
+        }));
+        traceEVENT_GROUP_CREATE!(&event_group);
+        event_group
+    }
+
+    /// # Description
+    /// Read bits within the event group, optionally entering the Blocked
+    /// state (with a timeout) to wait for a bit or group of bits to become set.
+    ///
+    /// * C implementation: event_groups.c 324-469
+    ///
+    /// # Arguments:
+    /// `bits_to_wait_for` - A bitwise value that indicates the bit or bits to
+    /// test inside the event group.  Must not be 0 and must not use the control
+    /// bits of the top byte.
+    ///
+    /// `clear_on_exit` - If true, the bits waited for are cleared in the event
+    /// group before this function returns, provided the wait condition was met.
+    ///
+    /// `wait_for_all_bits` - If true, wait until all the bits in
+    /// `bits_to_wait_for` are set; otherwise wait until any of them is set.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time (in ticks) to wait.
+    ///
+    /// # Return:
+    /// The value of the event group at the time either the bits being waited
+    /// for became set, or the block time expired.  Test the return value to
+    /// know which bits were set.
+    pub fn wait_bits(
+        &self,
+        bits_to_wait_for: EventBits,
+        clear_on_exit: bool,
+        wait_for_all_bits: bool,
+        ticks_to_wait: TickType,
+    ) -> EventBits {
+        let inner = self.0.get();
+        let mut ticks_to_wait = ticks_to_wait;
+        let mut control_bits: EventBits = 0;
+        let mut ret_val: EventBits;
+
+        /* Check the user is not attempting to wait on the bits used by the kernel
+        itself, and that at least one bit is being requested. */
+        assert!((bits_to_wait_for & eventEVENT_BITS_CONTROL_BYTES) == 0);
+        assert!(bits_to_wait_for != 0);
+        check_scheduler_not_suspended(ticks_to_wait);
+
+        kernel::task_suspend_all();
+        {
+            let current_event_bits = unsafe { (*inner).event_bits };
+
+            /* Check to see if the wait condition is already met or not. */
+            if test_wait_condition(current_event_bits, bits_to_wait_for, wait_for_all_bits) {
+                /* The wait condition has already been met so there is no need to
+                block. */
+                ret_val = current_event_bits;
+                ticks_to_wait = 0;
+
+                /* Clear the wait bits if requested to do so. */
+                if clear_on_exit {
+                    unsafe { (*inner).event_bits &= !bits_to_wait_for };
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            } else if ticks_to_wait == 0 {
+                /* The wait condition has not been met, but no block time was
+                specified, so just return the current value. */
+                ret_val = current_event_bits;
+            } else {
+                /* The task is going to block to wait for its required bits to be
+                set.  control_bits are used to remember the specified behaviour of
+                this call to wait_bits() - for use when the event bits unblock
+                the task. */
+                if clear_on_exit {
+                    control_bits |= eventCLEAR_EVENTS_ON_EXIT_BIT;
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+
+                if wait_for_all_bits {
+                    control_bits |= eventWAIT_FOR_ALL_BITS;
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+
+                /* Store the bits that the calling task is waiting for in the
+                task's event list item so the kernel knows when a match is
+                found.  Then enter the blocked state. */
+                task_place_on_unordered_event_list(
+                    unsafe { &(*inner).tasks_waiting_for_bits },
+                    bits_to_wait_for | control_bits,
+                    ticks_to_wait,
+                );
+
+                /* This is obsolete as it will get set after the task unblocks. */
+                ret_val = 0;
+
+                traceEVENT_GROUP_WAIT_BITS_BLOCK!(self, bits_to_wait_for);
+            }
+        }
+        let already_yielded = kernel::task_resume_all().unwrap();
+
+        if ticks_to_wait != 0 {
+            if !already_yielded {
+                portYIELD_WITHIN_API!();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+
+            /* The task blocked to wait for its required bits to be set - at this
+            point either the required bits were set or the block time expired.  If
+            the required bits were set they will have been stored in the task's
+            event list item, and they should now be retrieved then cleared. */
+            ret_val = task_reset_event_item_value();
+
+            if (ret_val & eventUNBLOCKED_DUE_TO_BIT_SET) == 0 {
+                taskENTER_CRITICAL!();
+                {
+                    /* The task timed out, just return the current event bit value. */
+                    ret_val = unsafe { (*inner).event_bits };
+
+                    /* It is possible that the event bits were updated between this
+                    task leaving the Blocked state and running again. */
+                    if test_wait_condition(ret_val, bits_to_wait_for, wait_for_all_bits) {
+                        if clear_on_exit {
+                            unsafe { (*inner).event_bits &= !bits_to_wait_for };
+                        } else {
+                            mtCOVERAGE_TEST_MARKER!();
+                        }
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+                }
+                taskEXIT_CRITICAL!();
+            } else {
+                /* The task unblocked because the bits were set. */
+            }
+
+            /* The task blocked so control bits may have been set. */
+            ret_val &= !eventEVENT_BITS_CONTROL_BYTES;
+        }
+        traceEVENT_GROUP_WAIT_BITS_END!(self, bits_to_wait_for, false);
+
+        ret_val
+    }
+
+    /// # Description
+    /// Set bits within the event group, unblocking every task whose wait
+    /// condition is met by the new value.
+    ///
+    /// * C implementation: event_groups.c 530-621
+    ///
+    /// # Arguments:
+    /// `bits_to_set` - A bitwise value that indicates the bit or bits to set.
+    ///
+    /// # Return:
+    /// The value of the event group at the time the call returns.  Bits may
+    /// already have been cleared again by the tasks that were unblocked.
+    pub fn set_bits(&self, bits_to_set: EventBits) -> EventBits {
+        let inner = self.0.get();
+        let mut bits_to_clear: EventBits = 0;
+
+        /* Check the user is not attempting to set the bits used by the kernel
+        itself. */
+        assert!((bits_to_set & eventEVENT_BITS_CONTROL_BYTES) == 0);
+
+        kernel::task_suspend_all();
+        {
+            traceEVENT_GROUP_SET_BITS!(self, bits_to_set);
+
+            /* Take a snapshot of the waiting tasks first, as the list items are
+            removed from the list while walking through it. */
+            let waiting_items = list::get_list_items(unsafe { &(*inner).tasks_waiting_for_bits });
+
+            /* Set the bits. */
+            unsafe { (*inner).event_bits |= bits_to_set };
+            let event_bits = unsafe { (*inner).event_bits };
+
+            /* See if the new bit value should unblock any tasks. */
+            for list_item in waiting_items {
+                let mut bits_waited_for = list::get_list_item_value(&list_item);
+
+                /* Split the bits waited for from the control bits. */
+                let control_bits = bits_waited_for & eventEVENT_BITS_CONTROL_BYTES;
+                bits_waited_for &= !eventEVENT_BITS_CONTROL_BYTES;
+
+                let match_found = if (control_bits & eventWAIT_FOR_ALL_BITS) == 0 {
+                    /* Just looking for single bit being set. */
+                    (bits_waited_for & event_bits) != 0
+                } else {
+                    /* Need all bits to be set. */
+                    (bits_waited_for & event_bits) == bits_waited_for
+                };
+
+                if match_found {
+                    /* The bits match.  Should the bits be cleared on exit? */
+                    if (control_bits & eventCLEAR_EVENTS_ON_EXIT_BIT) != 0 {
+                        bits_to_clear |= bits_waited_for;
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+
+                    /* Store the actual event flag value in the task's event list
+                    item before removing the task from the event list.  The
+                    eventUNBLOCKED_DUE_TO_BIT_SET bit is set so the task knows
+                    that is was unblocked due to its required bits matching, rather
+                    than because it timed out. */
+                    task_remove_from_unordered_event_list(
+                        &list_item,
+                        event_bits | eventUNBLOCKED_DUE_TO_BIT_SET,
+                    );
+                }
+            }
+
+            /* Clear any bits that matched when the eventCLEAR_EVENTS_ON_EXIT_BIT
+            bit was set in the control word. */
+            unsafe { (*inner).event_bits &= !bits_to_clear };
+        }
+        kernel::task_resume_all().unwrap();
+
+        unsafe { (*inner).event_bits }
+    }
+
+    /// # Description
+    /// Clear bits within the event group.
+    ///
+    /// * C implementation: event_groups.c 472-494
+    ///
+    /// # Arguments:
+    /// `bits_to_clear` - A bitwise value that indicates the bit or bits to
+    /// clear.
+    ///
+    /// # Return:
+    /// The value of the event group before the specified bits were cleared.
+    pub fn clear_bits(&self, bits_to_clear: EventBits) -> EventBits {
+        let inner = self.0.get();
+        let ret_val: EventBits;
+
+        /* Check the user is not attempting to clear the bits used by the kernel
+        itself. */
+        assert!((bits_to_clear & eventEVENT_BITS_CONTROL_BYTES) == 0);
+
+        taskENTER_CRITICAL!();
+        {
+            traceEVENT_GROUP_CLEAR_BITS!(self, bits_to_clear);
+
+            /* The value returned is the event group value prior to the bits being
+            cleared. */
+            ret_val = unsafe { (*inner).event_bits };
+
+            /* Clear the bits. */
+            unsafe { (*inner).event_bits &= !bits_to_clear };
+        }
+        taskEXIT_CRITICAL!();
+
+        ret_val
+    }
+
+    /// # Description
+    /// Returns the current value of the bits in the event group.
+    ///
+    /// * C implementation: event_groups.h 669
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// The event group bits at the time the function was called.
+    pub fn get_bits(&self) -> EventBits {
+        self.clear_bits(0)
+    }
+
+    /// # Description
+    /// A version of get_bits() that can be called from an ISR.
+    ///
+    /// * C implementation: event_groups.c 514-527
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// The event group bits at the time the function was called.
+    pub fn get_bits_from_isr(&self) -> EventBits {
+        let ret_val: EventBits;
+        let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+        {
+            ret_val = unsafe { (*self.0.get()).event_bits };
+        }
+        portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+
+        ret_val
+    }
+
+    /// # Description
+    /// Atomically set bits within the event group, then wait for a combination
+    /// of bits to be set.  This is typically used to synchronise (rendezvous)
+    /// multiple tasks, each task setting its own bit and waiting for the bits
+    /// of all the others.  The bits waited for are always cleared on exit.
+    ///
+    /// * C implementation: event_groups.c 208-321
+    ///
+    /// # Arguments:
+    /// `bits_to_set` - The bits to set in the event group before determining
+    /// if (and possibly waiting for) all the bits in `bits_to_wait_for` are set.
+    ///
+    /// `bits_to_wait_for` - The bits that must all be set before this function
+    /// returns.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time (in ticks) to wait.
+    ///
+    /// # Return:
+    /// The value of the event group at the time either the bits being waited
+    /// for became set (before they were cleared), or the block time expired.
+    pub fn sync(
+        &self,
+        bits_to_set: EventBits,
+        bits_to_wait_for: EventBits,
+        ticks_to_wait: TickType,
+    ) -> EventBits {
+        let inner = self.0.get();
+        let mut ticks_to_wait = ticks_to_wait;
+        let mut ret_val: EventBits;
+
+        assert!((bits_to_wait_for & eventEVENT_BITS_CONTROL_BYTES) == 0);
+        assert!(bits_to_wait_for != 0);
+        check_scheduler_not_suspended(ticks_to_wait);
+
+        kernel::task_suspend_all();
+        {
+            let original_bit_value = unsafe { (*inner).event_bits };
+
+            self.set_bits(bits_to_set);
+
+            if ((original_bit_value | bits_to_set) & bits_to_wait_for) == bits_to_wait_for {
+                /* All the rendezvous bits are now set - no need to block. */
+                ret_val = original_bit_value | bits_to_set;
+
+                /* Rendezvous always clear the bits.  They will have been cleared
+                already unless this is the only task in the rendezvous. */
+                unsafe { (*inner).event_bits &= !bits_to_wait_for };
+
+                ticks_to_wait = 0;
+            } else if ticks_to_wait != 0 {
+                traceEVENT_GROUP_SYNC_BLOCK!(self, bits_to_set, bits_to_wait_for);
+
+                /* Store the bits that the calling task is waiting for in the
+                task's event list item so the kernel knows when a match is
+                found.  Then enter the blocked state. */
+                task_place_on_unordered_event_list(
+                    unsafe { &(*inner).tasks_waiting_for_bits },
+                    bits_to_wait_for | eventCLEAR_EVENTS_ON_EXIT_BIT | eventWAIT_FOR_ALL_BITS,
+                    ticks_to_wait,
+                );
+
+                /* This is obsolete as it will get set after the task unblocks. */
+                ret_val = 0;
+            } else {
+                /* The rendezvous bits were not set, but no block time was
+                specified - just return the current event bit value. */
+                ret_val = unsafe { (*inner).event_bits };
+            }
+        }
+        let already_yielded = kernel::task_resume_all().unwrap();
+
+        if ticks_to_wait != 0 {
+            if !already_yielded {
+                portYIELD_WITHIN_API!();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+
+            /* The task blocked to wait for its required bits to be set - at this
+            point either the required bits were set or the block time expired.  If
+            the required bits were set they will have been stored in the task's
+            event list item, and they should now be retrieved then cleared. */
+            ret_val = task_reset_event_item_value();
+
+            if (ret_val & eventUNBLOCKED_DUE_TO_BIT_SET) == 0 {
+                /* The task timed out, just return the current event bit value. */
+                taskENTER_CRITICAL!();
+                {
+                    ret_val = unsafe { (*inner).event_bits };
+
+                    /* Although the task got here because it timed out before the
+                    bits it was waiting for were set, it is possible that since it
+                    unblocked another task has set the bits.  If this is the case
+                    then it needs to clear the bits before exiting. */
+                    if (ret_val & bits_to_wait_for) == bits_to_wait_for {
+                        unsafe { (*inner).event_bits &= !bits_to_wait_for };
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+                }
+                taskEXIT_CRITICAL!();
+            } else {
+                /* The task unblocked because the bits were set. */
+            }
+
+            /* Control bits might be set as the task had blocked should not be
+            returned. */
+            ret_val &= !eventEVENT_BITS_CONTROL_BYTES;
+        }
+
+        traceEVENT_GROUP_SYNC_END!(self, bits_to_set, bits_to_wait_for, false);
+
+        ret_val
+    }
+
+    /// # Description
+    /// A version of set_bits() that can be called from an ISR.  Setting bits
+    /// is not a deterministic operation (an unknown number of tasks may be
+    /// waiting for them), so the operation is deferred to the timer daemon
+    /// task instead of being performed inside the interrupt.
+    ///
+    /// * C implementation: event_groups.c 729-740
+    ///
+    /// # Arguments:
+    /// `event_group` - The event group in which the bits are to be set.
+    ///
+    /// `bits_to_set` - A bitwise value that indicates the bit or bits to set.
+    ///
+    /// # Return:
+    /// `Ok(())` if the request was posted to the timer daemon task, otherwise
+    /// `FreeRtosError::QueueFull`.  The bool is true if posting the request
+    /// unblocked a task with a priority higher than the running task (the
+    /// timer daemon task), in which case a context switch should be requested
+    /// before the interrupt exits.
+    #[cfg(all(
+        feature = "configUSE_TIMERS",
+        feature = "INCLUDE_xTimerPendFunctionCall"
+    ))]
+    pub fn set_bits_from_isr(
+        event_group: &Arc<EventGroup>,
+        bits_to_set: EventBits,
+    ) -> (Result<(), FreeRtosError>, bool) {
+        traceEVENT_GROUP_SET_BITS_FROM_ISR!(event_group, bits_to_set);
+        let event_group = Arc::clone(event_group);
+        timers::timer_pend_function_call_from_isr(move || {
+            event_group.set_bits(bits_to_set);
+        })
+    }
+
+    /// # Description
+    /// A version of clear_bits() that can be called from an ISR.  The
+    /// operation is deferred to the timer daemon task.
+    ///
+    /// * C implementation: event_groups.c 497-509
+    ///
+    /// # Arguments:
+    /// `event_group` - The event group in which the bits are to be cleared.
+    ///
+    /// `bits_to_clear` - A bitwise value that indicates the bit or bits to
+    /// clear.
+    ///
+    /// # Return:
+    /// `Ok(())` if the request was posted to the timer daemon task, otherwise
+    /// `FreeRtosError::QueueFull`.  The bool is true if a context switch should be
+    /// requested before the interrupt exits.
+    #[cfg(all(
+        feature = "configUSE_TIMERS",
+        feature = "INCLUDE_xTimerPendFunctionCall"
+    ))]
+    pub fn clear_bits_from_isr(
+        event_group: &Arc<EventGroup>,
+        bits_to_clear: EventBits,
+    ) -> (Result<(), FreeRtosError>, bool) {
+        traceEVENT_GROUP_CLEAR_BITS_FROM_ISR!(event_group, bits_to_clear);
+        let event_group = Arc::clone(event_group);
+        timers::timer_pend_function_call_from_isr(move || {
+            event_group.clear_bits(bits_to_clear);
+        })
+    }
+}
+
+impl fmt::Debug for EventGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventGroup {{ event_bits: {:#x} }}", unsafe {
+            (*self.0.get()).event_bits
+        })
+    }
+}
+
+impl Drop for EventGroup {
+    /* C implementation: event_groups.c 624-664.  Tasks block on an event group
+    through a reference to it, so no task can still be waiting for bits by the
+    time the event group is dropped. */
+    fn drop(&mut self) {
+        traceEVENT_GROUP_DELETE!(self);
+        assert!(list::list_is_empty(unsafe {
+            &(*self.0.get()).tasks_waiting_for_bits
+        }));
+    }
+}
+
+/* A task must not block while the scheduler is suspended.  Read the
+scheduler state directly, task_get_scheduler_state() may be configured out. */
+fn check_scheduler_not_suspended(ticks_to_wait: TickType) {
+    if get_scheduler_running!() && get_scheduler_suspended!() != 0 {
+        assert!(ticks_to_wait == 0);
+    }
+}
+
+/* Test the bits set in current_event_bits to see if the wait condition is met.
+The wait condition is defined by wait_for_all_bits.  If wait_for_all_bits is true
+then the wait condition is met if all the bits set in bits_to_wait_for are also
+set in current_event_bits.  If wait_for_all_bits is false then the wait
+condition is met if any of the bits set in bits_to_wait for are also set in
+current_event_bits.  C implementation: event_groups.c 682-715 */
+fn test_wait_condition(
+    current_event_bits: EventBits,
+    bits_to_wait_for: EventBits,
+    wait_for_all_bits: bool,
+) -> bool {
+    if !wait_for_all_bits {
+        /* Task only has to wait for one bit within bits_to_wait_for to be
+        set.  Is one already set? */
+        (current_event_bits & bits_to_wait_for) != 0
+    } else {
+        /* Task has to wait for all the bits in bits_to_wait_for to be set.
+        Are they set already? */
+        (current_event_bits & bits_to_wait_for) == bits_to_wait_for
+    }
+}
@@ -44,11 +44,18 @@ extern "C" fn vTaskSuspendAll() {
 #[no_mangle]
 extern "C" fn xTaskResumeAll() -> BaseType {
     trace!("xTaskResumeAll() called from ffi!");
-    if kernel::task_resume_all() {
-        info!("task_resume_all() returned true");
-        pdTRUE
-    } else {
-        info!("task_resume_all() returned false");
-        pdFALSE
+    match kernel::task_resume_all() {
+        Ok(true) => {
+            info!("task_resume_all() returned true");
+            pdTRUE
+        }
+        Ok(false) => {
+            info!("task_resume_all() returned false");
+            pdFALSE
+        }
+        Err(error) => {
+            error!("task_resume_all() failed: {}", error);
+            pdFALSE
+        }
     }
 }
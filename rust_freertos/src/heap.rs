@@ -0,0 +1,283 @@
+// heap.rs - The memory management schemes of heap_1.c, heap_2.c, heap_4.c and
+// heap_5.c, written in Rust.
+// Which scheme port_malloc() uses is picked by the portHEAP_* features, without
+// any of them it calls malloc() through portable/heap_3.c.  The functions keep
+// their C names, port.rs maps them to the port_* functions as it does for C.
+//
+// heap_1, heap_2 and heap_4 hand out memory from a static array of
+// configTOTAL_HEAP_SIZE!() bytes, heap_5 from the regions given to
+// port_define_heap_regions().  As on the target, every block carries a header
+// and is rounded up to portBYTE_ALIGNMENT, so a test sees the same
+// fragmentation as the application does on the hardware:
+//
+// * heap_1 never frees anything.
+// * heap_2 takes the smallest free block that is large enough, but never
+//   merges freed blocks with their neighbours.
+// * heap_4 takes the first free block that is large enough, and merges freed
+//   blocks with their neighbours.
+// * heap_5 is heap_4 spread over several regions of memory.
+//
+// The C versions suspend the scheduler while they walk their lists.  Here the
+// lists are behind a mutex, as allocating never blocks or yields.
+use crate::port::{portBYTE_ALIGNMENT_MASK, HeapStats};
+use crate::sync::Mutex;
+use crate::*;
+use std::cell::UnsafeCell;
+use std::mem;
+use std::os::raw::c_void;
+
+const portBYTE_ALIGNMENT: usize = portBYTE_ALIGNMENT_MASK as usize + 1;
+
+/* Each block starts with a header holding its size, the size of BlockLink_t
+in the C versions. */
+const heapSTRUCT_SIZE: usize = align_up(2 * mem::size_of::<usize>());
+
+/* Blocks are not split if the remainder would be smaller than this. */
+const heapMINIMUM_BLOCK_SIZE: usize = heapSTRUCT_SIZE * 2;
+
+const fn align_up(size: usize) -> usize {
+    (size + portBYTE_ALIGNMENT - 1) & !(portBYTE_ALIGNMENT - 1)
+}
+
+/* The memory of heap_1, heap_2 and heap_4, ucHeap[] in the C versions. */
+#[cfg(not(feature = "portHEAP_5"))]
+#[repr(align(8))]
+struct Arena(UnsafeCell<[u8; configTOTAL_HEAP_SIZE!()]>);
+
+#[cfg(not(feature = "portHEAP_5"))]
+unsafe impl Sync for Arena {}
+
+#[cfg(not(feature = "portHEAP_5"))]
+static ARENA: Arena = Arena(UnsafeCell::new([0; configTOTAL_HEAP_SIZE!()]));
+
+/* A free block, `start` is the address of its header. */
+#[derive(Clone, Copy)]
+struct Block {
+    start: usize,
+    size: usize,
+}
+
+struct Heap {
+    /* Address ordered.  heap_1 uses a single block for what is left of the
+    array. */
+    free_blocks: Vec<Block>,
+    /* The addresses the regions start at, blocks are never merged across
+    them. */
+    region_starts: Vec<usize>,
+    free_bytes_remaining: usize,
+    minimum_ever_free_bytes_remaining: usize,
+}
+
+lazy_static! {
+    static ref HEAP: Mutex<Heap> = Mutex::new(Heap::new());
+}
+
+impl Heap {
+    #[cfg(not(feature = "portHEAP_5"))]
+    fn new() -> Self {
+        let mut heap = Heap::empty();
+        heap.add_region(ARENA.0.get() as usize, configTOTAL_HEAP_SIZE!());
+        heap
+    }
+
+    /* heap_5 is empty until vPortDefineHeapRegions() is called. */
+    #[cfg(feature = "portHEAP_5")]
+    fn new() -> Self {
+        Heap::empty()
+    }
+
+    fn empty() -> Self {
+        Heap {
+            free_blocks: Vec::new(),
+            region_starts: Vec::new(),
+            free_bytes_remaining: 0,
+            minimum_ever_free_bytes_remaining: 0,
+        }
+    }
+
+    /* Add the memory from `start` to `start + size` as a single free block,
+    trimmed to the alignment. */
+    fn add_region(&mut self, start: usize, size: usize) {
+        let aligned_start = align_up(start);
+        let size = size.saturating_sub(aligned_start - start) & !(portBYTE_ALIGNMENT - 1);
+        if size < heapMINIMUM_BLOCK_SIZE {
+            warn!("Heap region at {:#x} is too small to be used.", start);
+            return;
+        }
+
+        let index = self
+            .free_blocks
+            .iter()
+            .position(|block| block.start > aligned_start)
+            .unwrap_or(self.free_blocks.len());
+        self.free_blocks.insert(
+            index,
+            Block {
+                start: aligned_start,
+                size,
+            },
+        );
+        self.region_starts.push(aligned_start);
+        self.free_bytes_remaining += size;
+        self.minimum_ever_free_bytes_remaining = self.free_bytes_remaining;
+    }
+
+    /* Pick the free block to allocate `wanted_size` bytes from. */
+    #[cfg(not(feature = "portHEAP_2"))]
+    fn find_block(&self, wanted_size: usize) -> Option<usize> {
+        self.free_blocks
+            .iter()
+            .position(|block| block.size >= wanted_size)
+    }
+
+    /* heap_2 uses the best fit instead of the first. */
+    #[cfg(feature = "portHEAP_2")]
+    fn find_block(&self, wanted_size: usize) -> Option<usize> {
+        self.free_blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.size >= wanted_size)
+            .min_by_key(|(_, block)| block.size)
+            .map(|(index, _)| index)
+    }
+
+    fn malloc(&mut self, size: usize) -> *mut u8 {
+        if size == 0 || size > usize::max_value() - heapSTRUCT_SIZE - portBYTE_ALIGNMENT {
+            return std::ptr::null_mut();
+        }
+
+        /* heap_1 has no use for a header, blocks are never freed. */
+        let wanted_size = if cfg!(feature = "portHEAP_1") {
+            align_up(size)
+        } else {
+            align_up(size) + heapSTRUCT_SIZE
+        };
+
+        let index = match self.find_block(wanted_size) {
+            Some(index) => index,
+            None => return std::ptr::null_mut(),
+        };
+
+        /* Split the block if the remainder is worth keeping, otherwise hand
+        out all of it. */
+        let block = self.free_blocks[index];
+        let allocated_size =
+            if cfg!(feature = "portHEAP_1") || block.size - wanted_size > heapMINIMUM_BLOCK_SIZE {
+                self.free_blocks[index] = Block {
+                    start: block.start + wanted_size,
+                    size: block.size - wanted_size,
+                };
+                wanted_size
+            } else {
+                self.free_blocks.remove(index);
+                block.size
+            };
+
+        self.free_bytes_remaining -= allocated_size;
+        if self.free_bytes_remaining < self.minimum_ever_free_bytes_remaining {
+            self.minimum_ever_free_bytes_remaining = self.free_bytes_remaining;
+        }
+
+        if cfg!(feature = "portHEAP_1") {
+            block.start as *mut u8
+        } else {
+            unsafe {
+                *(block.start as *mut usize) = allocated_size;
+            }
+            (block.start + heapSTRUCT_SIZE) as *mut u8
+        }
+    }
+
+    /* Memory is never freed with heap_1. */
+    #[cfg(feature = "portHEAP_1")]
+    fn free(&mut self, _pv: *mut u8) {}
+
+    #[cfg(not(feature = "portHEAP_1"))]
+    fn free(&mut self, pv: *mut u8) {
+        let start = pv as usize - heapSTRUCT_SIZE;
+        let size = unsafe { *(start as *const usize) };
+        self.free_bytes_remaining += size;
+
+        let index = self
+            .free_blocks
+            .iter()
+            .position(|block| block.start > start)
+            .unwrap_or(self.free_blocks.len());
+        self.free_blocks.insert(index, Block { start, size });
+
+        #[cfg(not(feature = "portHEAP_2"))]
+        {
+            /* Merge with the following block, then with the preceding one. */
+            if index + 1 < self.free_blocks.len() {
+                self.merge(index);
+            }
+            if index > 0 {
+                self.merge(index - 1);
+            }
+        }
+    }
+
+    /* Merge the free block at `index` with the one after it, if they are
+    adjacent and in the same region. */
+    #[cfg(not(any(feature = "portHEAP_1", feature = "portHEAP_2")))]
+    fn merge(&mut self, index: usize) {
+        let block = self.free_blocks[index];
+        let next = self.free_blocks[index + 1];
+        if block.start + block.size == next.start && !self.region_starts.contains(&next.start) {
+            self.free_blocks[index].size += next.size;
+            self.free_blocks.remove(index + 1);
+        }
+    }
+}
+
+/* Returns null if there is no free block large enough. */
+pub unsafe fn pvPortMalloc(xWantedSize: usize) -> *mut c_void {
+    HEAP.lock().unwrap().malloc(xWantedSize) as *mut c_void
+}
+
+pub unsafe fn vPortFree(pv: *mut c_void) {
+    if !pv.is_null() {
+        HEAP.lock().unwrap().free(pv as *mut u8);
+    }
+}
+
+pub unsafe fn xPortGetFreeHeapSize() -> usize {
+    HEAP.lock().unwrap().free_bytes_remaining
+}
+
+pub unsafe fn xPortGetMinimumEverFreeHeapSize() -> usize {
+    HEAP.lock().unwrap().minimum_ever_free_bytes_remaining
+}
+
+/* The numbers of allocations, frees and failures are counted by port.rs, for
+any heap. */
+pub unsafe fn vPortGetHeapStats() -> HeapStats {
+    let heap = HEAP.lock().unwrap();
+    /* heap_1 may be left with an empty block. */
+    let sizes = heap
+        .free_blocks
+        .iter()
+        .map(|block| block.size)
+        .filter(|size| *size > 0);
+    HeapStats {
+        available_heap_space: Some(heap.free_bytes_remaining),
+        largest_free_block: Some(sizes.clone().max().unwrap_or(0)),
+        smallest_free_block: Some(sizes.clone().min().unwrap_or(0)),
+        number_of_free_blocks: Some(sizes.count()),
+        minimum_ever_free: Some(heap.minimum_ever_free_bytes_remaining),
+        ..HeapStats::default()
+    }
+}
+
+/* The regions are taken in any order, but must not overlap. */
+#[cfg(feature = "portHEAP_5")]
+pub unsafe fn vPortDefineHeapRegions(pxHeapRegions: Vec<&'static mut [u8]>) {
+    let mut heap = HEAP.lock().unwrap();
+    assert!(
+        heap.region_starts.is_empty(),
+        "The heap regions can only be defined once."
+    );
+    for region in pxHeapRegions {
+        heap.add_region(region.as_mut_ptr() as usize, region.len());
+    }
+}
@@ -0,0 +1,258 @@
+// heap_accounting.rs - Attribute each block allocated by port_malloc() to the
+// task that asked for it, to find out which tasks use up or leak the heap.
+// There is no C counterpart to this file.
+//
+// A block is owned by the task that was running when it was allocated, even if
+// it is freed by another task, as the stack of a deleted task is freed by the
+// idle task.  Blocks allocated before the scheduler was started have no owner.
+//
+// port_malloc() hands out the stacks of the tasks and the memory the
+// application allocates with it.  TCBs, queues and the other kernel objects
+// are Rust values on the Rust heap, and so are the Boxes, Vecs and Strings of
+// the tasks.  Those are only seen if the application makes AccountingAllocator
+// its global allocator, and they are reported apart from the blocks of
+// port_malloc() by rust_usage_by_task() and rust_leak_report():
+//
+//     #[global_allocator]
+//     static ALLOCATOR: heap_accounting::AccountingAllocator =
+//         heap_accounting::AccountingAllocator;
+//
+// Recording an allocation allocates too.  Those allocations are not recorded,
+// or the allocator would call itself forever.
+use crate::port::CVoidPointer;
+use crate::sync::Mutex;
+use crate::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+
+struct Allocation {
+    owner: Option<String>,
+    size: usize,
+}
+
+lazy_static! {
+    /* Indexed by the address of the block. */
+    static ref ALLOCATIONS: Mutex<HashMap<usize, Allocation>> = Mutex::new(HashMap::new());
+    /* The same for the blocks of AccountingAllocator. */
+    static ref RUST_ALLOCATIONS: Mutex<HashMap<usize, Allocation>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    /* Set while the thread records an allocation, the allocations it makes
+    meanwhile are not recorded. */
+    static IN_ACCOUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/* Run f() with the allocations of this thread not recorded.  Returns None
+if they are not recorded already, or if the thread is being torn down. */
+fn without_accounting<R>(f: impl FnOnce() -> R) -> Option<R> {
+    let entered = IN_ACCOUNTING
+        .try_with(|in_accounting| !in_accounting.replace(true))
+        .unwrap_or(false);
+    if !entered {
+        return None;
+    }
+
+    let result = f();
+    IN_ACCOUNTING.with(|in_accounting| in_accounting.set(false));
+    Some(result)
+}
+
+/* The name of the running task, None before the scheduler is started.  The
+allocator may be called with CURRENT_TCB or the TCB locked, so this never
+waits for a lock, the block simply has no owner then. */
+fn current_task_name() -> Option<String> {
+    if !get_scheduler_running!() {
+        return None;
+    }
+    task_global::CURRENT_TCB
+        .try_read()
+        .ok()?
+        .as_ref()
+        .and_then(|task| task.try_get_name())
+}
+
+/// The blocks a task has allocated and that are not freed yet, returned by
+/// usage_by_task() and rust_usage_by_task().
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskHeapUsage {
+    /// The name of the task, None for the blocks allocated before the
+    /// scheduler was started.
+    pub task_name: Option<String>,
+    /// The number of blocks.
+    pub blocks: usize,
+    /// The number of bytes asked for, not counting the overhead of the heap.
+    pub bytes: usize,
+}
+
+/* Called by port_malloc() for each block it returns. */
+pub(crate) fn record_malloc(pv: CVoidPointer, size: usize) {
+    let record = || {
+        let owner = current_task_name();
+        ALLOCATIONS
+            .lock()
+            .unwrap()
+            .insert(pv as usize, Allocation { owner, size });
+    };
+    /* The block is recorded even while the thread is torn down, or
+    port_free() would not find it. */
+    if without_accounting(record).is_none() {
+        record();
+    }
+}
+
+/* Called by port_free() for each block it is given. */
+pub(crate) fn record_free(pv: CVoidPointer) {
+    let record = || {
+        ALLOCATIONS.lock().unwrap().remove(&(pv as usize));
+    };
+    if without_accounting(record).is_none() {
+        record();
+    }
+}
+
+/// A global allocator that takes its memory from the system allocator, and
+/// records which task allocated each block, as port_malloc() does.  Install
+/// it with `#[global_allocator]` for rust_usage_by_task() and
+/// rust_leak_report() to see the Rust heap.
+pub struct AccountingAllocator;
+
+unsafe impl GlobalAlloc for AccountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            without_accounting(|| {
+                let owner = current_task_name();
+                RUST_ALLOCATIONS.lock().unwrap().insert(
+                    ptr as usize,
+                    Allocation {
+                        owner,
+                        size: layout.size(),
+                    },
+                );
+            });
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        /* Forget the block before it can be handed out again. */
+        without_accounting(|| {
+            RUST_ALLOCATIONS.lock().unwrap().remove(&(ptr as usize));
+        });
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Add up the blocks allocated with port_malloc() that are not freed yet, by
+/// the task that allocated them.
+///
+/// # Arguments
+///
+/// Nothing
+///
+/// # Return
+///
+/// One entry for each task that owns blocks, the largest user of the heap
+/// first.
+pub fn usage_by_task() -> Vec<TaskHeapUsage> {
+    usage_of(&ALLOCATIONS)
+}
+
+/// Add up the blocks allocated with AccountingAllocator that are not freed
+/// yet, by the task that allocated them.  Empty unless AccountingAllocator is
+/// the global allocator.
+///
+/// # Arguments
+///
+/// Nothing
+///
+/// # Return
+///
+/// One entry for each task that owns blocks, the largest user of the heap
+/// first.
+pub fn rust_usage_by_task() -> Vec<TaskHeapUsage> {
+    usage_of(&RUST_ALLOCATIONS)
+}
+
+fn usage_of(allocations: &Mutex<HashMap<usize, Allocation>>) -> Vec<TaskHeapUsage> {
+    /* The Vec would be recorded in the map that is locked while it grows. */
+    without_accounting(|| sum_by_owner(&allocations.lock().unwrap()))
+        .unwrap_or_else(|| sum_by_owner(&allocations.lock().unwrap()))
+}
+
+fn sum_by_owner(allocations: &HashMap<usize, Allocation>) -> Vec<TaskHeapUsage> {
+    let mut usage: Vec<TaskHeapUsage> = Vec::new();
+    for allocation in allocations.values() {
+        match usage
+            .iter_mut()
+            .find(|task_usage| task_usage.task_name == allocation.owner)
+        {
+            Some(task_usage) => {
+                task_usage.blocks += 1;
+                task_usage.bytes += allocation.size;
+            }
+            None => usage.push(TaskHeapUsage {
+                task_name: allocation.owner.clone(),
+                blocks: 1,
+                bytes: allocation.size,
+            }),
+        }
+    }
+
+    usage.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.task_name.cmp(&b.task_name))
+    });
+    usage
+}
+
+/// Write a table of the blocks allocated with port_malloc() and not freed
+/// yet into `write_buffer`.  Each line holds the name of the task that
+/// allocated the blocks ("-" before the scheduler was started), the number
+/// of blocks and the number of bytes, the largest user of the heap first.
+/// Pass a String to get the table as a string.
+///
+/// # Arguments
+///
+/// `write_buffer` - The writer the table is written into.
+///
+/// # Return
+///
+/// The result of writing to `write_buffer`.
+pub fn leak_report<W: fmt::Write>(write_buffer: &mut W) -> fmt::Result {
+    write_report(usage_by_task(), write_buffer)
+}
+
+/// Write the same table as leak_report() for the blocks allocated with
+/// AccountingAllocator and not freed yet.
+///
+/// # Arguments
+///
+/// `write_buffer` - The writer the table is written into.
+///
+/// # Return
+///
+/// The result of writing to `write_buffer`.
+pub fn rust_leak_report<W: fmt::Write>(write_buffer: &mut W) -> fmt::Result {
+    write_report(rust_usage_by_task(), write_buffer)
+}
+
+fn write_report<W: fmt::Write>(usage: Vec<TaskHeapUsage>, write_buffer: &mut W) -> fmt::Result {
+    for task_usage in usage {
+        let name = task_usage.task_name.as_ref().map_or("-", |name| &name[..]);
+        write!(
+            write_buffer,
+            "{:width$}\t{}\t{}\r\n",
+            name,
+            task_usage.blocks,
+            task_usage.bytes,
+            width = configMAX_TASK_NAME_LEN!() - 1
+        )?;
+    }
+
+    Ok(())
+}
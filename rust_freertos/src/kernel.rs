@@ -2,9 +2,10 @@
 // This file is created by Fan Jinhao.
 // Functions defined in this file are explained in Chapter 9 and 10.
 use crate::list;
-use crate::port::UBaseType;
-use crate::projdefs::pdFALSE;
-use crate::task_control::{TaskHandle, TCB};
+use crate::port::{BaseType, TickType, UBaseType};
+use crate::projdefs::{pdFALSE, FreeRtosError};
+use crate::task_control::{task_state, TaskHandle, TCB};
+use std::fmt;
 use crate::task_global::*;
 use crate::*; // TODO: Is this line necessary?
               // use crate::task_control::TCB;
@@ -153,7 +154,8 @@ macro_rules! taskENABLE_INTERRUPTS {
 /// Nothing
 ///
 pub fn task_start_scheduler() {
-    create_idle_task();
+    let idle_task_handle = create_idle_task();
+    *IDLE_TASK_HANDLE.write().unwrap() = Some(idle_task_handle);
 
     #[cfg(feature = "configUSE_TIMERS")]
     create_timer_task();
@@ -211,29 +213,281 @@ pub fn create_idle_task() -> TaskHandle {
 
             {
                 #![cfg(feature = "configUSE_IDLE_HOOK")]
-                // TODO: Use IdleHook
-                // extern void vApplicationIdleHook( void );
-
                 /* Call the user defined function from within the idle task.  This
                 allows the application designer to add background functionality
                 without the overhead of a separate task.
-                NOTE: vApplicationIdleHook() MUST NOT, UNDER ANY CIRCUMSTANCES,
+                NOTE: THE IDLE HOOK MUST NOT, UNDER ANY CIRCUMSTANCES,
                 CALL A FUNCTION THAT MIGHT BLOCK. */
-                // vApplicationIdleHook();
-                trace!("Idle Task running");
+                call_application_hook(&IDLE_HOOK);
             }
+
+            /* Nothing else can happen before the next interrupt, so let the
+            port wait for it instead of spinning. */
+            port::port_wait_for_interrupt();
         }
     };
 
-    TCB::new()
-        .priority(0)
-        .name("Idle")
-        .initialise(idle_task_fn)
-        .unwrap_or_else(|err| panic!("Idle task creation failed with error: {:?}", err))
+    let idle_task = TCB::new().priority(0).name("Idle");
+
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    let result = match IDLE_TASK_STACK.lock().unwrap().take() {
+        Some(stack) => idle_task.initialise_with_stack(idle_task_fn, stack),
+        None => idle_task.initialise(idle_task_fn),
+    };
+    #[cfg(not(feature = "configSUPPORT_STATIC_ALLOCATION"))]
+    let result = idle_task.initialise(idle_task_fn);
+
+    result.unwrap_or_else(|err| panic!("Idle task creation failed with error: {:?}", err))
+}
+
+/// Give the idle task a stack, so it is created without port_malloc().
+/// Must be called before task_start_scheduler(), otherwise the idle task
+/// allocates its stack as any other task does.
+///
+/// * C implementation: vApplicationGetIdleTaskMemory() in task.h
+///
+/// # Arguments
+///
+/// `stack` - The stack of the idle task.  On the POSIX port it must hold at
+/// least port::portMINIMAL_STACK_BYTES.
+///
+/// # Return
+///
+/// Nothing
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+pub fn set_idle_task_stack(stack: &'static mut [port::StackType]) {
+    *IDLE_TASK_STACK.lock().unwrap() = Some(stack);
 }
 
+/// Free the stacks of the tasks that have deleted themselves and drop the
+/// scheduler's references to their TCBs.  A task cannot do this for itself,
+/// as it is still running on its stack when it calls task_delete().
+///
+/// THIS FUNCTION IS CALLED FROM THE RTOS IDLE TASK.
+///
+/// * C implementation: tasks.c 3365-3406
+///
+/// # Arguments
+///
+///
+/// # Return
+///
+/// Nothing
 fn check_tasks_waiting_termination() {
-    // TODO: Wait for task_delete.
+    #[cfg(feature = "INCLUDE_vTaskDelete")]
+    {
+        /* DELETED_TASKS_WAITING_CLEAN_UP is used to prevent task_suspend_all()
+        being called too often in the idle task. */
+        while get_deleted_tasks_waiting_clean_up!() > 0 {
+            task_suspend_all();
+            let list_is_empty = list::list_is_empty(&TASKS_WAITING_TERMINATION);
+            task_resume_all().unwrap();
+
+            if !list_is_empty {
+                taskENTER_CRITICAL!();
+                let pxtcb = list::get_owner_of_head_entry(&TASKS_WAITING_TERMINATION);
+                list::list_remove(pxtcb.get_state_list_item());
+                set_current_number_of_tasks!(get_current_number_of_tasks!() - 1);
+                set_deleted_tasks_waiting_clean_up!(get_deleted_tasks_waiting_clean_up!() - 1);
+                taskEXIT_CRITICAL!();
+
+                crate::task_control::delete_tcb(pxtcb);
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        }
+    }
+}
+
+/// Register a function the idle task calls on every iteration of its loop,
+/// replacing the previously registered one.  This is the place for
+/// background work such as kicking a watchdog or putting the processor
+/// into a low power mode.
+///
+/// The hook MUST NOT call a function that might block.
+///
+/// * C implementation: vApplicationIdleHook() in tasks.c 3177-3188
+///
+/// # Arguments
+///
+/// `hook` - The function to call, None removes the hook.
+///
+/// # Return
+///
+/// Nothing
+#[cfg(feature = "configUSE_IDLE_HOOK")]
+pub fn set_idle_hook(hook: Option<Box<dyn Fn() + Send + Sync>>) {
+    set_application_hook(&IDLE_HOOK, hook);
+}
+
+/// Register a function that is called from within the tick interrupt,
+/// replacing the previously registered one.  The hook runs at interrupt
+/// level, so it must be very short, must not use much stack and must not
+/// call any API function that is not meant to be used from an ISR.
+///
+/// * C implementation: vApplicationTickHook() in tasks.c 2626-2652
+///
+/// # Arguments
+///
+/// `hook` - The function to call, None removes the hook.
+///
+/// # Return
+///
+/// Nothing
+#[cfg(feature = "configUSE_TICK_HOOK")]
+pub fn set_tick_hook(hook: Option<Box<dyn Fn() + Send + Sync>>) {
+    set_application_hook(&TICK_HOOK, hook);
+}
+
+/// Register a function that is called whenever port_malloc() fails to
+/// allocate memory, replacing the previously registered one.
+///
+/// * C implementation: vApplicationMallocFailedHook() in heap_*.c
+///
+/// # Arguments
+///
+/// `hook` - The function to call, None removes the hook.
+///
+/// # Return
+///
+/// Nothing
+#[cfg(feature = "configUSE_MALLOC_FAILED_HOOK")]
+pub fn set_malloc_failed_hook(hook: Option<Box<dyn Fn() + Send + Sync>>) {
+    set_application_hook(&MALLOC_FAILED_HOOK, hook);
+}
+
+/* Called by port_malloc() when an allocation fails. */
+#[cfg(feature = "configUSE_MALLOC_FAILED_HOOK")]
+pub(crate) fn call_malloc_failed_hook() {
+    call_application_hook(&MALLOC_FAILED_HOOK);
+}
+
+/// Register a function that is called when the stack of a task is found to
+/// have overflowed, replacing the previously registered one.  The check is
+/// made each time a task is switched out, using the method selected by
+/// configCHECK_FOR_STACK_OVERFLOW!().  Without a hook the overflow is only
+/// logged.  A task that hits the guard page below its stack, with
+/// portSTACK_GUARD_PAGE, aborts the process without calling the hook.
+///
+/// The hook is called from within the context switch, so it must not call
+/// a function that might block.
+///
+/// * C implementation: vApplicationStackOverflowHook() in StackMacros.h
+///
+/// # Arguments
+///
+/// `hook` - The function to call with the handle of the offending task,
+/// None removes the hook.
+///
+/// # Return
+///
+/// Nothing
+#[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+pub fn set_stack_overflow_hook(hook: Option<Box<dyn Fn(TaskHandle) + Send + Sync>>) {
+    taskENTER_CRITICAL!();
+    {
+        *STACK_OVERFLOW_HOOK.lock().unwrap() = hook.map(std::sync::Arc::from);
+    }
+    taskEXIT_CRITICAL!();
+}
+
+/* Called by taskCHECK_FOR_STACK_OVERFLOW!() from within the context switch,
+which cannot preempt a critical section, so the slot is never found
+locked. */
+#[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+pub(crate) fn call_stack_overflow_hook(task: TaskHandle) {
+    let hook = STACK_OVERFLOW_HOOK.lock().unwrap().clone();
+    match hook {
+        Some(hook) => hook(task),
+        None => error!("Stack overflow in task {}", task.get_name()),
+    }
+}
+
+/// Register a function that is called when the closure of a task panics,
+/// replacing the previously registered one.  The panic is caught where the
+/// task was started, and the task is then dealt with according to its
+/// PanicPolicy.  Without a hook the panic is only logged.
+///
+/// The hook runs in the context of the task that panicked.
+///
+/// # Arguments
+///
+/// `hook` - The function to call with the handle of the task and the payload
+/// of the panic, None removes the hook.
+///
+/// # Return
+///
+/// Nothing
+pub fn set_task_panic_hook(
+    hook: Option<Box<dyn Fn(TaskHandle, &(dyn std::any::Any + Send)) + Send + Sync>>,
+) {
+    taskENTER_CRITICAL!();
+    {
+        *TASK_PANIC_HOOK.lock().unwrap() = hook.map(std::sync::Arc::from);
+    }
+    taskEXIT_CRITICAL!();
+}
+
+/* Called by the task that panicked, once the panic has been caught. */
+pub(crate) fn call_task_panic_hook(task: TaskHandle, payload: &(dyn std::any::Any + Send)) {
+    let hook: TaskPanicHook;
+    taskENTER_CRITICAL!();
+    {
+        hook = TASK_PANIC_HOOK.lock().unwrap().clone();
+    }
+    taskEXIT_CRITICAL!();
+
+    match hook {
+        Some(hook) => hook(task, payload),
+        None => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => *message,
+                None => match payload.downcast_ref::<String>() {
+                    Some(message) => message.as_str(),
+                    None => "Box<Any>",
+                },
+            };
+            error!("Task {} panicked: {}", task.get_name(), message);
+        }
+    }
+}
+
+fn set_application_hook(
+    slot: &crate::sync::Mutex<ApplicationHook>,
+    hook: Option<Box<dyn Fn() + Send + Sync>>,
+) {
+    /* The critical section keeps the tick interrupt from finding the slot
+    locked. */
+    taskENTER_CRITICAL!();
+    {
+        *slot.lock().unwrap() = hook.map(std::sync::Arc::from);
+    }
+    taskEXIT_CRITICAL!();
+}
+
+fn call_application_hook(slot: &crate::sync::Mutex<ApplicationHook>) {
+    /* Take a reference to the hook and release the lock before calling it,
+    so the hook is free to replace itself. */
+    let hook: ApplicationHook;
+    taskENTER_CRITICAL!();
+    {
+        hook = slot.lock().unwrap().clone();
+    }
+    taskEXIT_CRITICAL!();
+
+    if let Some(hook) = hook {
+        hook();
+    }
+}
+
+/* Called from the tick interrupt, which cannot preempt a critical section, so
+the slot is never found locked and no critical section is needed. */
+#[cfg(feature = "configUSE_TICK_HOOK")]
+fn call_tick_hook() {
+    let hook = TICK_HOOK.lock().unwrap().clone();
+    if let Some(hook) = hook {
+        hook();
+    }
 }
 
 /// The second (optional) part of task_start_scheduler(),
@@ -247,10 +501,10 @@ fn check_tasks_waiting_termination() {
 /// # Return
 ///
 /// Nothing
+#[cfg(feature = "configUSE_TIMERS")]
 fn create_timer_task() {
-    // TODO: This function relies on the software timer, which we may not implement.
-    // timer::create_timer_task()
-    // On fail, panic!("No enough heap space to allocate timer task.");
+    timers::create_timer_task()
+        .unwrap_or_else(|err| panic!("No enough heap space to allocate timer task: {:?}", err));
 }
 
 /// The third part of task_step_scheduler, do some initialziation
@@ -277,7 +531,7 @@ fn initialize_scheduler() {
 
     set_next_task_unblock_time!(port::portMAX_DELAY);
     set_scheduler_running!(true);
-    set_tick_count!(0);
+    set_tick_count!(configINITIAL_TICK_COUNT!());
 
     /* If configGENERATE_RUN_TIME_STATS is defined then the following
     macro must be defined to configure the timer/counter used to generate
@@ -376,18 +630,16 @@ pub fn task_suspend_all() {
 ///
 /// # Return
 ///
-/// If resuming the scheduler caused a context switch then true is
-/// returned, otherwise false is returned.
-pub fn task_resume_all() -> bool {
+/// Ok(true) if resuming the scheduler caused a context switch, otherwise
+/// Ok(false).  FreeRtosError::SchedulerNotSuspended if the call does not
+/// match a previous call to task_suspend_all().
+pub fn task_resume_all() -> Result<bool, FreeRtosError> {
     trace!("resume_all called!");
     let mut already_yielded = false;
 
-    // TODO: This is a recoverable error, use Result<> instead.
-    assert!(
-        get_scheduler_suspended!() > pdFALSE as UBaseType,
-        "The call to task_resume_all() does not match \
-         a previous call to vTaskSuspendAll()."
-    );
+    if get_scheduler_suspended!() == pdFALSE as UBaseType {
+        return Err(FreeRtosError::SchedulerNotSuspended);
+    }
 
     /* It is possible that an ISR caused a task to be removed from an event
     list while the scheduler was suspended.  If this was the case then the
@@ -441,9 +693,10 @@ pub fn task_resume_all() -> bool {
             mtCOVERAGE_TEST_MARKER!();
         }
     }
+    taskEXIT_CRITICAL!();
 
     trace!("Already yielded is {}", already_yielded);
-    already_yielded
+    Ok(already_yielded)
 }
 
 fn move_tasks_to_ready_list() -> bool {
@@ -618,7 +871,7 @@ fn generate_context_switch_stats() {
     ulTotalRunTime = portGET_RUN_TIME_COUNTER_VALUE();
     #endif
     */
-    let total_run_time = portGET_RUN_TIME_COUNTER_VALUE!() as u32;
+    let total_run_time = portGET_RUN_TIME_COUNTER_VALUE!();
     trace!("Total runtime: {}", total_run_time);
     set_total_run_time!(total_run_time);
 
@@ -653,7 +906,7 @@ pub fn task_increment_tick() -> bool {
     if get_scheduler_suspended!() == pdFALSE as UBaseType {
         /* Minor optimisation.  The tick count cannot change in this
         block. */
-        let const_tick_count = get_tick_count!() + 1;
+        let const_tick_count = get_tick_count!().wrapping_add(1);
 
         /* Increment the RTOS tick, switching the delayed and overflowed
         delayed lists if it wraps to 0. */
@@ -752,7 +1005,7 @@ pub fn task_increment_tick() -> bool {
             /* Guard against the tick hook being called when the pended tick
             count is being unwound (when the scheduler is being unlocked). */
             if get_pended_ticks!() == 0 {
-                // vApplicationTickHook();
+                call_tick_hook();
             } else {
                 mtCOVERAGE_TEST_MARKER!();
             }
@@ -763,7 +1016,7 @@ pub fn task_increment_tick() -> bool {
         /* The tick hook gets called at regular intervals, even if the
         scheduler is locked. */
         #[cfg(feature = "configUSE_TICK_HOOK")]
-        vApplicationTickHook();
+        call_tick_hook();
 
         #[cfg(feature = "configUSE_PREEMPTION")]
         {
@@ -777,6 +1030,52 @@ pub fn task_increment_tick() -> bool {
     switch_required
 }
 
+/// Get the count of ticks since task_start_scheduler() was called.
+///
+/// * C implementation: tasks.c 2127-2139
+///
+/// # Arguments
+///
+///
+/// # Return
+///
+/// The count of ticks since task_start_scheduler() was called.
+pub fn task_get_tick_count() -> TickType {
+    let ticks: TickType;
+
+    /* Critical section required if running on a 16 bit processor. */
+    taskENTER_CRITICAL!();
+    {
+        ticks = get_tick_count!();
+    }
+    taskEXIT_CRITICAL!();
+
+    ticks
+}
+
+/// This is a version of task_get_tick_count() that is safe to be called from an
+/// ISR - provided that TickType is the natural word size of the
+/// microcontroller being used or interrupt nesting is either not supported or
+/// not being used.
+///
+/// * C implementation: tasks.c 2142-2170
+///
+/// # Arguments
+///
+///
+/// # Return
+///
+/// The count of ticks since task_start_scheduler() was called.
+pub fn task_get_tick_count_from_isr() -> TickType {
+    portASSERT_IF_INTERRUPT_PRIORITY_INVALID!();
+
+    let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+    let ticks = get_tick_count!();
+    portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+
+    ticks
+}
+
 #[cfg(any(
     feature = "INCLUDE_xTaskGetSchedulerState",
     feature = "configUSE_TIMERS"
@@ -794,6 +1093,344 @@ pub fn task_get_scheduler_state() -> SchedulerState {
     }
 }
 
+/// Used with task_get_system_state() to return the state of each task in the
+/// system.
+#[cfg(feature = "configUSE_TRACE_FACILITY")]
+#[derive(Clone, Debug)]
+pub struct TaskStatus {
+    /// The handle of the task to which the rest of the information relates.
+    pub handle: TaskHandle,
+    /// The name of the task.
+    pub name: String,
+    /// A number unique to the task.
+    pub task_number: UBaseType,
+    /// The state in which the task existed when the structure was populated.
+    pub current_state: task_state,
+    /// The priority at which the task was running (may be inherited) when the
+    /// structure was populated.
+    pub current_priority: UBaseType,
+    /// The priority to which the task will return if the task's current
+    /// priority has been inherited to avoid unbounded priority inversion when
+    /// obtaining a mutex.  Only valid if configUSE_MUTEXES is enabled.
+    pub base_priority: UBaseType,
+    /// The total run time allocated to the task so far, as defined by the run
+    /// time stats clock.  Only valid when configGENERATE_RUN_TIME_STATS is
+    /// enabled.
+    pub run_time_counter: u64,
+    /// The minimum amount of stack space that has remained for the task since
+    /// the task was created, in words.  The closer this value is to zero the
+    /// closer the task has come to overflowing its stack.
+    pub stack_high_water_mark: UBaseType,
+}
+
+/// Populate a TaskStatus structure for every task in the system.  TaskStatus
+/// structures contain, among other things, members for the task handle, task
+/// name, task priority, task state, and total amount of run time consumed by
+/// the task.
+///
+/// NOTE:  This function is intended for debugging use only as its use results
+/// in the scheduler remaining suspended for an extended period.
+///
+/// * C implementation: tasks.c 2321-2388
+///
+/// # Arguments
+///
+///
+/// # Return
+///
+/// The status of every task, and the total run time since the target booted
+/// as defined by the run time stats clock (0 if configGENERATE_RUN_TIME_STATS
+/// is not enabled).
+#[cfg(feature = "configUSE_TRACE_FACILITY")]
+pub fn task_get_system_state() -> (Vec<TaskStatus>, u64) {
+    let mut task_status_array = Vec::with_capacity(get_current_number_of_tasks!() as usize);
+    let total_run_time: u64;
+
+    task_suspend_all();
+    {
+        /* Fill in a TaskStatus structure with information on each task in
+        the Ready state. */
+        for priority in (0..configMAX_PRIORITIES!()).rev() {
+            list_tasks_within_single_list(
+                &mut task_status_array,
+                &READY_TASK_LISTS[priority],
+                task_state::ready,
+            );
+        }
+
+        /* Fill in a TaskStatus structure with information on each task in
+        the Blocked state. */
+        list_tasks_within_single_list(
+            &mut task_status_array,
+            &DELAYED_TASK_LIST,
+            task_state::blocked,
+        );
+        list_tasks_within_single_list(
+            &mut task_status_array,
+            &OVERFLOW_DELAYED_TASK_LIST,
+            task_state::blocked,
+        );
+
+        #[cfg(feature = "INCLUDE_vTaskDelete")]
+        {
+            /* Fill in a TaskStatus structure with information on each task
+            that has been deleted but not yet cleaned up. */
+            list_tasks_within_single_list(
+                &mut task_status_array,
+                &TASKS_WAITING_TERMINATION,
+                task_state::deleted,
+            );
+        }
+
+        #[cfg(feature = "INCLUDE_vTaskSuspend")]
+        {
+            /* Fill in a TaskStatus structure with information on each task
+            in the Suspended state. */
+            list_tasks_within_single_list(
+                &mut task_status_array,
+                &SUSPENDED_TASK_LIST,
+                task_state::suspended,
+            );
+        }
+
+        #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
+        {
+            total_run_time = portGET_RUN_TIME_COUNTER_VALUE!();
+        }
+        #[cfg(not(feature = "configGENERATE_RUN_TIME_STATS"))]
+        {
+            total_run_time = 0;
+        }
+    }
+    task_resume_all().unwrap();
+
+    (task_status_array, total_run_time)
+}
+
+/* Characters used by task_list() to show the state of each task. */
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+const tskRUNNING_CHAR: char = 'X';
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+const tskBLOCKED_CHAR: char = 'B';
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+const tskREADY_CHAR: char = 'R';
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+const tskDELETED_CHAR: char = 'D';
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+const tskSUSPENDED_CHAR: char = 'S';
+
+/* Write the task name, padding with spaces so the columns line up when
+printed out. */
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+fn write_name_to_buffer<W: fmt::Write>(write_buffer: &mut W, task_name: &str) -> fmt::Result {
+    write!(
+        write_buffer,
+        "{:<width$}",
+        task_name,
+        width = configMAX_TASK_NAME_LEN!() - 1
+    )
+}
+
+/// Write a table of every task in the system into `write_buffer`.  Each line
+/// holds the name of the task, its state ('X' running, 'R' ready, 'B'
+/// blocked, 'S' suspended, 'D' deleted), its priority, its stack high water
+/// mark and its task number.  Pass a String to get the table as a string.
+///
+/// NOTE:  This function disables the scheduler for its duration.  It is
+/// intended for debugging use only.
+///
+/// * C implementation: tasks.c 3942-4030
+///
+/// # Arguments
+///
+/// `write_buffer` - The writer the table is written into.
+///
+/// # Return
+///
+/// The result of writing to `write_buffer`.
+#[cfg(all(
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+pub fn task_list<W: fmt::Write>(write_buffer: &mut W) -> fmt::Result {
+    /* Generate the (binary) data. */
+    let (task_status_array, _) = task_get_system_state();
+
+    /* Create a human readable table from the binary data. */
+    for status in task_status_array {
+        let status_char = match status.current_state {
+            task_state::running => tskRUNNING_CHAR,
+            task_state::ready => tskREADY_CHAR,
+            task_state::blocked => tskBLOCKED_CHAR,
+            task_state::suspended => tskSUSPENDED_CHAR,
+            task_state::deleted => tskDELETED_CHAR,
+        };
+
+        /* Write the task name to the string, padding with spaces so it
+        can be printed in tabular form more easily. */
+        write_name_to_buffer(write_buffer, &status.name)?;
+
+        /* Write the rest of the string. */
+        write!(
+            write_buffer,
+            "\t{}\t{}\t{}\t{}\r\n",
+            status_char, status.current_priority, status.stack_high_water_mark, status.task_number
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write a table of the run time of every task in the system into
+/// `write_buffer`.  Each line holds the name of the task, the absolute
+/// amount of time it has spent in the Running state, and that time as a
+/// percentage of the total run time.  Pass a String to get the table as a
+/// string.
+///
+/// NOTE:  This function disables the scheduler for its duration.  It is
+/// intended for debugging use only.
+///
+/// * C implementation: tasks.c 4037-4157
+///
+/// # Arguments
+///
+/// `write_buffer` - The writer the table is written into.
+///
+/// # Return
+///
+/// The result of writing to `write_buffer`.
+#[cfg(all(
+    feature = "configGENERATE_RUN_TIME_STATS",
+    feature = "configUSE_TRACE_FACILITY",
+    feature = "configUSE_STATS_FORMATTING_FUNCTIONS"
+))]
+pub fn task_get_run_time_stats<W: fmt::Write>(write_buffer: &mut W) -> fmt::Result {
+    /* Generate the (binary) data. */
+    let (task_status_array, total_time) = task_get_system_state();
+
+    /* For percentage calculations. */
+    let total_time = total_time / 100;
+
+    /* Avoid divide by zero errors. */
+    if total_time > 0 {
+        /* Create a human readable table from the binary data. */
+        for status in task_status_array {
+            /* What percentage of the total run time has the task used?
+            This will always be rounded down to the nearest integer.
+            total_time has already been divided by 100. */
+            let stats_as_percentage = status.run_time_counter / total_time;
+
+            /* Write the task name to the string, padding with
+            spaces so it can be printed in tabular form more
+            easily. */
+            write_name_to_buffer(write_buffer, &status.name)?;
+
+            if stats_as_percentage > 0 {
+                write!(
+                    write_buffer,
+                    "\t{}\t\t{}%\r\n",
+                    status.run_time_counter, stats_as_percentage
+                )?;
+            } else {
+                /* If the percentage is zero here then the task has
+                consumed less than 1% of the total run time. */
+                write!(write_buffer, "\t{}\t\t<1%\r\n", status.run_time_counter)?;
+            }
+        }
+    } else {
+        mtCOVERAGE_TEST_MARKER!();
+    }
+
+    Ok(())
+}
+
+/* Append the status of every task referenced from `list` to `task_status_array`. */
+#[cfg(feature = "configUSE_TRACE_FACILITY")]
+fn list_tasks_within_single_list(
+    task_status_array: &mut Vec<TaskStatus>,
+    list: &list::ListLink,
+    state: task_state,
+) {
+    for item in list::get_list_items(list) {
+        let task = list::get_list_item_owner(&item);
+        task_status_array.push(task_get_info(task, state));
+    }
+}
+
+/* Populate a TaskStatus structure with information about `task`, which was
+found in a list holding tasks in `state`.  Adapted from vTaskGetInfo() in
+tasks.c. */
+#[cfg(feature = "configUSE_TRACE_FACILITY")]
+fn task_get_info(task: TaskHandle, state: task_state) -> TaskStatus {
+    let mut current_state = state;
+
+    if task == get_current_task_handle!() {
+        /* The running task is still referenced from its ready list. */
+        current_state = task_state::running;
+    }
+
+    #[cfg(feature = "INCLUDE_vTaskSuspend")]
+    {
+        /* If the task is in the suspended list then there is a chance it is
+        actually just blocked indefinitely - so really it should be reported as
+        being in the Blocked state. */
+        if let task_state::suspended = current_state {
+            if list::get_list_item_container(&task.get_event_list_item()).is_some() {
+                current_state = task_state::blocked;
+            }
+        }
+    }
+
+    let base_priority: UBaseType;
+    #[cfg(feature = "configUSE_MUTEXES")]
+    {
+        base_priority = task.get_base_priority();
+    }
+    #[cfg(not(feature = "configUSE_MUTEXES"))]
+    {
+        base_priority = 0;
+    }
+
+    let run_time_counter: u64;
+    #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
+    {
+        run_time_counter = task.get_run_time();
+    }
+    #[cfg(not(feature = "configGENERATE_RUN_TIME_STATS"))]
+    {
+        run_time_counter = 0;
+    }
+
+    TaskStatus {
+        name: task.get_name(),
+        task_number: task.get_task_number(),
+        current_state,
+        current_priority: task.get_priority(),
+        base_priority,
+        run_time_counter,
+        stack_high_water_mark: task.check_free_stack_space(),
+        handle: task,
+    }
+}
+
 /* Define away taskRESET_READY_PRIORITY() and portRESET_READY_PRIORITY() as
 they are only required when a port optimised method of task selection is
 being used. */
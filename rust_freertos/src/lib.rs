@@ -3,7 +3,6 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(unused)]
-#![feature(fnbox)]
 #![feature(test)]
 #![feature(weak_ptr_eq)]
 #[macro_use]
@@ -13,24 +12,101 @@ extern crate log;
 extern crate simplelog;
 extern crate test;
 
-mod bindings; // This file is generated by bindgen and doesn't show up in the git repo.
+/* The kernel logs through these rather than through the macros of the log
+crate, so that the POSIX port does not preempt a task while it holds the lock
+of the logger (see sync.rs).  Nothing is logged from the tick signal handler,
+which may have stopped the task in the middle of logging. */
+macro_rules! error {
+    ($($arg:tt)+) => {
+        if let Some(_logging) = crate::sync::LockHeld::for_logging() {
+            log::error!($($arg)+);
+        }
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        if let Some(_logging) = crate::sync::LockHeld::for_logging() {
+            log::warn!($($arg)+);
+        }
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => {
+        if let Some(_logging) = crate::sync::LockHeld::for_logging() {
+            log::info!($($arg)+);
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        if let Some(_logging) = crate::sync::LockHeld::for_logging() {
+            log::trace!($($arg)+);
+        }
+    };
+}
+
 pub mod config;
 pub mod ffi;
 pub mod list;
 pub mod port;
+mod posix;
 pub mod projdefs;
+pub mod sync;
 pub mod task_control;
 pub mod task_global;
 mod trace;
-// mod task_api;
+pub mod task_api;
 pub mod kernel;
 pub mod queue;
 pub mod queue_api;
-mod queue_h;
+pub mod queue_h;
 mod task_queue;
 //mod mutex;
 pub mod semaphore;
+pub mod event_groups;
 pub mod task_timemanager;
+#[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+pub mod task_notify;
+#[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+pub mod stream_buffer;
+#[cfg(feature = "configUSE_TIMERS")]
+pub mod timers;
+#[cfg(feature = "portSTACK_GUARD_PAGE")]
+mod stack_guard;
+#[cfg(feature = "portVIRTUAL_TIME")]
+pub mod sim;
+#[cfg(feature = "portMOCK")]
+pub mod mock;
+#[cfg(any(
+    feature = "portHEAP_1",
+    feature = "portHEAP_2",
+    feature = "portHEAP_4",
+    feature = "portHEAP_5"
+))]
+mod heap;
+#[cfg(feature = "portHEAP_ACCOUNTING")]
+pub mod heap_accounting;
+
+#[cfg(all(feature = "portVIRTUAL_TIME", feature = "portSTACK_GUARD_PAGE"))]
+compile_error!("portSTACK_GUARD_PAGE needs the POSIX port, it cannot be used with portVIRTUAL_TIME.");
+#[cfg(all(feature = "portMOCK", feature = "portVIRTUAL_TIME"))]
+compile_error!("portMOCK and portVIRTUAL_TIME both replace the POSIX port, pick one.");
+#[cfg(all(feature = "portMOCK", feature = "portSTACK_GUARD_PAGE"))]
+compile_error!("portSTACK_GUARD_PAGE needs the POSIX port, it cannot be used with portMOCK.");
+#[cfg(all(feature = "configSUPPORT_STATIC_ALLOCATION", feature = "portSTACK_GUARD_PAGE"))]
+compile_error!("Guarded stacks are mapped by the port, they cannot be given by the application.");
+#[cfg(any(
+    all(feature = "portHEAP_1", feature = "portHEAP_2"),
+    all(feature = "portHEAP_1", feature = "portHEAP_4"),
+    all(feature = "portHEAP_1", feature = "portHEAP_5"),
+    all(feature = "portHEAP_2", feature = "portHEAP_4"),
+    all(feature = "portHEAP_2", feature = "portHEAP_5"),
+    all(feature = "portHEAP_4", feature = "portHEAP_5")
+))]
+compile_error!("Only one of the portHEAP_* features can be enabled.");
 
 #[cfg(test)]
 mod tests {
@@ -1,7 +1,8 @@
 use std::fmt;
-use std::sync::{Arc, RwLock, Weak};
+use std::sync::{Arc, Weak};
 
 use crate::port::{portMAX_DELAY, TickType, UBaseType};
+use crate::sync::RwLock;
 use crate::task_control::{TaskHandle, TCB};
 
 impl fmt::Debug for ListItem {
@@ -405,6 +406,22 @@ pub fn list_remove(item_link: ItemLink) -> UBaseType {
         .remove(Arc::downgrade(&item_link))
 }
 
+/// * Descrpition:
+///  Collect every item currently contained in a list, starting from the head
+///  entry and ending just before the list end marker.  This is the equivalent
+///  of walking the list with listGET_HEAD_ENTRY(), listGET_NEXT() and
+///  listGET_END_MARKER().  The index of the list is not modified.
+///
+/// # Arguments:
+///  `list` The list to walk through.
+///
+/// * Return:
+///  The items of the list in list order.
+///
+pub fn get_list_items(list: &ListLink) -> Vec<ItemLink> {
+    list.read().unwrap().get_items()
+}
+
 impl List {
     fn insert(&mut self, item_link: WeakItemLink) {
         println!("in");
@@ -511,6 +528,21 @@ impl List {
         owner
     }
 
+    fn get_items(&self) -> Vec<ItemLink> {
+        let list_end = Arc::downgrade(&self.list_end);
+        let mut items = Vec::with_capacity(self.number_of_items as usize);
+        let mut item = get_list_item_next(&list_end);
+        while !Weak::ptr_eq(&item, &list_end) {
+            let next = get_list_item_next(&item);
+            items.push(
+                item.upgrade()
+                    .unwrap_or_else(|| panic!("List item is None")),
+            );
+            item = next;
+        }
+        items
+    }
+
     fn get_owner_of_head_entry(&self) -> Weak<RwLock<TCB>> {
         let list_end = get_list_item_next(&Arc::downgrade(&self.list_end));
         let owned_index = list_end
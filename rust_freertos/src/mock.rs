@@ -0,0 +1,181 @@
+// mock.rs - A port that runs nothing and records every call the kernel makes
+// to it, for testing kernel logic without threads.
+// There is no C counterpart to this file.
+//
+// With the portMOCK feature no task ever runs: the thread that calls the
+// kernel stays in control, and acts as the task the kernel considers to be
+// running.  A yield still lets the kernel select the next task, so the state
+// of the tasks shows what a real port would have switched to.  See
+// examples/test_mock_port.rs.
+use crate::kernel;
+use crate::port::{BaseType, Port, StackType, TaskFunction_t};
+use crate::projdefs::{pdFALSE, pdTRUE};
+use crate::task_control::TaskHandle;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A call made to the port, in the terms of the Port trait.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PortCall {
+    Yield,
+    YieldFromIsr,
+    SetInterruptMask,
+    ClearInterruptMask(BaseType),
+    DisableInterrupts,
+    EnableInterrupts,
+    EnterCritical,
+    ExitCritical,
+    InitialiseStack,
+    /* With the name of the task. */
+    AddTaskHandle(String),
+    /* With the name of the task. */
+    CleanUpTcb(String),
+    StartScheduler,
+    EndScheduler,
+    WaitForInterrupt,
+    ConfigureTimerForRunTimeStats,
+    GetRunTimeCounterValue,
+}
+
+lazy_static! {
+    static ref CALLS: Mutex<Vec<PortCall>> = Mutex::new(Vec::new());
+}
+
+static CRITICAL_NESTING: AtomicUsize = AtomicUsize::new(0);
+static INTERRUPTS_ENABLED: AtomicBool = AtomicBool::new(true);
+static RUN_TIME_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn record(call: PortCall) {
+    CALLS.lock().unwrap().push(call);
+}
+
+/// Take the calls made to the port since the last call to this function,
+/// oldest first.
+pub fn take_calls() -> Vec<PortCall> {
+    CALLS.lock().unwrap().drain(..).collect()
+}
+
+/// The depth of critical sections the kernel is currently in.
+pub fn critical_nesting() -> usize {
+    CRITICAL_NESTING.load(Ordering::Relaxed)
+}
+
+/// Whether the kernel left interrupts enabled.
+pub fn interrupts_enabled() -> bool {
+    INTERRUPTS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Set the value the run time statistics are taken from.
+///
+/// # Arguments
+///
+/// `value` - The value get_run_time_counter_value() returns from now on.
+///
+/// # Return
+///
+/// Nothing
+pub fn set_run_time_counter(value: u64) {
+    RUN_TIME_COUNTER.store(value as usize, Ordering::Relaxed);
+}
+
+/// The mock port.
+pub struct Mock;
+
+impl Port for Mock {
+    fn yield_task() {
+        record(PortCall::Yield);
+        kernel::task_switch_context();
+    }
+
+    fn yield_from_isr() {
+        record(PortCall::YieldFromIsr);
+    }
+
+    fn set_interrupt_mask() -> BaseType {
+        record(PortCall::SetInterruptMask);
+        if INTERRUPTS_ENABLED.swap(false, Ordering::Relaxed) {
+            pdTRUE
+        } else {
+            pdFALSE
+        }
+    }
+
+    fn clear_interrupt_mask(mask: BaseType) {
+        record(PortCall::ClearInterruptMask(mask));
+        if mask != pdFALSE {
+            INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn disable_interrupts() {
+        record(PortCall::DisableInterrupts);
+        INTERRUPTS_ENABLED.store(false, Ordering::Relaxed);
+    }
+
+    fn enable_interrupts() {
+        record(PortCall::EnableInterrupts);
+        INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    fn enter_critical() {
+        record(PortCall::EnterCritical);
+        INTERRUPTS_ENABLED.store(false, Ordering::Relaxed);
+        CRITICAL_NESTING.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn exit_critical() {
+        record(PortCall::ExitCritical);
+        let critical_nesting = CRITICAL_NESTING.load(Ordering::Relaxed);
+        assert!(
+            critical_nesting > 0,
+            "Unmatched exit from a critical section."
+        );
+        CRITICAL_NESTING.store(critical_nesting - 1, Ordering::Relaxed);
+        if critical_nesting == 1 {
+            INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn initialise_stack(
+        top_of_stack: *mut StackType,
+        _code: TaskFunction_t,
+        _parameters: *mut c_void,
+    ) -> *mut StackType {
+        record(PortCall::InitialiseStack);
+        top_of_stack
+    }
+
+    fn add_task_handle(task: TaskHandle) {
+        record(PortCall::AddTaskHandle(task.get_name()));
+    }
+
+    fn clean_up_tcb(task: &TaskHandle) {
+        record(PortCall::CleanUpTcb(task.get_name()));
+    }
+
+    /* Returns straight away, the caller goes on as the first task. */
+    fn start_scheduler() -> BaseType {
+        record(PortCall::StartScheduler);
+        CRITICAL_NESTING.store(0, Ordering::Relaxed);
+        INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+        pdTRUE
+    }
+
+    fn end_scheduler() {
+        record(PortCall::EndScheduler);
+    }
+
+    fn wait_for_interrupt() {
+        record(PortCall::WaitForInterrupt);
+    }
+
+    fn configure_timer_for_run_time_stats() {
+        record(PortCall::ConfigureTimerForRunTimeStats);
+    }
+
+    fn get_run_time_counter_value() -> u64 {
+        record(PortCall::GetRunTimeCounterValue);
+        RUN_TIME_COUNTER.load(Ordering::Relaxed) as u64
+    }
+}
@@ -1,7 +1,15 @@
-// port.c - The wrapper of portable functions written in C.
+// port.rs - The portable layer.
 // This file is created by Fan Jinhao.
-use crate::bindings::*;
+// The ports themselves implement the Port trait below: posix.rs runs the tasks
+// on threads in real time, sim.rs on virtual time, and mock.rs records the
+// calls the kernel makes without running anything.  The port is picked at
+// compile time by the port* features, see Target.  The heap is either one of
+// the schemes in heap.rs or, by default, malloc() through portable/heap_3.c.
+use crate::posix;
 use crate::projdefs::FreeRtosError;
+use crate::task_control::TaskHandle;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 // NOTE! These type aliases may vary across different platforms.
 // TODO: Find a better way to define these types.
@@ -10,11 +18,20 @@ pub type BaseType = i64;
 pub type UBaseType = u64;
 pub type TickType = u32;
 pub type CVoidPointer = *mut std::os::raw::c_void;
+pub type TaskFunction_t = Option<unsafe extern "C" fn(*mut c_void)>;
 
 #[cfg(target_arch = "x86_64")]
-pub const portBYTE_ALIGNMENT_MASK: UBaseType = 8;
+pub const portBYTE_ALIGNMENT_MASK: UBaseType = 0x0007;
 #[cfg(not(target_arch = "x86_64"))]
-pub const portBYTE_ALIGNMENT_MASK: UBaseType = 4;
+pub const portBYTE_ALIGNMENT_MASK: UBaseType = 0x0003;
+
+/* The stack grows downwards, see portmacro.h. */
+pub const portSTACK_GROWTH: BaseType = -1;
+
+/* The stack sizes given to tasks are meant for a microcontroller, but a task
+of the POSIX port runs host code such as the logger on its stack, so no task
+stack is made smaller than this many bytes. */
+pub const portMINIMAL_STACK_BYTES: usize = 64 * 1024;
 
 #[cfg(feature = "configUSE_16_BIT_TICKS")]
 pub const portMAX_DELAY: TickType = 0xffff;
@@ -25,7 +42,7 @@ pub const portMAX_DELAY: TickType = 0xffffffff;
 #[macro_export]
 macro_rules! portYIELD {
     () => {
-        unsafe { crate::bindings::vPortYield() }
+        crate::port::port_yield()
     };
 }
 
@@ -41,9 +58,7 @@ macro_rules! portYIELD_WITHIN_API {
 macro_rules! portEND_SWITCHING_ISR {
     ($xSwitchRequired: expr) => {
         if $xSwitchRequired {
-            unsafe {
-                crate::bindings::vPortYieldFromISR();
-            }
+            crate::port::port_yield_from_isr();
         }
     };
 }
@@ -51,63 +66,63 @@ macro_rules! portEND_SWITCHING_ISR {
 #[macro_export]
 macro_rules! portYIELD_FROM_ISR {
     ($xSwitchRequired: expr) => {
-        unsafe { portEND_SWITCHING_ISR($xSwitchRequired) }
+        portEND_SWITCHING_ISR!($xSwitchRequired)
     };
 }
 
 #[macro_export]
 macro_rules! portSET_INTERRUPT_MASK_FROM_ISR {
     () => {
-        unsafe { (crate::bindings::xPortSetInterruptMask() as BaseType) }
+        crate::port::port_set_interrupt_mask()
     };
 }
 
 #[macro_export]
 macro_rules! portCLEAR_INTERRUPT_MASK_FROM_ISR {
     ($xMask: expr) => {
-        unsafe { crate::bindings::vPortClearInterruptMask($xMask as BaseType) }
+        crate::port::port_clear_interrupt_mask($xMask as BaseType)
     };
 }
 
 #[macro_export]
 macro_rules! portSET_INTERRUPT_MASK {
     () => {
-        unsafe { crate::bindings::vPortDisableInterrupts() }
+        crate::port::port_disable_interrupts()
     };
 }
 
 #[macro_export]
 macro_rules! portCLEAR_INTERRUPT_MASK {
     () => {
-        unsafe { crate::bindings::vPortEnableInterrupts() }
+        crate::port::port_enable_interrupts()
     };
 }
 
 #[macro_export]
 macro_rules! portDISABLE_INTERRUPTS {
     () => {
-        unsafe { portSET_INTERRUPT_MASK!() }
+        portSET_INTERRUPT_MASK!()
     };
 }
 
 #[macro_export]
 macro_rules! portENABLE_INTERRUPTS {
     () => {
-        unsafe { portCLEAR_INTERRUPT_MASK() }
+        portCLEAR_INTERRUPT_MASK!()
     };
 }
 
 #[macro_export]
 macro_rules! portENTER_CRITICAL {
     () => {
-        unsafe { crate::bindings::vPortEnterCritical() }
+        crate::port::port_enter_critical()
     };
 }
 
 #[macro_export]
 macro_rules! portEXIT_CRITICAL {
     () => {
-        unsafe { crate::bindings::vPortExitCritical() }
+        crate::port::port_exit_critical()
     };
 }
 
@@ -121,37 +136,25 @@ macro_rules! portNOP {
     };
 }
 
-#[macro_export]
-macro_rules! traceTASK_DELETE {
-    ($pxTaskToDelete: expr) => {
-        unsafe {
-            // TODO: Add a trace!()
-            bindings::vPortForciblyEndThread(std::sync::Arc::into_raw($pxTaskToDelete) as *mut _)
-        }
-    };
-}
-
 #[macro_export]
 macro_rules! traceTASK_CREATE {
     ($pxTaskHandle: expr) => {
-        unsafe {
-            trace!("Task creation accomplished.");
-            bindings::vPortAddTaskHandle($pxTaskHandle.as_raw())
-        }
+        trace!("Task creation accomplished.");
+        crate::port::port_add_task_handle($pxTaskHandle)
     };
 }
 
 #[macro_export]
 macro_rules! portCONFIGURE_TIMER_FOR_RUN_TIME_STATS {
     () => {
-        unsafe { crate::bindings::vPortFindTicksPerSecond() }
+        crate::port::port_configure_timer_for_run_time_stats()
     };
 }
 
 #[macro_export]
 macro_rules! portGET_RUN_TIME_COUNTER_VALUE {
     () => {
-        unsafe { crate::bindings::ulPortGetTimerValue() }
+        crate::port::port_get_run_time_counter_value()
     };
 }
 
@@ -162,23 +165,21 @@ macro_rules! portTICK_PERIOS_MS {
     };
 }
 
-// This macro was not implemented by port.c, so it was left blank.
-// You can modify it yourself.
 #[macro_export]
 macro_rules! portCLEAN_UP_TCB {
     ($pxTCB: expr) => {
-        $pxTCB
+        crate::port::port_clean_up_tcb($pxTCB)
     };
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portPRE_TASK_DELETE_HOOK {
     ($pvTaskToDelete:expr, $pxYieldPending: expr) => {};
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portSETUP_TCB {
@@ -187,28 +188,28 @@ macro_rules! portSETUP_TCB {
     };
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portSUPPRESS_TICKS_AND_SLEEP {
     ($xExpectedIdleTime:expr) => {};
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portTASK_USES_FLOATING_POINT {
     () => {};
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portASSERT_IF_INTERRUPT_PRIORITY_INVALID {
     () => {};
 }
 
-// This macro was not implemented by port.c, so it was left blank.
+// This macro is not implemented by the port, so it was left blank.
 // You can modify it yourself.
 #[macro_export]
 macro_rules! portASSERT_IF_IN_ISR {
@@ -222,8 +223,122 @@ macro_rules! portRESET_READY_PRIORITY {
     };
 }
 
+/*------------------------- The port interface ----------------------- */
+
+/// What the kernel needs from a port.  Every function of a port is an
+/// associated function, as there is only ever one port in a build: the one
+/// named by `Target`.
+pub trait Port {
+    /// Select the next task to run and switch to it, see task_switch_context().
+    fn yield_task();
+
+    /// Ask for a context switch once the current interrupt is over.
+    fn yield_from_isr();
+
+    /// Disable interrupts and return whether they were enabled, so that
+    /// clear_interrupt_mask() can restore them.  Usable from interrupts.
+    fn set_interrupt_mask() -> BaseType;
+
+    /// Restore the interrupt state returned by set_interrupt_mask().
+    fn clear_interrupt_mask(mask: BaseType);
+
+    fn disable_interrupts();
+
+    fn enable_interrupts();
+
+    /// Enter a critical section.  Critical sections nest.
+    fn enter_critical();
+
+    /// Leave a critical section, interrupts are enabled again when the
+    /// outermost one is left.
+    fn exit_critical();
+
+    /// Prepare a new task to run `code` with `parameters` the first time it
+    /// is switched to.  Returns the new top of stack, or null on failure.
+    fn initialise_stack(
+        top_of_stack: *mut StackType,
+        code: TaskFunction_t,
+        parameters: *mut c_void,
+    ) -> *mut StackType;
+
+    /// Run the task of the next call to initialise_stack() on the given
+    /// stack.  A port whose tasks do not run on their stacks ignores it.
+    fn set_next_task_stack(_stack_base: CVoidPointer, _stack_size: usize) {}
+
+    /// Tell the port which task the last call to initialise_stack() was for.
+    fn add_task_handle(_task: TaskHandle) {}
+
+    /// Called when the kernel frees a deleted task, so that the port can let
+    /// go of what it keeps for the task.
+    fn clean_up_tcb(_task: &TaskHandle) {}
+
+    /// Free the stack of a deleted task with `free_stack`.  A port that runs
+    /// the task on its stack waits until nothing runs on it any more, the
+    /// default frees it right away.
+    fn free_task_stack(_task: &TaskHandle, stack_base: CVoidPointer, free_stack: fn(CVoidPointer)) {
+        free_stack(stack_base)
+    }
+
+    /// Start the tick and run the first task.  Returns pdFALSE if the
+    /// scheduler was ended.
+    fn start_scheduler() -> BaseType;
+
+    /// Stop the tick, and give the processor back to the caller of
+    /// start_scheduler() if the port can.
+    fn end_scheduler();
+
+    /// Called by the idle task on every iteration of its loop.  Returns once
+    /// an interrupt, such as the tick, may have made another task ready.
+    fn wait_for_interrupt() {}
+
+    fn configure_timer_for_run_time_stats();
+
+    /// The time base of the run time statistics.
+    fn get_run_time_counter_value() -> u64;
+}
+
+/// The panic payload a port unwinds the thread of a deleted task with, so
+/// that the thread can exit.  It is not a panic of the task: run_wrapper()
+/// passes it on without calling the task panic hook.
+pub(crate) struct TaskDeleted;
+
+/// The port this build runs on: the POSIX port, unless portVIRTUAL_TIME or
+/// portMOCK selects another one.
+#[cfg(not(any(feature = "portVIRTUAL_TIME", feature = "portMOCK")))]
+pub type Target = posix::Posix;
+#[cfg(feature = "portVIRTUAL_TIME")]
+pub type Target = crate::sim::Sim;
+#[cfg(feature = "portMOCK")]
+pub type Target = crate::mock::Mock;
+
 /*------------------- Functions starting with "Port_" ----------------- */
 
+/* Implemented by portable/heap_3.c, or by heap.rs if one of the portHEAP_*
+features is set. */
+#[cfg(not(any(
+    feature = "portHEAP_1",
+    feature = "portHEAP_2",
+    feature = "portHEAP_4",
+    feature = "portHEAP_5"
+)))]
+extern "C" {
+    fn pvPortMalloc(xSize: usize) -> *mut c_void;
+    fn vPortFree(pv: *mut c_void);
+}
+#[cfg(any(
+    feature = "portHEAP_1",
+    feature = "portHEAP_2",
+    feature = "portHEAP_4",
+    feature = "portHEAP_5"
+))]
+use crate::heap::*;
+
+/* Counted here rather than by the heap, so that heap_stats() has them for
+heap_3 too. */
+static SUCCESSFUL_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static SUCCESSFUL_FREES: AtomicUsize = AtomicUsize::new(0);
+static FAILED_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
 // NOTE: I made some changes to the following function names!
 
 /*
@@ -234,17 +349,36 @@ pub fn port_malloc(size: usize) -> Result<CVoidPointer, FreeRtosError> {
         let ret_ptr = pvPortMalloc(size);
         if ret_ptr.is_null() {
             error!("Malloc returned null.");
+            FAILED_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "configUSE_MALLOC_FAILED_HOOK")]
+            crate::kernel::call_malloc_failed_hook();
             Err(FreeRtosError::OutOfMemory)
         } else {
+            SUCCESSFUL_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "portHEAP_ACCOUNTING")]
+            crate::heap_accounting::record_malloc(ret_ptr, size);
             Ok(ret_ptr)
         }
     }
 }
 
 pub fn port_free(pv: *mut ::std::os::raw::c_void) {
+    if !pv.is_null() {
+        SUCCESSFUL_FREES.fetch_add(1, Ordering::Relaxed);
+    }
+    #[cfg(feature = "portHEAP_ACCOUNTING")]
+    crate::heap_accounting::record_free(pv);
     unsafe { vPortFree(pv) }
 }
 
+/*
+ * Make the next task created by port_initialise_stack() run on the given
+ * stack.
+ */
+pub fn port_set_next_task_stack(stack_base: CVoidPointer, stack_size: usize) {
+    Target::set_next_task_stack(stack_base, stack_size)
+}
+
 /* NOTE: vPortInitialiseBlocks() was declared but not implemented.
 
     pub fn port_initialize_blocks() {
@@ -255,32 +389,108 @@ pub fn port_free(pv: *mut ::std::os::raw::c_void) {
 
 */
 
-/* NOTE: xPortGetFreeHeapSize() was declared but not implemented
-
-    pub fn port_get_free_heap_size() -> usize{
-        unsafe {
-            xPortGetFreeHeapSize()
-        }
-    }
+/* NOTE: xPortGetFreeHeapSize() and xPortGetMinimumEverFreeHeapSize() are
+only implemented by heap.rs, heap_3.c does not keep track of the free space. */
+#[cfg(any(
+    feature = "portHEAP_1",
+    feature = "portHEAP_2",
+    feature = "portHEAP_4",
+    feature = "portHEAP_5"
+))]
+pub fn port_get_free_heap_size() -> usize {
+    unsafe { xPortGetFreeHeapSize() }
+}
 
-*/
+#[cfg(any(
+    feature = "portHEAP_1",
+    feature = "portHEAP_2",
+    feature = "portHEAP_4",
+    feature = "portHEAP_5"
+))]
+pub fn port_get_minimum_ever_free_heap_size() -> usize {
+    unsafe { xPortGetMinimumEverFreeHeapSize() }
+}
 
-/* NOTE: xPortGetMinimumEverFreeHeapSize() was declared but not implemented
+/// The state of the heap, returned by heap_stats().
+///
+/// malloc() does not tell how much of its heap is free, so with heap_3, the
+/// default, the fields about the free space are None.  The numbers of
+/// allocations, frees and failures are counted by port_malloc() and
+/// port_free() for any heap.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeapStats {
+    /// The number of bytes left in the heap, port_get_free_heap_size().
+    pub available_heap_space: Option<usize>,
+    /// The size of the largest free block, the largest request port_malloc()
+    /// can still meet (less the header of the block).
+    pub largest_free_block: Option<usize>,
+    /// The size of the smallest free block.
+    pub smallest_free_block: Option<usize>,
+    /// The number of free blocks, the more there are for the same free space
+    /// the more fragmented the heap is.
+    pub number_of_free_blocks: Option<usize>,
+    /// The fewest bytes that have been left in the heap since the system
+    /// started, port_get_minimum_ever_free_heap_size().
+    pub minimum_ever_free: Option<usize>,
+    /// The number of calls to port_malloc() that returned a block.
+    pub successful_allocations: usize,
+    /// The number of blocks passed to port_free().  heap_1 takes them, but
+    /// never hands them out again.
+    pub successful_frees: usize,
+    /// The number of calls to port_malloc() that failed.
+    pub failed_allocations: usize,
+}
 
-    pub fn port_get_minimum_ever_free_heap_size() -> usize {
-        unsafe {
-            xPortGetMinimumEverFreeHeapSize()
-        }
+/// Take a snapshot of the state of the heap.
+///
+/// * C implementation: vPortGetHeapStats() in heap_4.c of later FreeRTOS versions
+///
+/// # Arguments
+///
+/// Nothing
+///
+/// # Return
+///
+/// The statistics of the heap.
+pub fn heap_stats() -> HeapStats {
+    #[cfg(any(
+        feature = "portHEAP_1",
+        feature = "portHEAP_2",
+        feature = "portHEAP_4",
+        feature = "portHEAP_5"
+    ))]
+    let free_space = unsafe { vPortGetHeapStats() };
+    #[cfg(not(any(
+        feature = "portHEAP_1",
+        feature = "portHEAP_2",
+        feature = "portHEAP_4",
+        feature = "portHEAP_5"
+    )))]
+    let free_space = HeapStats::default();
+
+    HeapStats {
+        successful_allocations: SUCCESSFUL_ALLOCATIONS.load(Ordering::Relaxed),
+        successful_frees: SUCCESSFUL_FREES.load(Ordering::Relaxed),
+        failed_allocations: FAILED_ALLOCATIONS.load(Ordering::Relaxed),
+        ..free_space
     }
+}
 
-*/
+/*
+ * Give heap_5 the memory it allocates from.  Must be called once, before the
+ * first call to port_malloc(), which fails until then.
+ */
+#[cfg(feature = "portHEAP_5")]
+pub fn port_define_heap_regions(regions: Vec<&'static mut [u8]>) {
+    unsafe { vPortDefineHeapRegions(regions) }
+}
 
 /*
  * Setup the hardware ready for the scheduler to take control.  This generally
  * sets up a tick interrupt and sets timers for the correct tick frequency.
  */
 pub fn port_start_scheduler() -> BaseType {
-    unsafe { xPortStartScheduler() }
+    Target::start_scheduler()
 }
 
 /*
@@ -289,7 +499,7 @@ pub fn port_start_scheduler() -> BaseType {
  * executing.
  */
 pub fn port_end_scheduler() {
-    unsafe { vPortEndScheduler() }
+    Target::end_scheduler()
 }
 
 /*
@@ -303,7 +513,7 @@ pub fn port_initialise_stack(
     pxCode: TaskFunction_t,
     pvParameters: *mut ::std::os::raw::c_void,
 ) -> Result<*mut StackType, FreeRtosError> {
-    let ret_val = unsafe { pxPortInitialiseStack(pxTopOfStack, pxCode, pvParameters) };
+    let ret_val = Target::initialise_stack(pxTopOfStack, pxCode, pvParameters);
     if ret_val.is_null() {
         error!("Port failed to initialise task stack!");
         Err(FreeRtosError::PortError)
@@ -311,3 +521,82 @@ pub fn port_initialise_stack(
         Ok(ret_val)
     }
 }
+
+/*
+ * The functions behind the port* macros above.
+ */
+pub fn port_yield() {
+    Target::yield_task()
+}
+
+pub fn port_yield_from_isr() {
+    Target::yield_from_isr()
+}
+
+pub fn port_set_interrupt_mask() -> BaseType {
+    Target::set_interrupt_mask()
+}
+
+pub fn port_clear_interrupt_mask(xMask: BaseType) {
+    Target::clear_interrupt_mask(xMask)
+}
+
+pub fn port_disable_interrupts() {
+    Target::disable_interrupts()
+}
+
+pub fn port_enable_interrupts() {
+    Target::enable_interrupts()
+}
+
+pub fn port_enter_critical() {
+    Target::enter_critical()
+}
+
+pub fn port_exit_critical() {
+    Target::exit_critical()
+}
+
+/*
+ * Tell the port which task the thread created by the last call to
+ * port_initialise_stack() runs.  The port keeps the task alive for as long
+ * as its thread exists.
+ */
+pub fn port_add_task_handle(task: TaskHandle) {
+    Target::add_task_handle(task)
+}
+
+/*
+ * Called by delete_tcb() before the task is freed.
+ */
+pub fn port_clean_up_tcb(task: &TaskHandle) {
+    Target::clean_up_tcb(task)
+}
+
+/*
+ * Called by delete_tcb(), before port_clean_up_tcb(), to free the stack of
+ * the task with `free_stack`.
+ */
+pub fn port_free_task_stack(
+    task: &TaskHandle,
+    stack_base: CVoidPointer,
+    free_stack: fn(CVoidPointer),
+) {
+    Target::free_task_stack(task, stack_base, free_stack)
+}
+
+/*
+ * Called by the idle task on every iteration of its loop.  Returns once an
+ * interrupt, such as the tick, may have made another task ready.
+ */
+pub fn port_wait_for_interrupt() {
+    Target::wait_for_interrupt()
+}
+
+pub fn port_configure_timer_for_run_time_stats() {
+    Target::configure_timer_for_run_time_stats()
+}
+
+pub fn port_get_run_time_counter_value() -> u64 {
+    Target::get_run_time_counter_value()
+}
@@ -0,0 +1,1013 @@
+// posix.rs - The POSIX port, written in Rust.
+// This file replaces portable/port.c.
+//
+// Every task runs on a thread of its own, on the stack the kernel allocated
+// for the task, but only one thread holds the processor at a time.  The
+// thread that gives up the processor names the thread that gets it and waits
+// on a condition variable until the processor is handed back.
+//
+// The tick "interrupt" comes from a thread that counts the ticks and sends
+// SIG_TICK to the thread that holds the processor.  The signal handler takes
+// the ticks on the thread of the task, as the tick interrupt of a real
+// processor would, and switches to another task if the kernel says so: the
+// preempted thread waits in the handler until it gets the processor back.
+// This is how a task that never calls into the kernel is preempted.
+//
+// A signal can stop a task at any point of its code, so the handler only
+// takes the ticks if that is safe: not in a critical section or in the port,
+// and not while the task holds one of the locks of the kernel or is logging
+// (see sync.rs).  Otherwise they stay pending, and the task takes them itself
+// the next time it enables interrupts or leaves a critical section.  The
+// locks of the host, such as the lock of the logger when the application
+// logs, are not known to the port.  A task may be stopped while it holds one,
+// and the task that runs next then waits for it in the host.  When the tick
+// finds the task that holds the processor waiting like that, the processor
+// is lent to the stopped tasks in turn, see lend_processor().  The idle task
+// waits for the next tick instead of spinning.
+//
+// The thread of a deleted task unwinds and exits.  Until it has, it still
+// runs on the stack of the task, so the stack is only freed once the idle
+// task has joined the thread.
+//
+// The virtual time port in sim.rs runs on the same machinery, but without the
+// tick thread: its ticks are raised by sim::advance_ticks() instead.
+use crate::kernel;
+use crate::port::{self, BaseType, CVoidPointer, Port, StackType, TaskFunction_t};
+use crate::projdefs::{pdFALSE, pdTRUE};
+use crate::sync::{self, LockHeld};
+use crate::task_control::TaskHandle;
+use crate::*;
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::{c_int, c_void};
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::LocalKey;
+use std::time::{Duration, Instant};
+use std::{mem, ptr, thread};
+
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum Owner {
+    /* The thread that started the scheduler.  It gets the processor back
+    when the scheduler ends, or with virtual time whenever the tasks are
+    idle. */
+    Driver,
+    /* The thread with the given index in Processor::tasks. */
+    Task(usize),
+}
+
+/* What the thread with a given index in Processor::threads runs. */
+enum TaskThread {
+    /* Created by initialise_stack(), add_task_handle() has not told which
+    task it runs yet. */
+    Created,
+    /* Runs the given task.  Holding the handle keeps the TCB alive until
+    the task is deleted. */
+    Running(TaskHandle),
+    /* The task was deleted, the thread exits as soon as it wakes up. */
+    Deleted,
+    /* The thread has exited, or could not be created.  Once it has been
+    joined, the index can be given to a new thread. */
+    Exited,
+}
+
+struct Thread {
+    task: TaskThread,
+    /* The pthread_t of the thread, until it has been joined. */
+    pthread: Option<usize>,
+    /* The stack of the deleted task and the function that frees it, called
+    once the thread has been joined. */
+    stack: Option<(usize, fn(CVoidPointer))>,
+    /* Set while the task waits for the processor in the tick signal handler,
+    having been stopped in the middle of its code.  The processor may be lent
+    to it, see lend_processor(). */
+    stopped: bool,
+}
+
+struct Processor {
+    owner: Owner,
+    threads: Vec<Thread>,
+    /* Ticks raised but not yet processed by the kernel. */
+    pending_ticks: usize,
+    scheduler_ended: bool,
+    /* How many times the processor changed hands. */
+    handovers: usize,
+    /* The thread that lent the processor to its owner, see
+    lend_processor(). */
+    lender: Option<usize>,
+    /* The thread the processor was last lent to. */
+    last_borrower: usize,
+}
+
+lazy_static! {
+    static ref PROCESSOR: Mutex<Processor> = Mutex::new(Processor {
+        owner: Owner::Driver,
+        threads: Vec::new(),
+        pending_ticks: 0,
+        scheduler_ended: false,
+        handovers: 0,
+        lender: None,
+        last_borrower: 0,
+    });
+    static ref PROCESSOR_HANDED_OVER: Condvar = Condvar::new();
+    static ref TICK_RAISED: Condvar = Condvar::new();
+    static ref START_TIME: Instant = Instant::now();
+}
+
+/* The signal the tick thread interrupts the task that holds the processor
+with. */
+const SIG_TICK: c_int = libc::SIGALRM;
+
+/* Counts in a counter of the thread for as long as it lives. */
+struct Counted(&'static LocalKey<Cell<usize>>);
+
+impl Counted {
+    fn new(counter: &'static LocalKey<Cell<usize>>) -> Self {
+        counter.with(|count| count.set(count.get() + 1));
+        Counted(counter)
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.with(|count| count.set(count.get() - 1));
+    }
+}
+
+/* Held while the thread runs the port.  It counts as a lock (see sync.rs),
+so the tick signal handler does not take the ticks while the thread is in the
+port. */
+struct InPort {
+    _in_port: Counted,
+    _held: LockHeld,
+}
+
+impl InPort {
+    fn new() -> Self {
+        InPort {
+            _in_port: Counted::new(&IN_PORT),
+            _held: LockHeld::new(),
+        }
+    }
+}
+
+fn in_port() -> bool {
+    IN_PORT.with(|in_port| in_port.get() > 0)
+}
+
+/* The processor, locked.  The tick signal handler locks it too, so it leaves
+alone a thread that holds the lock, or waits on a condition variable with it. */
+struct LockedProcessor {
+    guard: MutexGuard<'static, Processor>,
+    _locked: Counted,
+    _in_port: InPort,
+}
+
+impl LockedProcessor {
+    fn wait(self, condvar: &Condvar) -> Self {
+        let LockedProcessor {
+            guard,
+            _locked,
+            _in_port,
+        } = self;
+        LockedProcessor {
+            guard: condvar.wait(guard).unwrap(),
+            _locked,
+            _in_port,
+        }
+    }
+}
+
+impl Deref for LockedProcessor {
+    type Target = Processor;
+
+    fn deref(&self) -> &Processor {
+        &self.guard
+    }
+}
+
+impl DerefMut for LockedProcessor {
+    fn deref_mut(&mut self) -> &mut Processor {
+        &mut self.guard
+    }
+}
+
+fn lock_processor() -> LockedProcessor {
+    let _in_port = InPort::new();
+    let _locked = Counted::new(&PROCESSOR_LOCKED);
+    LockedProcessor {
+        guard: PROCESSOR.lock().unwrap(),
+        _locked,
+        _in_port,
+    }
+}
+
+/* Size of the stack the tick signal handler runs on, see SignalStack.  The
+handler runs the kernel, and the task it stopped waits on it until it gets the
+processor back. */
+const SIGNAL_STACK_SIZE: usize = 64 * 1024;
+
+/* The stack handed over by set_next_task_stack(), as base and size. */
+lazy_static! {
+    static ref NEXT_TASK_STACK: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+}
+
+/* Only the owner of the processor touches these, the hand over orders the
+accesses. */
+static CRITICAL_NESTING: AtomicUsize = AtomicUsize::new(0);
+static INTERRUPTS_ENABLED: AtomicBool = AtomicBool::new(false);
+static PENDING_YIELD: AtomicBool = AtomicBool::new(false);
+static SERVICING_INTERRUPTS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static THREAD_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+    /* How many InPort the thread holds. */
+    static IN_PORT: Cell<usize> = const { Cell::new(0) };
+    /* How many LockedProcessor the thread holds. */
+    static PROCESSOR_LOCKED: Cell<usize> = const { Cell::new(0) };
+    /* Set while the thread holds a processor lent to it, see
+    lend_processor(). */
+    static BORROWING: Cell<bool> = const { Cell::new(false) };
+    /* How many times the processor had changed hands, and how much processor
+    time the thread had used, when it last took the tick signal. */
+    static LAST_TICK_SIGNAL: Cell<Option<(usize, Duration)>> = const { Cell::new(None) };
+}
+
+pub(crate) fn me() -> Owner {
+    match THREAD_INDEX.with(|index| index.get()) {
+        Some(index) => Owner::Task(index),
+        None => Owner::Driver,
+    }
+}
+
+/* The index of the thread that runs `task`. */
+fn thread_of(processor: &Processor, task: &TaskHandle) -> Option<usize> {
+    processor
+        .threads
+        .iter()
+        .position(|thread| match &thread.task {
+            TaskThread::Running(running) => running.ptr_eq(task),
+            _ => false,
+        })
+}
+
+/* The thread that runs the task the kernel has selected. */
+fn owner_of_current_task() -> Owner {
+    let current_task = get_current_task_handle!();
+    let processor = lock_processor();
+    let index = thread_of(&processor, &current_task)
+        .unwrap_or_else(|| panic!("Task {} has no thread.", current_task.get_name()));
+    Owner::Task(index)
+}
+
+/* Wait until the processor is handed over to `me`.  The thread of a task
+that is deleted in the meantime never gets it: it unwinds instead, to exit
+from run_task_thread(). */
+fn wait_for_processor(mut processor: LockedProcessor, me: Owner) -> LockedProcessor {
+    while processor.owner != me {
+        if let Owner::Task(index) = me {
+            if let TaskThread::Deleted = processor.threads[index].task {
+                if sync::in_interrupt() {
+                    /* The task was preempted by the tick signal, at a point
+                    of its code that cannot be unwound from a signal handler.
+                    The thread stays parked for good instead, so its stack is
+                    never freed. */
+                    loop {
+                        processor = processor.wait(&PROCESSOR_HANDED_OVER);
+                    }
+                }
+                drop(processor);
+                panic::resume_unwind(Box::new(port::TaskDeleted));
+            }
+        }
+        processor = processor.wait(&PROCESSOR_HANDED_OVER);
+    }
+    processor
+}
+
+/* Give the processor to `next` and wait until it is given back.  The
+critical nesting belongs to the thread, so it is saved and restored. */
+pub(crate) fn hand_over(next: Owner) {
+    let me = me();
+    let critical_nesting = CRITICAL_NESTING.load(Ordering::Relaxed);
+
+    let mut processor = lock_processor();
+    processor.owner = next;
+    processor.handovers = processor.handovers.wrapping_add(1);
+    PROCESSOR_HANDED_OVER.notify_all();
+    drop(wait_for_processor(processor, me));
+
+    CRITICAL_NESTING.store(critical_nesting, Ordering::Relaxed);
+    INTERRUPTS_ENABLED.store(critical_nesting == 0, Ordering::Relaxed);
+}
+
+/* Let the tasks run until the processor is given back to the driver. */
+pub(crate) fn run_tasks() {
+    let next = owner_of_current_task();
+    hand_over(next);
+}
+
+pub(crate) fn scheduler_ended() -> bool {
+    lock_processor().scheduler_ended
+}
+
+/* The equivalent of a tick interrupt, taken as soon as interrupts are
+enabled. */
+pub(crate) fn raise_tick() {
+    lock_processor().pending_ticks += 1;
+    TICK_RAISED.notify_all();
+}
+
+/* Whether a tick was raised or a yield requested that the kernel has not
+seen yet. */
+pub(crate) fn interrupt_pending() -> bool {
+    lock_processor().pending_ticks > 0 || PENDING_YIELD.load(Ordering::Relaxed)
+}
+
+/* Process the ticks raised and the yield requested from "interrupts" since
+interrupts were last enabled.  This is what vPortSystemTickHandler() did. */
+pub(crate) fn service_interrupts() {
+    let _in_port = InPort::new();
+    let mut switch_required = take_ticks();
+
+    /* Select the next task only if using preemption. */
+    if !cfg!(feature = "configUSE_PREEMPTION") {
+        switch_required = false;
+    }
+    if PENDING_YIELD.swap(false, Ordering::Relaxed) || switch_required {
+        yield_task();
+    }
+}
+
+/* Let the kernel process the pending ticks, returns whether a context switch
+is required. */
+fn take_ticks() -> bool {
+    if !get_scheduler_running!() || SERVICING_INTERRUPTS.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+
+    let mut switch_required = false;
+    loop {
+        let pending_ticks = mem::replace(&mut lock_processor().pending_ticks, 0);
+        if pending_ticks == 0 {
+            break;
+        }
+        for _ in 0..pending_ticks {
+            if kernel::task_increment_tick() {
+                switch_required = true;
+            }
+        }
+    }
+    SERVICING_INTERRUPTS.store(false, Ordering::Relaxed);
+    switch_required
+}
+
+/* Nothing can happen before the next tick, so the idle task waits for it
+instead of spinning. */
+fn wait_for_tick() {
+    let _in_port = InPort::new();
+    join_exited_threads();
+
+    let mut processor = lock_processor();
+    while processor.pending_ticks == 0 && !PENDING_YIELD.load(Ordering::Relaxed) {
+        processor = processor.wait(&TICK_RAISED);
+    }
+    drop(processor);
+    service_interrupts();
+}
+
+fn tick_period() -> Duration {
+    Duration::from_millis((1000 / configTICK_RATE_HZ!()) as u64)
+}
+
+fn start_tick_thread() {
+    let period = tick_period();
+    thread::spawn(move || {
+        let mut next_tick = Instant::now();
+        loop {
+            /* Aim at the next multiple of the period, so the ticks don't
+            drift. */
+            next_tick += period;
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            }
+
+            if scheduler_ended() {
+                break;
+            }
+            raise_tick();
+            interrupt_running_task();
+        }
+    });
+}
+
+/* Send the tick signal to the thread that holds the processor, so that the
+task takes the tick even if it does not call into the kernel. */
+fn interrupt_running_task() {
+    let processor = lock_processor();
+    if let Owner::Task(index) = processor.owner {
+        /* The thread cannot exit while it holds the processor, and the
+        processor cannot change hands while it is locked. */
+        if let Some(pthread) = processor.threads[index].pthread {
+            unsafe {
+                libc::pthread_kill(pthread as libc::pthread_t, SIG_TICK);
+            }
+        }
+    }
+}
+
+/* The tick interrupt of the task that holds the processor, see
+interrupt_running_task(). */
+extern "C" fn tick_signal_handler(_signal: c_int) {
+    let processor_locked = PROCESSOR_LOCKED.with(|locked| locked.get() > 0);
+    let me = match me() {
+        Owner::Task(index) if !processor_locked => index,
+        _ => return,
+    };
+
+    /* The task may have been stopped between a call and the check of errno. */
+    let errno = unsafe { *libc::__errno_location() };
+    sync::interrupt(|| take_tick_signal(me));
+    unsafe {
+        *libc::__errno_location() = errno;
+    }
+}
+
+/* The ticks are only taken if the task was stopped at a point where the
+kernel could have been interrupted: not in a critical section, not in the port
+and not holding a lock of the kernel.  Otherwise they stay pending until the
+task enables interrupts, or until the next tick finds it at such a point. */
+fn take_tick_signal(me: usize) {
+    /* The tick ends a loan here rather than in end_loan(), which would not
+    mark the thread as stopped. */
+    if BORROWING.with(|borrowing| borrowing.get()) {
+        sync::before_next_lock(None);
+    }
+    let handovers = {
+        let processor = lock_processor();
+        /* A signal sent before the thread gave the processor away may
+        arrive late. */
+        if processor.owner != Owner::Task(me) {
+            return;
+        }
+        processor.handovers
+    };
+
+    let borrowing = if BORROWING.with(|borrowing| borrowing.replace(false)) {
+        /* The tick ends the loan. */
+        wait_stopped(me, return_processor)
+    } else {
+        let stalled = stalled(handovers);
+        let safe =
+            !in_port() && !sync::holds_lock() && INTERRUPTS_ENABLED.load(Ordering::Relaxed);
+        if safe && wait_stopped(me, service_interrupts) {
+            true
+        } else {
+            if stalled {
+                lend_processor(me, handovers);
+            }
+            false
+        }
+    };
+
+    if borrowing {
+        BORROWING.with(|borrowing| borrowing.set(true));
+        sync::before_next_lock(Some(end_loan));
+    }
+}
+
+/* Run `give_away`, which may give the processor away, with the thread marked
+as stopped by the tick signal.  Returns whether the processor was then lent
+to the thread, rather than given back to it by the kernel. */
+fn wait_stopped(me: usize, give_away: impl FnOnce()) -> bool {
+    lock_processor().threads[me].stopped = true;
+    give_away();
+    let mut processor = lock_processor();
+    processor.threads[me].stopped = false;
+    processor.lender.is_some()
+}
+
+/* Whether the thread has kept the processor since the last tick, but has
+used less than half of the tick: it waits for something in the host. */
+fn stalled(handovers: usize) -> bool {
+    let mut cpu_time: libc::timespec = unsafe { mem::zeroed() };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut cpu_time);
+    }
+    let cpu_time = Duration::new(cpu_time.tv_sec as u64, cpu_time.tv_nsec as u32);
+
+    let last = LAST_TICK_SIGNAL.with(|last| last.replace(Some((handovers, cpu_time))));
+    match last {
+        Some((last_handovers, last_cpu_time)) => {
+            last_handovers == handovers && cpu_time - last_cpu_time < tick_period() / 2
+        }
+        None => false,
+    }
+}
+
+/* The task that holds the processor waits in the host, most likely for a lock
+that a task stopped by the tick signal holds, say the lock of the logger.  The
+host would run the other task meanwhile, so the processor is lent to one of
+the tasks the tick signal stopped, in turn.  The loan ends at the next tick,
+or as soon as the borrower calls into the kernel, which still runs the task
+that lent the processor.  The lender waits in the tick signal handler for the
+whole loan, so only one of them runs at a time. */
+fn lend_processor(me: usize, handovers: usize) {
+    let mut processor = lock_processor();
+    if processor.handovers != handovers || processor.lender.is_some() {
+        return;
+    }
+    let count = processor.threads.len();
+    let borrower = (1..=count)
+        .map(|offset| (processor.last_borrower + offset) % count)
+        .find(|&index| {
+            let thread = &processor.threads[index];
+            match thread.task {
+                TaskThread::Running(_) => index != me && thread.stopped,
+                _ => false,
+            }
+        });
+
+    if let Some(borrower) = borrower {
+        processor.last_borrower = borrower;
+        processor.lender = Some(me);
+        drop(processor);
+        hand_over(Owner::Task(borrower));
+    }
+}
+
+/* Give the processor back to the task that lent it. */
+fn return_processor() {
+    let lender = lock_processor()
+        .lender
+        .take()
+        .expect("The processor was not lent.");
+    hand_over(Owner::Task(lender));
+}
+
+/* Called before a borrower takes a lock of the kernel: the task has to wait
+until the kernel runs it. */
+fn end_loan() {
+    if BORROWING.with(|borrowing| borrowing.replace(false)) {
+        return_processor();
+    }
+}
+
+/* The tick signal handler runs kernel code, which must not go on the stack of
+the task it stopped: the task may have used up most of it.  Like the
+interrupts of a real processor, the handler runs on a stack of its own, which
+each task thread installs before it runs any task code.  It is mapped apart
+from the heap the task stacks come from.  With portSTACK_GUARD_PAGE the task
+replaces it with the signal stack stack_guard.rs maps above its task stack. */
+struct SignalStack {
+    base: *mut c_void,
+}
+
+impl SignalStack {
+    fn install() -> Self {
+        unsafe {
+            let base = libc::mmap(
+                ptr::null_mut(),
+                SIGNAL_STACK_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if base == libc::MAP_FAILED {
+                panic!("Cannot map the signal stack of a task thread.");
+            }
+            let signal_stack = libc::stack_t {
+                ss_sp: base,
+                ss_flags: 0,
+                ss_size: SIGNAL_STACK_SIZE,
+            };
+            if libc::sigaltstack(&signal_stack, ptr::null_mut()) != 0 {
+                panic!("Cannot install the signal stack of a task thread.");
+            }
+            SignalStack { base }
+        }
+    }
+}
+
+impl Drop for SignalStack {
+    fn drop(&mut self) {
+        let disabled = libc::stack_t {
+            ss_sp: ptr::null_mut(),
+            ss_flags: libc::SS_DISABLE,
+            ss_size: 0,
+        };
+        unsafe {
+            /* The signal stack may still be the one stack_guard.rs mapped, it
+            is only disabled here. */
+            libc::sigaltstack(&disabled, ptr::null_mut());
+            libc::munmap(self.base, SIGNAL_STACK_SIZE);
+        }
+    }
+}
+
+fn install_tick_signal_handler() {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = tick_signal_handler as extern "C" fn(c_int) as usize;
+        /* SIG_TICK itself is blocked while the handler runs, which includes
+        the time the preempted thread waits in it for the processor. */
+        libc::sigemptyset(&mut action.sa_mask);
+        action.sa_flags = libc::SA_RESTART | libc::SA_ONSTACK;
+        if libc::sigaction(SIG_TICK, &action, ptr::null_mut()) != 0 {
+            panic!("Cannot install the tick signal handler, tasks could not be preempted.");
+        }
+    }
+}
+
+/* Start the first task.  Returns once the processor is given back to the
+driver. */
+pub(crate) fn start_first_task() -> BaseType {
+    CRITICAL_NESTING.store(0, Ordering::Relaxed);
+    INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+    run_tasks();
+
+    if scheduler_ended() {
+        pdFALSE
+    } else {
+        pdTRUE
+    }
+}
+
+pub(crate) fn end_scheduler() {
+    let me = me();
+    let mut processor = lock_processor();
+    processor.scheduler_ended = true;
+
+    if let Owner::Task(_) = me {
+        /* Give the processor back to the driver for good.  The other task
+        threads stay parked until the process exits. */
+        processor.owner = Owner::Driver;
+        PROCESSOR_HANDED_OVER.notify_all();
+        loop {
+            processor = processor.wait(&PROCESSOR_HANDED_OVER);
+        }
+    }
+}
+
+pub(crate) fn yield_task() {
+    let _in_port = InPort::new();
+
+    /* Save where the stack of a task that yields got to, as portSAVE_CONTEXT
+    would, for stack overflow checking method 1. */
+    #[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+    {
+        /* The tick signal handler runs on a stack of its own, and the stack
+        pointer of the task it stopped is not known: the one saved when the
+        task last yielded stays. */
+        if let (Owner::Task(_), false) = (me(), sync::in_interrupt()) {
+            let stack_pointer = 0u8;
+            get_current_task_handle!().set_top_of_stack(&stack_pointer as *const u8 as StackType);
+        }
+    }
+
+    /* The tasks are about to be rescheduled anyway, so take the ticks that
+    came in first: a task that only ever yields still sees time pass. */
+    if INTERRUPTS_ENABLED.load(Ordering::Relaxed) {
+        take_ticks();
+    }
+    kernel::task_switch_context();
+    let next = owner_of_current_task();
+    match me() {
+        /* The driver acts like an interrupt: the selected task runs before
+        the driver continues. */
+        Owner::Driver => hand_over(next),
+        me if me != next => hand_over(next),
+        _ => {}
+    }
+}
+
+pub(crate) fn yield_from_isr() {
+    /* Just note that a yield is required, it is done as soon as interrupts
+    are enabled. */
+    PENDING_YIELD.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn set_interrupt_mask() -> BaseType {
+    let was_enabled = INTERRUPTS_ENABLED.swap(false, Ordering::Relaxed);
+    if was_enabled {
+        pdTRUE
+    } else {
+        pdFALSE
+    }
+}
+
+pub(crate) fn clear_interrupt_mask(mask: BaseType) {
+    if mask != pdFALSE {
+        enable_interrupts();
+    }
+}
+
+pub(crate) fn disable_interrupts() {
+    INTERRUPTS_ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub(crate) fn enable_interrupts() {
+    service_interrupts();
+    INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn enter_critical() {
+    disable_interrupts();
+    CRITICAL_NESTING.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn exit_critical() {
+    /* Check for unmatched exits. */
+    let critical_nesting = CRITICAL_NESTING.load(Ordering::Relaxed);
+    if critical_nesting > 0 {
+        CRITICAL_NESTING.store(critical_nesting - 1, Ordering::Relaxed);
+    }
+
+    /* If we have reached 0 then re-enable the interrupts, and take the ones
+    that came in the meantime. */
+    if CRITICAL_NESTING.load(Ordering::Relaxed) == 0 {
+        enable_interrupts();
+    }
+}
+
+/* What a task thread needs to know to start. */
+#[derive(Copy, Clone)]
+struct ThreadStart {
+    index: usize,
+    code: unsafe extern "C" fn(*mut c_void),
+    parameters: usize,
+}
+
+fn run_task_thread(start: ThreadStart) {
+    THREAD_INDEX.with(|thread_index| thread_index.set(Some(start.index)));
+
+    /* Wait until the task is first given the processor. */
+    let started = panic::catch_unwind(|| {
+        drop(wait_for_processor(
+            lock_processor(),
+            Owner::Task(start.index),
+        ));
+    });
+
+    /* A task deleted before it ever ran unwinds from wait_for_processor()
+    right away, later on run_wrapper() stops the unwinding and returns. */
+    if started.is_ok() {
+        CRITICAL_NESTING.store(0, Ordering::Relaxed);
+        INTERRUPTS_ENABLED.store(true, Ordering::Relaxed);
+        unsafe { (start.code)(start.parameters as *mut c_void) }
+    }
+    lock_processor().threads[start.index].task = TaskThread::Exited;
+}
+
+/* Run the thread on the stack handed over by set_next_task_stack(), which
+std::thread cannot do.  Returns the pthread_t of the thread. */
+fn spawn_task_thread(start: ThreadStart) -> Option<usize> {
+    extern "C" fn thread_entry(start: *mut c_void) -> *mut c_void {
+        let start = unsafe { Box::from_raw(start as *mut ThreadStart) };
+        let _signal_stack = SignalStack::install();
+        run_task_thread(*start);
+        ptr::null_mut()
+    }
+
+    let (stack_base, stack_size) = NEXT_TASK_STACK
+        .lock()
+        .unwrap()
+        .take()
+        .expect("No stack was handed over for the task.");
+    let start = Box::into_raw(Box::new(start));
+
+    unsafe {
+        let mut attributes: libc::pthread_attr_t = mem::zeroed();
+        libc::pthread_attr_init(&mut attributes);
+        /* Fails if the stack is smaller than PTHREAD_STACK_MIN, the thread
+        must not be created on a stack of its own choosing then. */
+        let mut result =
+            libc::pthread_attr_setstack(&mut attributes, stack_base as *mut c_void, stack_size);
+
+        let mut thread: libc::pthread_t = mem::zeroed();
+        if result == 0 {
+            result =
+                libc::pthread_create(&mut thread, &attributes, thread_entry, start as *mut c_void);
+        }
+        libc::pthread_attr_destroy(&mut attributes);
+
+        if result == 0 {
+            Some(thread as usize)
+        } else {
+            drop(Box::from_raw(start));
+            None
+        }
+    }
+}
+
+pub(crate) fn set_next_task_stack(stack_base: CVoidPointer, stack_size: usize) {
+    *NEXT_TASK_STACK.lock().unwrap() = Some((stack_base as usize, stack_size));
+}
+
+/* Join the threads that have exited, and free the stacks of their tasks,
+which nothing runs on any more. */
+pub(crate) fn join_exited_threads() {
+    let exited: Vec<(usize, usize)> = PROCESSOR
+        .lock()
+        .unwrap()
+        .threads
+        .iter()
+        .enumerate()
+        .filter_map(|(index, thread)| match (&thread.task, thread.pthread) {
+            (TaskThread::Exited, Some(pthread)) => Some((index, pthread)),
+            _ => None,
+        })
+        .collect();
+
+    for (index, pthread) in exited {
+        unsafe {
+            libc::pthread_join(pthread as libc::pthread_t, ptr::null_mut());
+        }
+        let stack = {
+            let mut processor = lock_processor();
+            processor.threads[index].pthread = None;
+            processor.threads[index].stack.take()
+        };
+        if let Some((stack_base, free_stack)) = stack {
+            free_stack(stack_base as CVoidPointer);
+        }
+    }
+}
+
+/* Create the thread of a new task, on the stack handed over by
+set_next_task_stack().  It waits until it is first given the processor. */
+pub(crate) fn initialise_stack(
+    top_of_stack: *mut StackType,
+    code: TaskFunction_t,
+    parameters: *mut c_void,
+) -> *mut StackType {
+    let code = code.expect("A task needs a function to run.");
+
+    /* Reuse the index of a thread that has exited and been joined. */
+    join_exited_threads();
+    let thread = Thread {
+        task: TaskThread::Created,
+        pthread: None,
+        stack: None,
+        stopped: false,
+    };
+    let mut processor = lock_processor();
+    let joined = processor
+        .threads
+        .iter()
+        .position(|thread| matches!(thread.task, TaskThread::Exited) && thread.pthread.is_none());
+    let index = match joined {
+        Some(index) => {
+            processor.threads[index] = thread;
+            index
+        }
+        None => {
+            processor.threads.push(thread);
+            processor.threads.len() - 1
+        }
+    };
+    drop(processor);
+
+    let start = ThreadStart {
+        index,
+        code,
+        parameters: parameters as usize,
+    };
+    let pthread = spawn_task_thread(start);
+    let mut processor = lock_processor();
+    processor.threads[index].pthread = pthread;
+    if pthread.is_some() {
+        top_of_stack
+    } else {
+        /* Thread create failed, signal the failure. */
+        processor.threads[index].task = TaskThread::Exited;
+        ptr::null_mut()
+    }
+}
+
+/* Tell the port which task the thread created last runs. */
+pub(crate) fn add_task_handle(task: TaskHandle) {
+    let mut processor = lock_processor();
+    let created = processor
+        .threads
+        .iter()
+        .position(|thread| matches!(thread.task, TaskThread::Created))
+        .expect("No thread was created for the task.");
+    processor.threads[created].task = TaskThread::Running(task);
+}
+
+/* The task was deleted: drop its handle, and wake its thread so that it
+exits. */
+pub(crate) fn clean_up_tcb(task: &TaskHandle) {
+    let mut processor = lock_processor();
+    if let Some(index) = thread_of(&processor, task) {
+        processor.threads[index].task = TaskThread::Deleted;
+        PROCESSOR_HANDED_OVER.notify_all();
+    }
+}
+
+/* The thread of the task still runs on its stack, so leave it to
+join_exited_threads() to free the stack. */
+pub(crate) fn free_task_stack(
+    task: &TaskHandle,
+    stack_base: CVoidPointer,
+    free_stack: fn(CVoidPointer),
+) {
+    let mut processor = lock_processor();
+    match thread_of(&processor, task) {
+        Some(index) => processor.threads[index].stack = Some((stack_base as usize, free_stack)),
+        None => {
+            drop(processor);
+            free_stack(stack_base);
+        }
+    }
+}
+
+/// The POSIX port: the tasks run in real time, with a tick thread.
+pub struct Posix;
+
+impl Port for Posix {
+    fn yield_task() {
+        yield_task()
+    }
+
+    fn yield_from_isr() {
+        yield_from_isr()
+    }
+
+    fn set_interrupt_mask() -> BaseType {
+        set_interrupt_mask()
+    }
+
+    fn clear_interrupt_mask(mask: BaseType) {
+        clear_interrupt_mask(mask)
+    }
+
+    fn disable_interrupts() {
+        disable_interrupts()
+    }
+
+    fn enable_interrupts() {
+        enable_interrupts()
+    }
+
+    fn enter_critical() {
+        enter_critical()
+    }
+
+    fn exit_critical() {
+        exit_critical()
+    }
+
+    fn initialise_stack(
+        top_of_stack: *mut StackType,
+        code: TaskFunction_t,
+        parameters: *mut c_void,
+    ) -> *mut StackType {
+        initialise_stack(top_of_stack, code, parameters)
+    }
+
+    fn set_next_task_stack(stack_base: CVoidPointer, stack_size: usize) {
+        set_next_task_stack(stack_base, stack_size)
+    }
+
+    fn add_task_handle(task: TaskHandle) {
+        add_task_handle(task)
+    }
+
+    fn clean_up_tcb(task: &TaskHandle) {
+        clean_up_tcb(task)
+    }
+
+    fn free_task_stack(task: &TaskHandle, stack_base: CVoidPointer, free_stack: fn(CVoidPointer)) {
+        free_task_stack(task, stack_base, free_stack)
+    }
+
+    /* Will not return until the scheduler ends. */
+    fn start_scheduler() -> BaseType {
+        install_tick_signal_handler();
+        start_tick_thread();
+        start_first_task()
+    }
+
+    fn end_scheduler() {
+        end_scheduler()
+    }
+
+    fn wait_for_interrupt() {
+        wait_for_tick()
+    }
+
+    fn configure_timer_for_run_time_stats() {
+        lazy_static::initialize(&START_TIME);
+    }
+
+    /* Run time statistics are counted in microseconds since the scheduler
+    started. */
+    fn get_run_time_counter_value() -> u64 {
+        START_TIME.elapsed().as_micros() as u64
+    }
+}
@@ -1,5 +1,9 @@
 // projdefs.rs - Basic (maybe useless) constant definitions.
 use crate::port::BaseType;
+use crate::queue_h::QueueError;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
 
 pub const pdTRUE: BaseType = 1;
 pub const pdFALSE: BaseType = 0;
@@ -7,6 +11,8 @@ pub const pdFALSE: BaseType = 0;
 pub const pdPASS: BaseType = pdTRUE;
 pub const pdFAIL: BaseType = pdFALSE;
 
+/// The error returned by every fallible function of the kernel, including the
+/// queue, semaphore and timer functions that used to return `QueueError`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FreeRtosError {
     OutOfMemory,
@@ -15,6 +21,102 @@ pub enum FreeRtosError {
     ProcessorHasShutDown,
     DeadLocked,
     PortError,
+    DelayAborted,
+    QueueSendTimeout,
+    QueueReceiveTimeout,
+    MutexTimeout,
+    QueueFull,
+    QueueEmpty,
+    /// A mutex was given by a task that does not hold it.
+    NotMutexHolder,
+    /// task_resume_all() was called without a matching task_suspend_all().
+    SchedulerNotSuspended,
+    /// A stack given to a task is smaller than port::portMINIMAL_STACK_BYTES.
+    StackTooSmall,
+    /// A queue or semaphore was added to a queue set while it is in one.
+    AlreadyInSet,
+    /// A queue or semaphore was removed from a queue set it is not in.
+    NotInSet,
+    /// A queue or semaphore was added to or removed from a queue set while
+    /// it has events pending.
+    PendingSetEvents,
+    /// A notification was sent without overwriting to a task that already
+    /// had one pending.
+    NotificationPending,
+    /// A stream buffer was reset while tasks are blocked on it.
+    TasksBlocked,
+    /// A trigger level larger than the stream buffer was given.
+    InvalidTriggerLevel,
+    /// task_abort_delay() was called on a task that is not blocked.
+    NotBlocked,
+}
+
+impl fmt::Display for FreeRtosError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FreeRtosError::OutOfMemory => write!(f, "Out of memory"),
+            FreeRtosError::Timeout => write!(f, "Timed out"),
+            FreeRtosError::TaskNotFound => write!(f, "Task not found"),
+            FreeRtosError::ProcessorHasShutDown => write!(f, "Processor has shut down"),
+            FreeRtosError::DeadLocked => write!(f, "Deadlocked"),
+            FreeRtosError::PortError => write!(f, "Port error"),
+            FreeRtosError::DelayAborted => write!(f, "Delay aborted"),
+            FreeRtosError::QueueSendTimeout => write!(f, "Timed out sending to a queue"),
+            FreeRtosError::QueueReceiveTimeout => write!(f, "Timed out receiving from a queue"),
+            FreeRtosError::MutexTimeout => write!(f, "Timed out taking a mutex"),
+            FreeRtosError::QueueFull => write!(f, "Queue full"),
+            FreeRtosError::QueueEmpty => write!(f, "Queue empty"),
+            FreeRtosError::NotMutexHolder => write!(f, "The mutex is not held by the calling task"),
+            FreeRtosError::SchedulerNotSuspended => write!(
+                f,
+                "task_resume_all() does not match a previous call to task_suspend_all()"
+            ),
+            FreeRtosError::StackTooSmall => write!(f, "The stack is too small for a task"),
+            FreeRtosError::AlreadyInSet => write!(f, "Already in a queue set"),
+            FreeRtosError::NotInSet => write!(f, "Not in the queue set"),
+            FreeRtosError::PendingSetEvents => write!(f, "Events are pending for the queue set"),
+            FreeRtosError::NotificationPending => {
+                write!(f, "The task already has a notification pending")
+            }
+            FreeRtosError::TasksBlocked => write!(f, "Tasks are blocked on the stream buffer"),
+            FreeRtosError::InvalidTriggerLevel => {
+                write!(f, "The trigger level is larger than the stream buffer")
+            }
+            FreeRtosError::NotBlocked => write!(f, "The task is not blocked"),
+        }
+    }
+}
+
+impl Error for FreeRtosError {}
+
+impl From<QueueError> for FreeRtosError {
+    fn from(error: QueueError) -> Self {
+        match error {
+            QueueError::QueueSendTimeout => FreeRtosError::QueueSendTimeout,
+            QueueError::QueueReceiveTimeout => FreeRtosError::QueueReceiveTimeout,
+            QueueError::MutexTimeout => FreeRtosError::MutexTimeout,
+            QueueError::QueueFull => FreeRtosError::QueueFull,
+            QueueError::QueueEmpty => FreeRtosError::QueueEmpty,
+            QueueError::DelayAborted => FreeRtosError::DelayAborted,
+        }
+    }
+}
+
+/* Gives the error back if QueueError has nothing like it. */
+impl TryFrom<FreeRtosError> for QueueError {
+    type Error = FreeRtosError;
+
+    fn try_from(error: FreeRtosError) -> Result<Self, Self::Error> {
+        match error {
+            FreeRtosError::QueueSendTimeout => Ok(QueueError::QueueSendTimeout),
+            FreeRtosError::QueueReceiveTimeout => Ok(QueueError::QueueReceiveTimeout),
+            FreeRtosError::MutexTimeout => Ok(QueueError::MutexTimeout),
+            FreeRtosError::QueueFull => Ok(QueueError::QueueFull),
+            FreeRtosError::QueueEmpty => Ok(QueueError::QueueEmpty),
+            FreeRtosError::DelayAborted => Ok(QueueError::DelayAborted),
+            error => Err(error),
+        }
+    }
 }
 
 #[macro_export]
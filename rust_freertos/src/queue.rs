@@ -1,9 +1,12 @@
 use std::collections::VecDeque;
 use crate::port::*;
 use crate::list::*;
+use crate::projdefs::FreeRtosError;
 use crate::queue_h::*;
 use crate::*;
 use crate::task_queue::*;
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+use crate::queue_api::{QueueSet, QueueSetMemberHandle};
 
 pub const queueQUEUE_IS_MUTEX: UBaseType = 0;
 pub const queueUNLOCKED: i8 = -1;
@@ -11,12 +14,109 @@ pub const queueLOCKED_UNMODIFIED: i8 = 0;
 pub const queueSEMAPHORE_QUEUE_ITEM_LENGTH: UBaseType = 0;
 pub const queueMUTEX_GIVE_BLOCK_TIME: TickType = 0;
 
+/* The storage area of a queue.  queue_generic_create() allocates it and lets
+it grow up to the length of the queue, the static constructors take a buffer
+from the application that is used as it is. */
+enum QueueStorage<T> {
+    Dynamic(VecDeque<T>),
+    /* The buffer is given as &'static mut [T], it is stored as a pointer and a
+    length so that T need not be 'static everywhere. */
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    Static(*mut T, usize),
+}
+
+impl<T> Default for QueueStorage<T> {
+    fn default() -> Self {
+        QueueStorage::Dynamic(VecDeque::new())
+    }
+}
+
+impl<T> QueueStorage<T>
+where
+    T: Default + Clone,
+{
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    fn slots(&mut self) -> &mut [T] {
+        match *self {
+            QueueStorage::Static(buffer, length) => unsafe {
+                std::slice::from_raw_parts_mut(buffer, length)
+            },
+            QueueStorage::Dynamic(_) => unreachable!(),
+        }
+    }
+
+    fn get(&self, uxIndex: usize) -> Option<&T> {
+        match *self {
+            QueueStorage::Dynamic(ref items) => items.get(uxIndex),
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            QueueStorage::Static(buffer, length) => {
+                if uxIndex < length {
+                    unsafe { Some(&*buffer.add(uxIndex)) }
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /* Store an item in the slot at `uxIndex`.  Slots are overwritten rather
+    than inserted, otherwise the items that have not been read yet would move
+    once the write position wraps around. */
+    fn write(&mut self, uxIndex: usize, pvItemToQueue: T) {
+        match self {
+            QueueStorage::Dynamic(items) => {
+                if uxIndex < items.len() {
+                    items[uxIndex] = pvItemToQueue;
+                } else {
+                    items.resize(uxIndex, T::default());
+                    items.push_back(pvItemToQueue);
+                }
+            }
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            QueueStorage::Static(..) => self.slots()[uxIndex] = pvItemToQueue,
+        }
+    }
+
+    /* The holder of a mutex is kept in front of the storage, there is never
+    more than one. */
+    fn push_front(&mut self, pvItemToQueue: T) {
+        match self {
+            QueueStorage::Dynamic(items) => items.push_front(pvItemToQueue),
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            QueueStorage::Static(..) => self.slots()[0] = pvItemToQueue,
+        }
+    }
+
+    fn pop_front(&mut self) {
+        match self {
+            QueueStorage::Dynamic(items) => {
+                items.pop_front();
+            }
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            QueueStorage::Static(..) => self.slots()[0] = T::default(),
+        }
+    }
+
+    /* Drop the items the queue holds. */
+    fn clear(&mut self) {
+        match self {
+            QueueStorage::Dynamic(items) => items.clear(),
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            QueueStorage::Static(..) => {
+                for slot in self.slots() {
+                    *slot = T::default();
+                }
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct QueueDefinition<T>
 where
     T: Default + Clone,
 {
-    pcQueue: VecDeque<T>,
+    pcQueue: QueueStorage<T>,
 
     pcHead: UBaseType,
     pcTail: UBaseType,
@@ -39,8 +139,10 @@ where
     ))]
     ucStaticallyAllocated: u8,
 
+    /* The queue set this queue or semaphore belongs to, and the handle it
+    posts to the queue set when it has an event. */
     #[cfg(feature = "configUSE_QUEUE_SETS")]
-    pxQueueSetContainer: Option<Box<QueueDefinition>>,
+    pxQueueSetContainer: Option<(QueueSet, QueueSetMemberHandle)>,
 
     #[cfg(feature = "configUSE_TRACE_FACILITY")]
     uxQueueNumber: UBaseType,
@@ -67,7 +169,48 @@ where
     #[cfg(feature = "configSUPPORT_DYNAMIC_ALLOCATION")]
     pub fn queue_generic_create(uxQueueLength: UBaseType, ucQueueType: QueueType) -> Self {
         let mut queue: QueueDefinition<T> = Default::default();
-        queue.pcQueue = VecDeque::with_capacity(uxQueueLength as usize);
+        queue.pcQueue = QueueStorage::Dynamic(VecDeque::with_capacity(uxQueueLength as usize));
+        queue.initialise_new_queue(uxQueueLength, ucQueueType);
+        queue
+    }
+
+    /// # Description
+    /// Create a new queue whose items are stored in `pucQueueStorage`, so that
+    /// the queue never allocates memory for them.  The length of the queue is
+    /// the length of the buffer.  Unlike in C the queue itself and its lists
+    /// of waiting tasks are not in the buffer.
+    ///
+    /// * C implementation: xQueueGenericCreateStatic() in queue.c
+    /// # Argument
+    /// `pucQueueStorage` - the buffer that holds the items of the queue
+    /// `ucQueueType` - the type of the queue
+    ///
+    /// # Return
+    /// The created queue.
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    pub fn queue_generic_create_static(
+        pucQueueStorage: &'static mut [T],
+        ucQueueType: QueueType,
+    ) -> Self
+    where
+        T: 'static,
+    {
+        assert!(
+            !pucQueueStorage.is_empty(),
+            "The storage of a queue must hold at least one item."
+        );
+        let uxQueueLength = pucQueueStorage.len() as UBaseType;
+        let mut queue: QueueDefinition<T> = Default::default();
+        queue.pcQueue = QueueStorage::Static(pucQueueStorage.as_mut_ptr(), pucQueueStorage.len());
+
+        {
+            #![cfg(feature = "configSUPPORT_DYNAMIC_ALLOCATION")]
+            /* Queues can be allocated either statically or dynamically, so
+            note this queue was allocated statically in case the queue is
+            later deleted. */
+            queue.ucStaticallyAllocated = crate::projdefs::pdTRUE as u8;
+        }
+
         queue.initialise_new_queue(uxQueueLength, ucQueueType);
         queue
     }
@@ -104,8 +247,8 @@ where
     /// * `xNewQueue` - whether the queue is a new queue
     /// 
     /// # Return
-    /// `Result<(),QueueError>` - Ok() if the queue was successfully reseted.
-    pub fn queue_generic_reset(&mut self, xNewQueue: bool) -> Result<(), QueueError> {
+    /// `Result<(),FreeRtosError>` - Ok() if the queue was successfully reseted.
+    pub fn queue_generic_reset(&mut self, xNewQueue: bool) -> Result<(), FreeRtosError> {
         //xNewQueue源码中为BaseType，改为bool
         //返回值原为BaseType，改为result
         taskENTER_CRITICAL!();
@@ -149,13 +292,13 @@ where
     /// `xCopyPosition` - the position that the item is to be placed to.
     ///
     /// # Return
-    /// Ok() if the item is successfully posted, otherwise Err(QueueError::QueueEmpty).
+    /// Ok() if the item is successfully posted, otherwise Err(FreeRtosError::QueueEmpty).
     pub fn queue_generic_send(
         &mut self,
         pvItemToQueue: T,
         xTicksToWait: TickType,
         xCopyPosition: BaseType,
-    ) -> Result<(), QueueError> {
+    ) -> Result<(), FreeRtosError> {
         let mut xEntryTimeSet: bool = false;
         let mut xTimeOut: time_out = Default::default();
         let mut xTicksToWait = xTicksToWait;
@@ -183,28 +326,27 @@ where
                     self.copy_data_to_queue(pvItemToQueue, xCopyPosition);
                     trace!("Queue can be sent");
 
-                    /* The queue is a member of a queue set, and posting
-                    to the queue set caused a higher priority task to
-                    unblock. A context switch is required. */
-                    #[cfg(feature = "configUSE_QUEUE_SETS")]
-                    match self.pxQueueSetContainer {
-                        Some => {
-                            if notify_queue_set_container(&self, &xCopyPosition) != false {
+                    {
+                        #![cfg(feature = "configUSE_QUEUE_SETS")]
+                        if self.is_set_event_on_send() {
+                            if self.notify_queue_set_container(xCopyPosition) != false {
+                                /* The queue is a member of a queue set, and posting
+                                to the queue set caused a higher priority task to
+                                unblock. A context switch is required. */
                                 queueYIELD_IF_USING_PREEMPTION!();
                             } else {
                                 mtCOVERAGE_TEST_MARKER!();
                             }
-                        }
-                        None => {
-                            if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
-                                if task_queue::task_remove_from_event_list(
-                                    &self.xTasksWaitingToReceive,
-                                ) {
-                                    queueYIELD_IF_USING_PREEMPTION!();
-                                } else {
-                                    mtCOVERAGE_TEST_MARKER!();
-                                }
+                        } else if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
+                            if task_queue::task_remove_from_event_list(
+                                &self.xTasksWaitingToReceive,
+                            ) {
+                                queueYIELD_IF_USING_PREEMPTION!();
+                            } else {
+                                mtCOVERAGE_TEST_MARKER!();
                             }
+                        } else {
+                            mtCOVERAGE_TEST_MARKER!();
                         }
                     }
 
@@ -253,7 +395,7 @@ where
                         the function. */
                         traceQUEUE_SEND_FAILED!(&self);
                         trace!("Queue Send: QueueFull");
-                        return Err(QueueError::QueueFull);
+                        return Err(FreeRtosError::QueueFull);
                     } else if !xEntryTimeSet {
                         /* The queue was full and a block time was specified so
                         configure the timeout structure. */
@@ -291,21 +433,24 @@ where
                     task is already in a ready list before it yields - in which
                     case the yield will not cause a context switch unless there
                     is also a higher priority task in the pending ready list. */
-                    if !kernel::task_resume_all() {
+                    if !kernel::task_resume_all().unwrap() {
                         portYIELD_WITHIN_API!();
                     }
                 } else {
                     /* Try again. */
                     self.unlock_queue();
-                    kernel::task_resume_all();
+                    kernel::task_resume_all().unwrap();
                 }
             } else {
                 /* The timeout has expired. */
                 self.unlock_queue();
-                kernel::task_resume_all();
+                kernel::task_resume_all().unwrap();
 
                 traceQUEUE_SEND_FAILED!(self);
-                return Err(QueueError::QueueFull);
+                if xTimeOut.delay_aborted() {
+                    return Err(FreeRtosError::DelayAborted);
+                }
+                return Err(FreeRtosError::QueueFull);
             }
         }
     }
@@ -328,11 +473,11 @@ where
         &mut self,
         pvItemToQueue: T,
         xCopyPosition: BaseType,
-    ) -> (Result<(), QueueError>, bool) {
+    ) -> (Result<(), FreeRtosError>, bool) {
         //原先参数const pxHigherPriorityTaskWoken: BaseType作为返回值的第二个元素，bool型
         //返回值改为struct
 
-        let mut xReturn: Result<(), QueueError> = Ok(());
+        let mut xReturn: Result<(), FreeRtosError> = Ok(());
         let mut pxHigherPriorityTaskWoken: bool = false; //默认为false,下面一些情况改为true
 
         portASSERT_IF_INTERRUPT_PRIORITY_INVALID!();
@@ -344,28 +489,25 @@ where
                 self.copy_data_to_queue(pvItemToQueue, xCopyPosition);
 
                 if cTxLock == queueUNLOCKED {
-                    #[cfg(feature = "configUSE_QUEUE_SETS")]
-                    match self.pxQueueSetContainer {
-                        Some => {
-                            if notify_queue_set_container(self, xCopyPosition) != false {
+                    {
+                        #![cfg(feature = "configUSE_QUEUE_SETS")]
+                        if self.is_set_event_on_send() {
+                            if self.notify_queue_set_container(xCopyPosition) != false {
                                 pxHigherPriorityTaskWoken = true
                             } else {
                                 mtCOVERAGE_TEST_MARKER!();
                             }
-                        }
-                        None => {
-                            if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
-                                if task_queue::task_remove_from_event_list(
-                                    &self.xTasksWaitingToReceive,
-                                ) != false
-                                {
-                                    pxHigherPriorityTaskWoken = true;
-                                } else {
-                                    mtCOVERAGE_TEST_MARKER!();
-                                }
+                        } else if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
+                            if task_queue::task_remove_from_event_list(
+                                &self.xTasksWaitingToReceive,
+                            ) != false
+                            {
+                                pxHigherPriorityTaskWoken = true;
                             } else {
                                 mtCOVERAGE_TEST_MARKER!();
                             }
+                        } else {
+                            mtCOVERAGE_TEST_MARKER!();
                         }
                     }
 
@@ -389,7 +531,7 @@ where
                 xReturn = Ok(());
             } else {
                 traceQUEUE_SEND_FROM_ISR_FAILED!(&self);
-                xReturn = Err(QueueError::QueueFull);
+                xReturn = Err(FreeRtosError::QueueFull);
             }
         }
         portCLEAR_INTERRUPT_MASK_FROM_ISR!(uxSavedInterruptStatus);
@@ -437,27 +579,27 @@ where
         {
             let mut cTxLock: i8 = self.cTxLock;
             while cTxLock > queueLOCKED_UNMODIFIED {
-                #[cfg(feature = "configUSE_QUEUE_SETS")]
-                match self.pxQueueSetContainer {
-                    Some => {
-                        if notify_queue_set_container(self, queueSEND_TO_BACK) != false {
+                {
+                    #![cfg(feature = "configUSE_QUEUE_SETS")]
+                    if self.is_set_event_on_send() {
+                        if self.notify_queue_set_container(queueSEND_TO_BACK) != false {
+                            /* The queue is a member of a queue set, and posting to
+                            the queue set caused a higher priority task to unblock.
+                            A context switch is required. */
                             task_queue::task_missed_yield();
                         } else {
                             mtCOVERAGE_TEST_MARKER!();
                         }
-                    }
-                    None => {
-                        if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
-                            if task_queue::task_remove_from_event_list(&self.xTasksWaitingToReceive)
-                                != false
-                            {
-                                task_queue::task_missed_yield();
-                            } else {
-                                mtCOVERAGE_TEST_MARKER!();
-                            }
+                    } else if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
+                        if task_queue::task_remove_from_event_list(&self.xTasksWaitingToReceive)
+                            != false
+                        {
+                            task_queue::task_missed_yield();
                         } else {
-                            break;
+                            mtCOVERAGE_TEST_MARKER!();
                         }
+                    } else {
+                        break;
                     }
                 }
                 {
@@ -518,12 +660,12 @@ where
     /// * `xJustPeeking` - whether the item will remain in the queue.
     ///
     /// # Return
-    /// Ok(T) if an item was successfully received from the queue, otherwise QueueError::QueueEmpty.
+    /// Ok(T) if an item was successfully received from the queue, otherwise FreeRtosError::QueueEmpty.
     pub fn queue_generic_receive(
         &mut self,
         mut xTicksToWait: TickType,
         xJustPeeking: bool,
-    ) -> Result<T, QueueError> {
+    ) -> Result<T, FreeRtosError> {
         let mut xEntryTimeSet: bool = false;
         let mut xTimeOut: time_out = Default::default();
         /*when receive = give, it has to call the function task_priority_disinherit. It may require
@@ -576,6 +718,19 @@ where
                                 mtCOVERAGE_TEST_MARKER!();
                             }
                         }
+                        {
+                            #![cfg(feature = "configUSE_QUEUE_SETS")]
+                            /* Receiving from the queue of a semaphore gives the
+                            semaphore, which is the event a semaphore in a queue
+                            set reports. */
+                            if self.is_set_event_on_receive() {
+                                if self.notify_queue_set_container(queueSEND_TO_BACK) {
+                                    queueYIELD_IF_USING_PREEMPTION!();
+                                } else {
+                                    mtCOVERAGE_TEST_MARKER!();
+                                }
+                            }
+                        }
                         trace!("queue_generic_receive -- line 498");
                         if list::list_is_empty(&self.xTasksWaitingToSend) == false {
                             if task_queue::task_remove_from_event_list(&self.xTasksWaitingToSend)
@@ -624,7 +779,7 @@ where
                         the block time has expired) so leave now. */
                         taskEXIT_CRITICAL!();
                         traceQUEUE_RECEIVE_FAILED!(&self);
-                        return Err(QueueError::QueueEmpty);
+                        return Err(FreeRtosError::QueueEmpty);
                     } else if xEntryTimeSet == false {
                         /* The queue was empty and a block time was specified so
                         configure the timeout structure. */
@@ -650,22 +805,25 @@ where
                         xTicksToWait,
                     );
                     self.unlock_queue();
-                    if kernel::task_resume_all() == false {
+                    if kernel::task_resume_all().unwrap() == false {
                         portYIELD_WITHIN_API!();
                     } else {
                         mtCOVERAGE_TEST_MARKER!();
                     }
                 } else {
                     self.unlock_queue();
-                    kernel::task_resume_all();
+                    kernel::task_resume_all().unwrap();
                 }
                 trace!("queue_generic_receive -- line 589");
             } else {
                 self.unlock_queue();
-                kernel::task_resume_all();
+                kernel::task_resume_all().unwrap();
                 if self.is_queue_empty() != false {
                     traceQUEUE_RECEIVE_FAILED!(&self);
-                    return Err(QueueError::QueueEmpty);
+                    if xTimeOut.delay_aborted() {
+                        return Err(FreeRtosError::DelayAborted);
+                    }
+                    return Err(FreeRtosError::QueueEmpty);
                 } else {
                     mtCOVERAGE_TEST_MARKER!();
                 }
@@ -694,7 +852,7 @@ where
             if self.ucQueueType == QueueType::Mutex || self.ucQueueType == QueueType::RecursiveMutex
             {
                 let mutex_holder = transed_task_handle_to_T(task_increment_mutex_held_count());
-                self.pcQueue.insert(0, mutex_holder);
+                self.pcQueue.push_front(mutex_holder);
             } else {
                 mtCOVERAGE_TEST_MARKER!();
             }
@@ -702,7 +860,7 @@ where
 
         if xPosition == queueSEND_TO_BACK {
             if self.ucQueueType != QueueType::Mutex && self.ucQueueType != QueueType::RecursiveMutex {
-                self.pcQueue.insert(self.pcWriteTo as usize, pvItemToQueue);
+                self.write_item(self.pcWriteTo, pvItemToQueue);
             }
             else {
             }
@@ -715,15 +873,15 @@ where
             }
         } else {
             if self.ucQueueType != QueueType::Mutex && self.ucQueueType != QueueType::RecursiveMutex {
-                self.pcQueue.insert(self.QueueUnion as usize, pvItemToQueue); //QueueUnion represents pcReadFrom
+                self.write_item(self.QueueUnion, pvItemToQueue); //QueueUnion represents pcReadFrom
             }
             else {
             }
-            self.QueueUnion = self.QueueUnion - 1;
-            if self.QueueUnion < self.pcHead {
+            /* pcHead is 0, so check before decrementing instead of after. */
+            if self.QueueUnion <= self.pcHead {
                 self.QueueUnion = self.pcTail - 1;
             } else {
-                mtCOVERAGE_TEST_MARKER!();
+                self.QueueUnion = self.QueueUnion - 1;
             }
 
             if xPosition == queueOVERWRITE {
@@ -743,6 +901,10 @@ where
         self.uxMessagesWaiting = uxMessagesWaiting + 1;
     }
 
+    fn write_item(&mut self, uxIndex: UBaseType, pvItemToQueue: T) {
+        self.pcQueue.write(uxIndex as usize, pvItemToQueue);
+    }
+
     /// # Description
     /// To know whether the queue is empty.
     ///
@@ -827,9 +989,172 @@ where
         self.uxQueueNumber
     }
 
+    /// # Description
+    /// Block the calling task on the queue until a message arrives or the
+    /// timeout expires, without actually receiving anything. Only used by the
+    /// timer daemon task, which must call it with the scheduler suspended.
+    ///
+    /// * C implementation: queue.c 2358-2380
+    ///
+    /// # Argument
+    /// * `xTicksToWait` - The maximum amount of time the task should block.
+    /// * `xWaitIndefinitely` - whether the task should block without a timeout.
+    ///
+    /// # Return
+    /// Nothing
+    #[cfg(feature = "configUSE_TIMERS")]
+    pub fn queue_wait_for_message_restricted(
+        &mut self,
+        xTicksToWait: TickType,
+        xWaitIndefinitely: bool,
+    ) {
+        /* This function should not be called by application code hence the
+        'Restricted' in its name.  It is not part of the public API. */
+        self.lock_queue();
+        if self.uxMessagesWaiting == 0 as UBaseType {
+            /* There is nothing in the queue, block for the specified period. */
+            task_queue::task_place_on_event_list_restricted(
+                &self.xTasksWaitingToReceive,
+                xTicksToWait,
+                xWaitIndefinitely,
+            );
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+        self.unlock_queue();
+    }
+
+    /// # Description
+    /// Add the queue or semaphore to a queue set.  A queue can only be added
+    /// to a set while it is empty, and a semaphore only while it is not
+    /// available, otherwise the set would miss the events that are already
+    /// pending.
+    ///
+    /// * C implementation: queue.c 2408-2434
+    ///
+    /// # Argument
+    /// * `xQueueSet` - the queue set the queue is added to.
+    /// * `xMemberHandle` - the handle the queue posts to the set.
+    ///
+    /// # Return
+    /// `Result<(), FreeRtosError>` - Ok if the queue was added to the set,
+    /// AlreadyInSet or PendingSetEvents if it could not be.
+    #[cfg(feature = "configUSE_QUEUE_SETS")]
+    pub fn add_to_set(
+        &mut self,
+        xQueueSet: QueueSet,
+        xMemberHandle: QueueSetMemberHandle,
+    ) -> Result<(), FreeRtosError> {
+        let xReturn: Result<(), FreeRtosError>;
+
+        /* Mutexes cannot be added to a queue set. */
+        assert!(self.ucQueueType != QueueType::Mutex && self.ucQueueType != QueueType::RecursiveMutex);
+
+        taskENTER_CRITICAL!();
+        {
+            if self.pxQueueSetContainer.is_some() {
+                /* Cannot add a queue/semaphore to more than one queue set. */
+                xReturn = Err(FreeRtosError::AlreadyInSet);
+            } else if self.has_pending_set_events() {
+                /* Cannot add a queue/semaphore to a queue set if there are already
+                items in the queue/semaphore. */
+                xReturn = Err(FreeRtosError::PendingSetEvents);
+            } else {
+                self.pxQueueSetContainer = Some((xQueueSet, xMemberHandle));
+                xReturn = Ok(());
+            }
+        }
+        taskEXIT_CRITICAL!();
+
+        xReturn
+    }
+
+    /// # Description
+    /// Remove the queue or semaphore from a queue set.  Like add_to_set(),
+    /// this only succeeds while the queue is empty or the semaphore is not
+    /// available.
+    ///
+    /// * C implementation: queue.c 2441-2470
+    ///
+    /// # Argument
+    /// * `xQueueSet` - the queue set the queue is removed from.
+    ///
+    /// # Return
+    /// `Result<(), FreeRtosError>` - Ok if the queue was removed from the
+    /// set, NotInSet or PendingSetEvents if it could not be.
+    #[cfg(feature = "configUSE_QUEUE_SETS")]
+    pub fn remove_from_set(&mut self, xQueueSet: &QueueSet) -> Result<(), FreeRtosError> {
+        let xReturn: Result<(), FreeRtosError>;
+
+        taskENTER_CRITICAL!();
+        {
+            let xIsMember = match self.pxQueueSetContainer {
+                Some((ref xContainer, _)) => xContainer == xQueueSet,
+                None => false,
+            };
+
+            if !xIsMember {
+                /* The queue was not a member of the set. */
+                xReturn = Err(FreeRtosError::NotInSet);
+            } else if self.has_pending_set_events() {
+                /* It is dangerous to remove a queue from a set when the queue is
+                not empty because the queue set will still hold pending events for
+                the queue. */
+                xReturn = Err(FreeRtosError::PendingSetEvents);
+            } else {
+                /* The queue is no longer contained in the set. */
+                self.pxQueueSetContainer = None;
+                xReturn = Ok(());
+            }
+        }
+        taskEXIT_CRITICAL!();
+
+        xReturn
+    }
+
+    /* Events of a queue are items sent to it.  Semaphores are inverted in this
+    implementation (taking one sends to its queue, giving one receives from
+    its queue), so events of a semaphore are items received from it. */
     #[cfg(feature = "configUSE_QUEUE_SETS")]
-    fn notify_queue_set_container(&self, xCopyPosition: BaseType) {
-        unimplemented!();
+    fn is_set_event_on_send(&self) -> bool {
+        self.pxQueueSetContainer.is_some() && self.ucQueueType == QueueType::Base
+    }
+
+    #[cfg(feature = "configUSE_QUEUE_SETS")]
+    fn is_set_event_on_receive(&self) -> bool {
+        self.pxQueueSetContainer.is_some()
+            && (self.ucQueueType == QueueType::BinarySemaphore
+                || self.ucQueueType == QueueType::CountingSemaphore)
+    }
+
+    #[cfg(feature = "configUSE_QUEUE_SETS")]
+    fn has_pending_set_events(&self) -> bool {
+        if self.ucQueueType == QueueType::Base {
+            self.uxMessagesWaiting != 0 as UBaseType
+        } else {
+            self.uxMessagesWaiting != self.uxLength
+        }
+    }
+
+    /// # Description
+    /// Post the handle of this queue to the queue set it belongs to.
+    ///
+    /// * C implementation: queue.c 2503-2552
+    ///
+    /// # Argument
+    /// * `xCopyPosition` - the position that the handle is to be placed.
+    ///
+    /// # Return
+    /// `bool` - true if posting to the queue set caused a task with a higher
+    /// priority than the calling task to unblock.
+    #[cfg(feature = "configUSE_QUEUE_SETS")]
+    fn notify_queue_set_container(&mut self, xCopyPosition: BaseType) -> bool {
+        let (pxQueueSetContainer, xMemberHandle) = self
+            .pxQueueSetContainer
+            .clone()
+            .unwrap_or_else(|| panic!("The queue is not a member of a queue set"));
+
+        pxQueueSetContainer.post_member(xMemberHandle, xCopyPosition)
     }
 
     /// # Description
@@ -866,6 +1191,66 @@ where
     }
 }
 
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+impl QueueDefinition<QueueSetMemberHandle> {
+    /// # Description
+    /// Post the handle of a member to this queue set, and unblock the task
+    /// waiting in select() if the queue set is not locked.
+    ///
+    /// * C implementation: queue.c 2503-2552
+    ///
+    /// # Argument
+    /// * `xMemberHandle` - the handle of the member that has an event.
+    /// * `xCopyPosition` - the position that the handle is to be placed.
+    ///
+    /// # Return
+    /// `bool` - true if posting to the queue set caused a task with a higher
+    /// priority than the calling task to unblock.
+    pub fn queue_set_post(
+        &mut self,
+        xMemberHandle: QueueSetMemberHandle,
+        xCopyPosition: BaseType,
+    ) -> bool {
+        let mut xReturn: bool = false;
+
+        /* This function must be called form a critical section. */
+
+        /* The queue set is created with enough space for an event from every
+        queue and semaphore it contains. */
+        assert!(self.uxMessagesWaiting < self.uxLength);
+
+        if self.uxMessagesWaiting < self.uxLength {
+            let cTxLock: i8 = self.cTxLock;
+
+            traceQUEUE_SEND!(&self);
+
+            /* The data copied is the handle of the queue that contains data. */
+            self.copy_data_to_queue(xMemberHandle, xCopyPosition);
+
+            if cTxLock == queueUNLOCKED {
+                if list::list_is_empty(&self.xTasksWaitingToReceive) == false {
+                    if task_queue::task_remove_from_event_list(&self.xTasksWaitingToReceive)
+                        != false
+                    {
+                        /* The task waiting has a higher priority. */
+                        xReturn = true;
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            } else {
+                self.cTxLock = cTxLock + 1;
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        xReturn
+    }
+}
+
 /// # Description
 /// Transform Option<task_control::TaskHandle> to T
 ///
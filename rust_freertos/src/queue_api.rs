@@ -4,8 +4,13 @@
 
 use crate::port::*;
 use crate::queue::*;
+use crate::projdefs::FreeRtosError;
 use crate::queue_h::*;
 use std::cell::UnsafeCell;
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+use std::sync::Arc;
 
 /// * Description:
 ///
@@ -47,6 +52,31 @@ where
         )))
     }
 
+    /// # Description:
+    /// Create a new queue whose items are stored in a buffer provided by the
+    /// application, so that no memory is allocated for them.  The lists of
+    /// the tasks waiting on the queue are still allocated on the heap.
+    ///
+    /// * C implementation: xQueueCreateStatic() in queue.h, which also takes
+    ///   the memory of the queue itself.
+    ///
+    /// # Arguments:
+    /// * `buffer` - The storage of the queue, the queue holds at most
+    ///   `buffer.len()` items.
+    ///
+    /// # Return:
+    /// The created queue.
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    pub fn new_with_storage(buffer: &'static mut [T]) -> Self
+    where
+        T: 'static,
+    {
+        Queue(UnsafeCell::new(QueueDefinition::queue_generic_create_static(
+            buffer,
+            QueueType::Base,
+        )))
+    }
+
     /// # Description
     /// Post an item to the front of a queue.
     /// 
@@ -60,7 +90,7 @@ where
     ///
     /// # Return
     /// Ok() if the item was successfully posted, otherwise errQUEUE_FULL.
-    pub fn send(&self, pvItemToQueue: T, xTicksToWait: TickType) -> Result<(), QueueError> {
+    pub fn send(&self, pvItemToQueue: T, xTicksToWait: TickType) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send(pvItemToQueue, xTicksToWait, queueSEND_TO_BACK)
@@ -84,7 +114,7 @@ where
         &self,
         pvItemToQueue: T,
         xTicksToWait: TickType,
-    ) -> Result<(), QueueError> {
+    ) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send(pvItemToQueue, xTicksToWait, queueSEND_TO_FRONT)
@@ -104,7 +134,7 @@ where
     /// 
     /// # Return
     /// Ok() if the item was successfully posted, otherwise errQUEUE_FULL.
-    pub fn send_to_back(&self, pvItemToQueue: T, xTicksToWait: TickType) -> Result<(), QueueError> {
+    pub fn send_to_back(&self, pvItemToQueue: T, xTicksToWait: TickType) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send(pvItemToQueue, xTicksToWait, queueSEND_TO_BACK)
@@ -124,7 +154,7 @@ where
     /// # Return
     /// Ok() is the only value that can be returned because queue_overwrite will write to the
     /// queue even when the queue is already full.
-    pub fn overwrite(&self, pvItemToQueue: T) -> Result<(), QueueError> {
+    pub fn overwrite(&self, pvItemToQueue: T) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send(pvItemToQueue, 0, queueOVERWRITE)
@@ -144,7 +174,7 @@ where
     /// * `Result` -Ok() if the data was successfully sent to the queue, otherwise errQUEUE_FULL.
     /// * `bool` - pxHigherPriorityTaskWoken is changed to be a return value. it is true if sending to the
     /// queue caused a task to unblock,otherwise it is false.
-    pub fn send_to_front_from_isr(&self, pvItemToQueue: T) -> (Result<(), QueueError>, bool) {
+    pub fn send_to_front_from_isr(&self, pvItemToQueue: T) -> (Result<(), FreeRtosError>, bool) {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send_from_isr(pvItemToQueue, queueSEND_TO_FRONT)
@@ -163,7 +193,7 @@ where
     /// * `Result` -Ok() if the data was successfully sent to the queue, otherwise errQUEUE_FULL.
     /// * `bool` - pxHigherPriorityTaskWoken is changed to be a return value. it is true if sending to the
     /// queue caused a task to unblock,otherwise it is false.
-    pub fn send_to_back_from_isr(&self, pvItemToQueue: T) -> (Result<(), QueueError>, bool) {
+    pub fn send_to_back_from_isr(&self, pvItemToQueue: T) -> (Result<(), FreeRtosError>, bool) {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send_from_isr(pvItemToQueue, queueSEND_TO_BACK)
@@ -182,7 +212,7 @@ where
     /// * `Result` -Ok().
     /// * `bool` - pxHigherPriorityTaskWoken is changed to be a return value. it is true if sending to the
     /// queue caused a task to unblock,otherwise it is false.
-    pub fn overwrite_from_isr(&self, pvItemToQueue: T) -> (Result<(), QueueError>, bool) {
+    pub fn overwrite_from_isr(&self, pvItemToQueue: T) -> (Result<(), FreeRtosError>, bool) {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send_from_isr(pvItemToQueue, queueOVERWRITE)
@@ -203,8 +233,9 @@ where
     /// of the call.It will return immediately if xTicksToWait is zero and the queue is empty.
     /// 
     /// # Return
-    /// Ok(T) if an item was successfully received from the queue, otherwise QueueError::QueueEmpty.
-    pub fn receive(&self, xTicksToWait: TickType) -> Result<T, QueueError> {
+    /// Ok(T) if an item was successfully received from the queue, otherwise FreeRtosError::QueueEmpty,
+    /// or FreeRtosError::DelayAborted if the wait was cut short by task_abort_delay().
+    pub fn receive(&self, xTicksToWait: TickType) -> Result<T, FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_receive(xTicksToWait, false)
@@ -225,11 +256,197 @@ where
     /// 
     /// # Return
     /// Ok(T) if an item was successfully received from the queue, otherwise
-    /// QueueError::QueueEmpty.
-    pub fn peek(&self, xTicksToWait: TickType) -> Result<T, QueueError> {
+    /// FreeRtosError::QueueEmpty.
+    pub fn peek(&self, xTicksToWait: TickType) -> Result<T, FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_receive(xTicksToWait, true)
         }
     }
+
+    /// # Description
+    /// Block on the queue until a message arrives or the timeout expires,
+    /// leaving the message in the queue. For use by the timer daemon task only.
+    ///
+    /// * C implementation: queue.c 2358
+    ///
+    /// # Argument
+    /// * `xTicksToWait` - The maximum amount of time the task should block.
+    /// * `xWaitIndefinitely` - whether the task should block without a timeout.
+    ///
+    /// # Return
+    /// Nothing
+    #[cfg(feature = "configUSE_TIMERS")]
+    pub(crate) fn wait_for_message_restricted(
+        &self,
+        xTicksToWait: TickType,
+        xWaitIndefinitely: bool,
+    ) {
+        unsafe {
+            let inner = self.0.get();
+            (*inner).queue_wait_for_message_restricted(xTicksToWait, xWaitIndefinitely)
+        }
+    }
+}
+
+/* Every time a queue or semaphore is added to a queue set it gets a new
+member handle, so a handle left over from an earlier membership can never be
+mistaken for the current one. */
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+static NEXT_MEMBER_HANDLE: AtomicUsize = AtomicUsize::new(1);
+
+/// * Description:
+///
+/// Identifies a queue or semaphore inside a queue set. It is returned by
+/// QueueSet::add() and by QueueSet::select() when the member has an event.
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QueueSetMemberHandle(usize);
+
+/// * Description:
+///
+/// A queue set allows a task to block on several queues and semaphores at
+/// once. A member posts its handle to the set every time an item is sent to
+/// it (queues) or every time it is given (semaphores), so select() returns
+/// the member that can now be read or taken without blocking.
+///
+/// The handle returned by select() must be followed by exactly one receive
+/// from (or take of) that member, otherwise the set and its members get out
+/// of step.
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+#[derive(Clone)]
+pub struct QueueSet(Arc<Queue<QueueSetMemberHandle>>);
+
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+impl PartialEq for QueueSet {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// * Description:
+///
+/// Implemented by the primitives that can be added to a queue set, i.e.
+/// Queue<T> and the binary and counting Semaphore.
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+pub trait QueueSetMember {
+    /// Record that `set` contains this member under `member`. Fails with
+    /// AlreadyInSet if the member is already in a set, or PendingSetEvents if
+    /// it has pending events.
+    fn add_to_set(&self, set: &QueueSet, member: QueueSetMemberHandle)
+        -> Result<(), FreeRtosError>;
+
+    /// Forget about `set`. Fails with NotInSet if this member is not in
+    /// `set`, or PendingSetEvents if it has pending events.
+    fn remove_from_set(&self, set: &QueueSet) -> Result<(), FreeRtosError>;
+}
+
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+impl QueueSet {
+    /// # Description:
+    /// Create a new queue set.
+    ///
+    /// * C implementation: queue.c 2394-2401
+    ///
+    /// # Arguments:
+    /// * `event_queue_length` - The maximum number of events that can be
+    /// queued in the set. It must be at least the sum of the lengths of all
+    /// the queues, plus the maximum counts of all the semaphores, in the set.
+    ///
+    /// # Return:
+    /// The created queue set.
+    pub fn new(event_queue_length: UBaseType) -> Self {
+        QueueSet(Arc::new(Queue(UnsafeCell::new(QueueDefinition::new(
+            event_queue_length,
+            QueueType::Set,
+        )))))
+    }
+
+    /// # Description:
+    /// Add a queue or semaphore to the set. A queue can only be added while
+    /// it is empty, and a semaphore only while it cannot be taken.
+    ///
+    /// * C implementation: queue.c 2408-2434
+    ///
+    /// # Arguments:
+    /// * `member` - the queue or semaphore to add.
+    ///
+    /// # Return:
+    /// The handle select() will return for this member, or AlreadyInSet or
+    /// PendingSetEvents if it could not be added.
+    pub fn add<M: QueueSetMember>(
+        &self,
+        member: &M,
+    ) -> Result<QueueSetMemberHandle, FreeRtosError> {
+        let handle = QueueSetMemberHandle(NEXT_MEMBER_HANDLE.fetch_add(1, Ordering::SeqCst));
+        member.add_to_set(self, handle)?;
+        Ok(handle)
+    }
+
+    /// # Description:
+    /// Remove a queue or semaphore from the set. Like add(), this only
+    /// succeeds while the queue is empty or the semaphore cannot be taken.
+    ///
+    /// * C implementation: queue.c 2441-2470
+    ///
+    /// # Arguments:
+    /// * `member` - the queue or semaphore to remove.
+    ///
+    /// # Return:
+    /// Ok if the member was removed, NotInSet or PendingSetEvents if not.
+    pub fn remove<M: QueueSetMember>(&self, member: &M) -> Result<(), FreeRtosError> {
+        member.remove_from_set(self)
+    }
+
+    /// # Description:
+    /// Block on the set until one of its members has an event.
+    ///
+    /// * C implementation: queue.c 2477-2483
+    ///
+    /// # Arguments:
+    /// * `ticks_to_wait` - The maximum amount of time the task should block
+    /// waiting for one of the members to have an event.
+    ///
+    /// # Return:
+    /// The handle of the member that can be read or taken, QueueEmpty if the
+    /// block time expired, or DelayAborted if the wait was cut short by
+    /// task_abort_delay().
+    pub fn select(&self, ticks_to_wait: TickType) -> Result<QueueSetMemberHandle, FreeRtosError> {
+        self.0.receive(ticks_to_wait)
+    }
+
+    pub(crate) fn post_member(
+        &self,
+        member: QueueSetMemberHandle,
+        xCopyPosition: BaseType,
+    ) -> bool {
+        unsafe {
+            let inner = (self.0).0.get();
+            (*inner).queue_set_post(member, xCopyPosition)
+        }
+    }
+}
+
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+impl<T> QueueSetMember for Queue<T>
+where
+    T: Default + Clone,
+{
+    fn add_to_set(
+        &self,
+        set: &QueueSet,
+        member: QueueSetMemberHandle,
+    ) -> Result<(), FreeRtosError> {
+        unsafe {
+            let inner = self.0.get();
+            (*inner).add_to_set(set.clone(), member)
+        }
+    }
+
+    fn remove_from_set(&self, set: &QueueSet) -> Result<(), FreeRtosError> {
+        unsafe {
+            let inner = self.0.get();
+            (*inner).remove_from_set(set)
+        }
+    }
 }
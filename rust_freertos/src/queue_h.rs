@@ -1,6 +1,9 @@
 use crate::port::*;
 use std::fmt;
 
+/// The error the queue functions returned before they returned
+/// `FreeRtosError`.  Kept for code written against them, it converts to and
+/// from `FreeRtosError`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum QueueError {
     QueueSendTimeout,
@@ -8,6 +11,7 @@ pub enum QueueError {
     MutexTimeout,
     QueueFull,
     QueueEmpty,
+    DelayAborted,
 }
 
 impl fmt::Display for QueueError {
@@ -18,10 +22,13 @@ impl fmt::Display for QueueError {
             QueueError::MutexTimeout => write!(f, "MutexSendTimeOut"),
             QueueError::QueueFull => write!(f, "QueueFull"),
             QueueError::QueueEmpty => write!(f, "QueueEmpty"),
+            QueueError::DelayAborted => write!(f, "DelayAborted"),
         }
     }
 }
 
+impl std::error::Error for QueueError {}
+
 pub const queueSEND_TO_BACK: BaseType = 0;
 pub const queueSEND_TO_FRONT: BaseType = 1;
 pub const queueOVERWRITE: BaseType = 2;
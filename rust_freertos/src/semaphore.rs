@@ -1,5 +1,8 @@
 use crate::port::*;
 use crate::queue::*;
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+use crate::queue_api::{QueueSet, QueueSetMember, QueueSetMemberHandle};
+use crate::projdefs::FreeRtosError;
 use crate::queue_h::*;
 use crate::task_control::*;
 use crate::*;
@@ -9,6 +12,19 @@ pub struct Semaphore(UnsafeCell<QueueDefinition<Option<TaskHandle>>>);
 unsafe impl Send for Semaphore {}
 unsafe impl Sync for Semaphore {}
 
+/// The storage of one count of a semaphore created by
+/// Semaphore::new_with_storage().  A mutex keeps its holder in it.
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+#[repr(transparent)]
+#[derive(Default)]
+pub struct SemaphoreSlot(Option<TaskHandle>);
+
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+impl SemaphoreSlot {
+    /// An empty slot, which can initialise a `static` array of slots.
+    pub const EMPTY: SemaphoreSlot = SemaphoreSlot(None);
+}
+
 impl Semaphore {
     /// # Descrpition
     /// Create a new mutex type semaphore instance.
@@ -62,8 +78,8 @@ impl Semaphore {
     /// Nothing
     /// 
     /// # Return:
-    /// Ok(T) if the semaphore was released, otherwise QueueError::QueueEmpty.
-    pub fn semaphore_up(&self) -> Result<Option<TaskHandle>, QueueError> {
+    /// Ok(T) if the semaphore was released, otherwise FreeRtosError::QueueEmpty.
+    pub fn semaphore_up(&self) -> Result<Option<TaskHandle>, FreeRtosError> {
         unsafe {
             trace!("Semaphore up runs!");
             let inner = self.0.get();
@@ -84,8 +100,9 @@ impl Semaphore {
     /// A block time of portMAX_DELAY can be used to block indefinitely.
     ///
     /// # Return:
-    /// Ok() if the semaphore was obtained, otherwise errQUEUE_FULL.
-    pub fn semaphore_down(&self, xBlockTime: TickType) -> Result<(), QueueError> {
+    /// Ok() if the semaphore was obtained, otherwise errQUEUE_FULL, or
+    /// FreeRtosError::DelayAborted if the wait was cut short by task_abort_delay().
+    pub fn semaphore_down(&self, xBlockTime: TickType) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             (*inner).queue_generic_send(None, xBlockTime, queueSEND_TO_BACK)
@@ -135,6 +152,53 @@ impl Semaphore {
         counting_semphr
     }
 
+    /// # Description
+    /// Create a semaphore or mutex of the given type whose counts are kept in
+    /// slots provided by the application.  Like the semaphores created by
+    /// create_binary() and create_counting(), a binary or counting semaphore
+    /// starts empty.  The lists of the tasks waiting for the semaphore are
+    /// still allocated on the heap.
+    ///
+    /// * C implementation: xSemaphoreCreateBinaryStatic() and friends in
+    ///   semphr.h, which also take the memory of the semaphore itself.
+    ///
+    /// # Arguments:
+    /// `semaphore_type` - QueueType::BinarySemaphore, CountingSemaphore,
+    /// Mutex or RecursiveMutex.
+    /// `slots` - One slot for each count the semaphore can hold, that is one
+    /// for a binary semaphore or a mutex and the maximum count for a counting
+    /// semaphore.
+    ///
+    /// # Return
+    /// The created semaphore.
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    pub fn new_with_storage(
+        semaphore_type: QueueType,
+        slots: &'static mut [SemaphoreSlot],
+    ) -> Self {
+        assert!(
+            semaphore_type != QueueType::Base && semaphore_type != QueueType::Set,
+            "A semaphore must be a semaphore or a mutex type."
+        );
+        assert!(
+            semaphore_type == QueueType::CountingSemaphore || slots.len() == 1,
+            "Only a counting semaphore can hold more than one count."
+        );
+        /* SemaphoreSlot is repr(transparent) over Option<TaskHandle>. */
+        let buffer = unsafe {
+            let slots_ptr = slots.as_mut_ptr() as *mut Option<TaskHandle>;
+            std::slice::from_raw_parts_mut(slots_ptr, slots.len())
+        };
+        let semaphore = Semaphore(UnsafeCell::new(
+            QueueDefinition::queue_generic_create_static(buffer, semaphore_type),
+        ));
+        unsafe {
+            let inner = semaphore.0.get();
+            (*inner).initialise_count(0);
+        }
+        semaphore
+    }
+
     /// # Description
     /// Created a recursive mutex.
     ///
@@ -163,24 +227,23 @@ impl Semaphore {
     /// Nothing
     /// 
     /// # Return
-    /// `bool` - true if the recursive mutex was released.
-    pub fn up_recursive(&self) -> bool {
+    /// Ok(()) if the recursive mutex was released, otherwise
+    /// FreeRtosError::NotMutexHolder if the calling task does not hold it.
+    pub fn up_recursive(&self) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
-            if (*inner).transed_task_handle_for_mutex().unwrap().clone()
-                == get_current_task_handle!()
-            {
+            if (*inner).transed_task_handle_for_mutex() == Some(get_current_task_handle!()) {
                 traceGIVE_MUTEX_RECURSIVE!(*inner);
                 (*inner).QueueUnion_decrease();
                 if (*inner).is_QueueUnion_zero() {
-                    (*inner).queue_generic_receive(semGIVE_BLOCK_TIME, false);
+                    (*inner).queue_generic_receive(semGIVE_BLOCK_TIME, false)?;
                 } else {
                     mtCOVERAGE_TEST_MARKER!();
                 }
-                return true;
+                Ok(())
             } else {
                 traceGIVE_MUTEX_RECURSIVE_FAILED!(*inner);
-                return false;
+                Err(FreeRtosError::NotMutexHolder)
             }
         }
     }
@@ -196,38 +259,30 @@ impl Semaphore {
     /// A block time of zero can be used to poll the semaphore.
     ///
     /// # Return:
-    /// `bool` - true if the recursive mutex was obtained.
-    pub fn down_recursive(&self, ticks_to_wait: TickType) -> bool {
-        let mut xReturn: bool = false;
+    /// Ok(()) if the recursive mutex was obtained, otherwise the error of
+    /// semaphore_down().
+    pub fn down_recursive(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
         unsafe {
             let inner = self.0.get();
             traceTAKE_MUTEX_RECURSIVE!(*inner);
-            trace!("Ready to get recursive mutex holder");
-            let mutex_holder = (*inner).transed_task_handle_for_mutex();
-            trace!("Get recursive mutex holder successfully");
-            if mutex_holder.is_some()
-            {
-                if mutex_holder.unwrap().clone() == get_current_task_handle!() {
-                    trace!("Not First Time get this mutex");
+            if (*inner).transed_task_handle_for_mutex() == Some(get_current_task_handle!()) {
+                trace!("Not First Time get this mutex");
+                (*inner).QueueUnion_increase();
+                return Ok(());
+            }
+
+            trace!("First Time get this mutex");
+            match (*inner).queue_generic_send(None, ticks_to_wait, queueSEND_TO_BACK) {
+                Ok(()) => {
                     (*inner).QueueUnion_increase();
-                    xReturn = false;
+                    Ok(())
                 }
-            } 
-            // else {
-                trace!("First Time get this mutex");
-                match (*inner).queue_generic_send(None, ticks_to_wait, queueSEND_TO_BACK) {
-                    Ok(x) => {
-                        (*inner).QueueUnion_increase();
-                        xReturn = true;
-                    }
-                    Err(x) => {
-                        traceTAKE_MUTEX_RECURSIVE_FAILED!(*inner);
-                        xReturn = false;
-                    }
+                Err(error) => {
+                    traceTAKE_MUTEX_RECURSIVE_FAILED!(*inner);
+                    Err(error)
                 }
-            // }
+            }
         }
-        return xReturn;
     }
 
     /// # Description
@@ -247,3 +302,24 @@ impl Semaphore {
         }
     }
 }
+
+#[cfg(feature = "configUSE_QUEUE_SETS")]
+impl QueueSetMember for Semaphore {
+    fn add_to_set(
+        &self,
+        set: &QueueSet,
+        member: QueueSetMemberHandle,
+    ) -> Result<(), FreeRtosError> {
+        unsafe {
+            let inner = self.0.get();
+            (*inner).add_to_set(set.clone(), member)
+        }
+    }
+
+    fn remove_from_set(&self, set: &QueueSet) -> Result<(), FreeRtosError> {
+        unsafe {
+            let inner = self.0.get();
+            (*inner).remove_from_set(set)
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// sim.rs - Virtual time for the POSIX port, for reproducible tests.
+// There is no C counterpart to this file.
+//
+// With the portVIRTUAL_TIME feature the kernel runs on the Sim port below,
+// which is the port in posix.rs without its tick thread: ticks only happen
+// when the thread driving the simulation calls advance_ticks().  Between ticks
+// the tasks run until the idle task is reached, that is until every other task
+// is blocked, and only then does the driver get the processor back.  As only
+// one thread runs at a time, every run of a test behaves the same.
+//
+// With virtual time task_start_scheduler() returns as soon as the tasks have
+// run for the first time, and the caller becomes the driver:
+//
+//     kernel::task_start_scheduler();
+//     sim::advance_ticks(10);
+//     // Inspect the state of the tasks after 10 ticks.
+//
+// A task that never blocks keeps the processor, and time stands still.
+use crate::port::{BaseType, CVoidPointer, Port, StackType, TaskFunction_t, TickType};
+use crate::posix::{self, Owner};
+use crate::task_control::TaskHandle;
+use crate::*;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+static START_TICK: AtomicUsize = AtomicUsize::new(0);
+
+/// Start the scheduler with the tick count at `tick` rather than 0, so that
+/// a test can reach the point where the tick count overflows.
+///
+/// Must be called before task_start_scheduler(), and before any timer is
+/// started, as the tick count moves to `tick` at once.
+///
+/// # Arguments
+///
+/// `tick` - The tick count the scheduler starts at.
+///
+/// # Return
+///
+/// Nothing
+pub fn start_at_tick(tick: TickType) {
+    START_TICK.store(tick as usize, Ordering::Relaxed);
+    set_tick_count!(tick);
+}
+
+/// The tick count the scheduler starts at, see start_at_tick().
+pub fn start_tick() -> TickType {
+    START_TICK.load(Ordering::Relaxed) as TickType
+}
+
+/// Advance the virtual clock by `ticks` ticks.  After each tick the tasks
+/// run until they are all blocked again, so when this function returns the
+/// system is in the state it reaches after exactly `ticks` ticks.
+///
+/// Must be called by the thread that started the scheduler, never by a task.
+/// Does nothing once the scheduler has ended.
+///
+/// # Arguments
+///
+/// `ticks` - The number of ticks to advance the clock by.
+///
+/// # Return
+///
+/// Nothing
+pub fn advance_ticks(ticks: TickType) {
+    assert!(
+        posix::me() == Owner::Driver,
+        "Only the driver can advance the clock."
+    );
+
+    for _ in 0..ticks {
+        if posix::scheduler_ended() {
+            break;
+        }
+
+        TICKS.fetch_add(1, Ordering::Relaxed);
+        posix::raise_tick();
+        posix::run_tasks();
+    }
+}
+
+/// The number of ticks the virtual clock has been advanced by.
+pub fn ticks() -> usize {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// The virtual time port.
+pub struct Sim;
+
+impl Port for Sim {
+    fn yield_task() {
+        posix::yield_task()
+    }
+
+    fn yield_from_isr() {
+        posix::yield_from_isr()
+    }
+
+    fn set_interrupt_mask() -> BaseType {
+        posix::set_interrupt_mask()
+    }
+
+    fn clear_interrupt_mask(mask: BaseType) {
+        posix::clear_interrupt_mask(mask)
+    }
+
+    fn disable_interrupts() {
+        posix::disable_interrupts()
+    }
+
+    fn enable_interrupts() {
+        posix::enable_interrupts()
+    }
+
+    fn enter_critical() {
+        posix::enter_critical()
+    }
+
+    fn exit_critical() {
+        posix::exit_critical()
+    }
+
+    fn initialise_stack(
+        top_of_stack: *mut StackType,
+        code: TaskFunction_t,
+        parameters: *mut c_void,
+    ) -> *mut StackType {
+        posix::initialise_stack(top_of_stack, code, parameters)
+    }
+
+    fn set_next_task_stack(stack_base: CVoidPointer, stack_size: usize) {
+        posix::set_next_task_stack(stack_base, stack_size)
+    }
+
+    fn add_task_handle(task: TaskHandle) {
+        posix::add_task_handle(task)
+    }
+
+    fn clean_up_tcb(task: &TaskHandle) {
+        posix::clean_up_tcb(task)
+    }
+
+    fn free_task_stack(task: &TaskHandle, stack_base: CVoidPointer, free_stack: fn(CVoidPointer)) {
+        posix::free_task_stack(task, stack_base, free_stack)
+    }
+
+    /* Returns as soon as the tasks are idle, the caller becomes the driver. */
+    fn start_scheduler() -> BaseType {
+        posix::start_first_task()
+    }
+
+    fn end_scheduler() {
+        posix::end_scheduler()
+    }
+
+    /* Time only moves on when the driver says so. */
+    fn wait_for_interrupt() {
+        posix::join_exited_threads();
+        if !posix::interrupt_pending() {
+            posix::hand_over(Owner::Driver);
+        }
+        posix::service_interrupts();
+    }
+
+    fn configure_timer_for_run_time_stats() {}
+
+    /* Run time statistics are counted in ticks. */
+    fn get_run_time_counter_value() -> u64 {
+        ticks() as u64
+    }
+}
@@ -0,0 +1,185 @@
+// stack_guard.rs - Task stacks protected by a guard page, for the POSIX port.
+// There is no C counterpart to this file: on real hardware the MPU or the
+// stack overflow checks in StackMacros.h play this role.
+//
+// Each stack is a single mapping laid out as
+//     [guard page][task stack][signal stack]
+// The guard page is made inaccessible, so a task that runs off the end of its
+// stack faults straight away instead of corrupting its neighbours.  The fault
+// is handled on the signal stack, which is why it lives in the same mapping.
+// A signal handler may only make async-signal-safe calls, so the handler
+// writes a message prepared when the task was created and aborts.
+use crate::port::{self, CVoidPointer};
+use crate::projdefs::FreeRtosError;
+use crate::sync::Mutex;
+use crate::task_control::TaskHandle;
+use std::os::raw::{c_int, c_void};
+use std::sync::Once;
+use std::{mem, ptr};
+
+/* Size of the stack the SIGSEGV handler runs on.  The handler does little,
+but the dynamic linker may have to resolve write() and abort() on their first
+call, so it is given more room than SIGSTKSZ. */
+const SIGNAL_STACK_SIZE: usize = 64 * 1024;
+
+struct GuardedStack {
+    /* The guard page is [guard_start, base), the task stack starts at
+    base. */
+    guard_start: usize,
+    base: usize,
+    mapping_size: usize,
+    /* Written to stderr by the SIGSEGV handler, which cannot format it. */
+    message: Vec<u8>,
+}
+
+lazy_static! {
+    static ref GUARDED_STACKS: Mutex<Vec<GuardedStack>> = Mutex::new(Vec::new());
+}
+
+static INSTALL_HANDLER: Once = Once::new();
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Map a task stack of at least `size` bytes with a guard page below it.
+/// The size is rounded up to whole pages, and to the smallest stack host code
+/// can run on.
+///
+/// # Return
+///
+/// The base and the size in bytes of the usable part of the stack, or
+/// `FreeRtosError::OutOfMemory` if the stack could not be mapped.
+pub fn allocate_stack(size: usize) -> Result<(CVoidPointer, usize), FreeRtosError> {
+    INSTALL_HANDLER.call_once(install_handler);
+
+    let page_size = page_size();
+    let stack_size = size
+        .max(port::portMINIMAL_STACK_BYTES)
+        .max(libc::PTHREAD_STACK_MIN);
+    let stack_size = (stack_size + page_size - 1) / page_size * page_size;
+    let mapping_size = page_size + stack_size + SIGNAL_STACK_SIZE;
+
+    let mapping = unsafe {
+        let mapping = libc::mmap(
+            ptr::null_mut(),
+            mapping_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if mapping == libc::MAP_FAILED {
+            error!("Could not map a stack of {} bytes.", mapping_size);
+            return Err(FreeRtosError::OutOfMemory);
+        }
+
+        if libc::mprotect(mapping, page_size, libc::PROT_NONE) != 0 {
+            error!("Could not protect the guard page of a stack.");
+            libc::munmap(mapping, mapping_size);
+            return Err(FreeRtosError::OutOfMemory);
+        }
+        mapping as usize
+    };
+
+    GUARDED_STACKS.lock().unwrap().push(GuardedStack {
+        guard_start: mapping,
+        base: mapping + page_size,
+        mapping_size,
+        message: b"A task hit the guard page of its stack.\n".to_vec(),
+    });
+    Ok(((mapping + page_size) as CVoidPointer, stack_size))
+}
+
+/// Remember which task the guard page below `stack_base` belongs to, so a
+/// fault on it can be reported against that task.
+pub fn register_stack(stack_base: CVoidPointer, task: &TaskHandle) {
+    let message = format!(
+        "Task {} hit the guard page of its stack.\n",
+        task.get_name()
+    );
+    let mut guarded_stacks = GUARDED_STACKS.lock().unwrap();
+    if let Some(stack) = guarded_stacks
+        .iter_mut()
+        .find(|stack| stack.base == stack_base as usize)
+    {
+        stack.message = message.into_bytes();
+    }
+}
+
+/// Unmap a stack mapped by allocate_stack(), with its guard page and signal
+/// stack.  Nothing may run on the stack any more.
+pub fn free_stack(stack_base: CVoidPointer) {
+    let mut guarded_stacks = GUARDED_STACKS.lock().unwrap();
+    let index = guarded_stacks
+        .iter()
+        .position(|stack| stack.base == stack_base as usize)
+        .expect("The stack was not mapped by allocate_stack().");
+    let stack = guarded_stacks.swap_remove(index);
+    drop(guarded_stacks);
+
+    unsafe {
+        if libc::munmap(stack.guard_start as *mut c_void, stack.mapping_size) != 0 {
+            error!("Could not unmap the stack at {:X}.", stack.base);
+        }
+    }
+}
+
+/// Make the calling thread handle signals on the signal stack that sits
+/// above its task stack.  Must be called by each task before it runs any
+/// task code.
+pub fn install_signal_stack(stack_top: usize) {
+    let signal_stack = libc::stack_t {
+        ss_sp: stack_top as *mut c_void,
+        ss_flags: 0,
+        ss_size: SIGNAL_STACK_SIZE,
+    };
+    unsafe {
+        if libc::sigaltstack(&signal_stack, ptr::null_mut()) != 0 {
+            error!("Could not install the signal stack of a task.");
+        }
+    }
+}
+
+fn install_handler() {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle_sigsegv as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGSEGV, &action, ptr::null_mut()) != 0 {
+            error!("Could not install the SIGSEGV handler.");
+        }
+    }
+}
+
+/* Only async-signal-safe calls may be made here: no logging, no allocation
+and no stack overflow hook. */
+extern "C" fn handle_sigsegv(_signal: c_int, info: *mut libc::siginfo_t, _context: *mut c_void) {
+    let fault_address = unsafe { (*info).si_addr() as usize };
+
+    /* The lock may be held by the faulting thread itself, so never wait for
+    it.  try_lock() does not block. */
+    if let Ok(guarded_stacks) = GUARDED_STACKS.try_lock() {
+        let stack = guarded_stacks
+            .iter()
+            .find(|stack| stack.guard_start <= fault_address && fault_address < stack.base);
+        if let Some(stack) = stack {
+            unsafe {
+                libc::write(
+                    libc::STDERR_FILENO,
+                    stack.message.as_ptr() as *const c_void,
+                    stack.message.len(),
+                );
+                /* The task cannot continue, returning would fault again. */
+                libc::abort();
+            }
+        }
+    }
+
+    /* Not a stack overflow.  Fall back to the default action, which is taken
+    when the faulting instruction runs again. */
+    unsafe {
+        libc::signal(libc::SIGSEGV, libc::SIG_DFL);
+    }
+}
@@ -0,0 +1,912 @@
+// stream_buffer.rs - Stream buffers and message buffers.
+// Functions defined in this file are adapted from stream_buffer.c (FreeRTOS
+// V10), message buffers being stream buffers that store a length before each
+// message.
+//
+// Stream buffers are optimised for the single writer / single reader case: the
+// writer only ever moves `head` and the reader only ever moves `tail`, so the
+// bytes themselves are copied without entering a critical section.  A task
+// blocked on a stream buffer is unblocked with a direct to task notification,
+// which is why this file requires configUSE_TASK_NOTIFICATIONS.
+use crate::kernel;
+use crate::port::*;
+use crate::projdefs::FreeRtosError;
+use crate::task_control::TaskHandle;
+use crate::task_notify;
+use crate::task_notify::NotifyAction;
+use crate::task_queue::*;
+use crate::*;
+use std::cell::UnsafeCell;
+use std::cmp;
+use std::mem;
+
+/* The number of bytes used to hold the length of a message in the buffer. */
+const sbBYTES_TO_STORE_MESSAGE_LENGTH: usize = mem::size_of::<usize>();
+
+struct StreamBufferDefinition {
+    /* Index to the next item to read within the buffer. */
+    tail: usize,
+    /* Index to the next item to write within the buffer. */
+    head: usize,
+    /* The length of the buffer.  One byte more than the capacity requested
+    by the user, as head == tail means the buffer is empty. */
+    length: usize,
+    /* The number of bytes that must be in the stream buffer before a task
+    that is waiting for data is unblocked. */
+    trigger_level_bytes: usize,
+    /* Holds the handle of a task waiting for data, or None. */
+    task_waiting_to_receive: Option<TaskHandle>,
+    /* Holds the handle of a task waiting to send data to a message buffer
+    that is full, or None. */
+    task_waiting_to_send: Option<TaskHandle>,
+    /* The memory in which the data is stored. */
+    buffer: Vec<u8>,
+    is_message_buffer: bool,
+}
+
+impl StreamBufferDefinition {
+    /* C implementation: xStreamBufferGenericCreate() and
+    prvInitialiseNewStreamBuffer() in stream_buffer.c */
+    fn new(buffer_size_bytes: usize, trigger_level_bytes: usize, is_message_buffer: bool) -> Self {
+        if is_message_buffer {
+            /* Is there enough space to store at least the message length? */
+            assert!(buffer_size_bytes > sbBYTES_TO_STORE_MESSAGE_LENGTH);
+        }
+        assert!(trigger_level_bytes <= buffer_size_bytes);
+
+        /* A trigger level of 0 would cause a waiting task to unblock even
+        when the buffer was empty. */
+        let trigger_level_bytes = cmp::max(trigger_level_bytes, 1);
+
+        /* A stream buffer that can hold buffer_size_bytes needs one extra byte
+        of storage, otherwise a full buffer could not be told from an empty
+        one. */
+        let length = buffer_size_bytes + 1;
+
+        StreamBufferDefinition {
+            tail: 0,
+            head: 0,
+            length,
+            trigger_level_bytes,
+            task_waiting_to_receive: None,
+            task_waiting_to_send: None,
+            buffer: vec![0; length],
+            is_message_buffer,
+        }
+    }
+
+    /* C implementation: xStreamBufferReset() in stream_buffer.c */
+    fn reset(&mut self) -> Result<(), FreeRtosError> {
+        let mut ret_val = Err(FreeRtosError::TasksBlocked);
+
+        /* Can only reset a message buffer if there are no tasks blocked on it. */
+        taskENTER_CRITICAL!();
+        {
+            if self.task_waiting_to_receive.is_none() && self.task_waiting_to_send.is_none() {
+                self.head = 0;
+                self.tail = 0;
+                ret_val = Ok(());
+                traceSTREAM_BUFFER_RESET!(self);
+            }
+        }
+        taskEXIT_CRITICAL!();
+
+        ret_val
+    }
+
+    /* C implementation: xStreamBufferSetTriggerLevel() in stream_buffer.c */
+    fn set_trigger_level(&mut self, trigger_level: usize) -> Result<(), FreeRtosError> {
+        /* It is not valid for the trigger level to be 0. */
+        let trigger_level = cmp::max(trigger_level, 1);
+
+        /* The trigger level is the number of bytes that must be in the stream
+        buffer before a task that is waiting for data is unblocked. */
+        if trigger_level < self.length {
+            self.trigger_level_bytes = trigger_level;
+            Ok(())
+        } else {
+            Err(FreeRtosError::InvalidTriggerLevel)
+        }
+    }
+
+    /* C implementation: xStreamBufferSpacesAvailable() in stream_buffer.c */
+    fn spaces_available(&self) -> usize {
+        let mut space = self.length + self.tail;
+        space -= self.head;
+        space -= 1;
+        if space >= self.length {
+            space -= self.length;
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+        space
+    }
+
+    /* C implementation: prvBytesInBuffer() in stream_buffer.c */
+    fn bytes_in_buffer(&self) -> usize {
+        /* Returns the distance between tail and head. */
+        let mut count = self.length + self.head;
+        count -= self.tail;
+        if count >= self.length {
+            count -= self.length;
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+        count
+    }
+
+    fn bytes_to_store_message_length(&self) -> usize {
+        if self.is_message_buffer {
+            sbBYTES_TO_STORE_MESSAGE_LENGTH
+        } else {
+            0
+        }
+    }
+
+    /* C implementation: xStreamBufferSend() in stream_buffer.c */
+    fn send(&mut self, data: &[u8], ticks_to_wait: TickType) -> usize {
+        let mut ticks_to_wait = ticks_to_wait;
+        let mut space = 0;
+        let mut time_out = time_out::default();
+
+        /* This send function is used to write to both message buffers and
+        stream buffers.  If this is a message buffer then the space needed must
+        be increased by the amount of bytes needed to store the length of the
+        message. */
+        let required_space = data.len() + self.bytes_to_store_message_length();
+
+        if ticks_to_wait != 0 {
+            task_set_time_out_state(&mut time_out);
+
+            loop {
+                /* Wait until the required number of bytes are free in the
+                message buffer. */
+                taskENTER_CRITICAL!();
+                {
+                    space = self.spaces_available();
+
+                    if space < required_space {
+                        /* Clear notification state as going to wait for
+                        space. */
+                        let current_task = get_current_task_handle!();
+                        current_task.notify_state_clear();
+
+                        /* Should only be one writer. */
+                        assert!(self.task_waiting_to_send.is_none());
+                        self.task_waiting_to_send = Some(current_task);
+                    } else {
+                        taskEXIT_CRITICAL!();
+                        break;
+                    }
+                }
+                taskEXIT_CRITICAL!();
+
+                traceBLOCKING_ON_STREAM_BUFFER_SEND!(self);
+                let _ = task_notify::task_notify_wait(0, 0, ticks_to_wait);
+                self.task_waiting_to_send = None;
+
+                if task_check_for_timeout(&mut time_out, &mut ticks_to_wait) {
+                    break;
+                }
+            }
+        }
+
+        if space == 0 {
+            space = self.spaces_available();
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        let ret_val = self.write_message_to_buffer(data, space, required_space);
+
+        if ret_val > 0 {
+            traceSTREAM_BUFFER_SEND!(self, ret_val);
+
+            /* Was a task waiting for the data? */
+            if self.bytes_in_buffer() >= self.trigger_level_bytes {
+                self.send_completed();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+            traceSTREAM_BUFFER_SEND_FAILED!(self);
+        }
+
+        ret_val
+    }
+
+    /* C implementation: xStreamBufferSendFromISR() in stream_buffer.c */
+    fn send_from_isr(&mut self, data: &[u8]) -> (usize, bool) {
+        let mut higher_priority_task_woken = false;
+        let required_space = data.len() + self.bytes_to_store_message_length();
+
+        let space = self.spaces_available();
+        let ret_val = self.write_message_to_buffer(data, space, required_space);
+
+        if ret_val > 0 {
+            /* Was a task waiting for the data? */
+            if self.bytes_in_buffer() >= self.trigger_level_bytes {
+                higher_priority_task_woken = self.send_completed_from_isr();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        traceSTREAM_BUFFER_SEND_FROM_ISR!(self, ret_val);
+
+        (ret_val, higher_priority_task_woken)
+    }
+
+    /* C implementation: prvWriteMessageToBuffer() in stream_buffer.c */
+    fn write_message_to_buffer(
+        &mut self,
+        data: &[u8],
+        space: usize,
+        required_space: usize,
+    ) -> usize {
+        let mut data_length_bytes = data.len();
+
+        let should_write = if space == 0 {
+            /* Doesn't matter if this is a stream buffer or a message buffer,
+            there is no space to write. */
+            false
+        } else if !self.is_message_buffer {
+            /* This is a stream buffer, as opposed to a message buffer, so
+            writing a stream of bytes rather than discrete messages.  Write as
+            many bytes as possible. */
+            data_length_bytes = cmp::min(data_length_bytes, space);
+            true
+        } else if space >= required_space {
+            /* This is a message buffer, as opposed to a stream buffer, and
+            there is enough space to write both the message length and the
+            message itself into the buffer.  Start by writing the length of
+            the data, the data itself will be written later in this
+            function. */
+            self.write_bytes_to_buffer(&data_length_bytes.to_ne_bytes());
+            true
+        } else {
+            /* There is space available, but not enough space. */
+            false
+        };
+
+        if should_write {
+            /* Writes the data itself. */
+            self.write_bytes_to_buffer(&data[..data_length_bytes])
+        } else {
+            0
+        }
+    }
+
+    /* C implementation: prvWriteBytesToBuffer() in stream_buffer.c */
+    fn write_bytes_to_buffer(&mut self, data: &[u8]) -> usize {
+        let count = data.len();
+
+        let mut next_head = self.head;
+
+        /* Calculate the number of bytes that can be added in the first write -
+        which may be less than the total number of bytes that need to be added
+        if the buffer will wrap back to the beginning. */
+        let first_length = cmp::min(self.length - next_head, count);
+
+        /* Write as many bytes as can be written in the first write. */
+        assert!(next_head + first_length <= self.length);
+        self.buffer[next_head..next_head + first_length].copy_from_slice(&data[..first_length]);
+
+        /* If the number of bytes written was less than the number that could be
+        written in the first write... */
+        if count > first_length {
+            /* ...then write the remaining bytes to the start of the buffer. */
+            self.buffer[..count - first_length].copy_from_slice(&data[first_length..]);
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        next_head += count;
+        if next_head >= self.length {
+            next_head -= self.length;
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        self.head = next_head;
+
+        count
+    }
+
+    /* C implementation: xStreamBufferReceive() in stream_buffer.c */
+    fn receive(&mut self, rx_data: &mut [u8], ticks_to_wait: TickType) -> usize {
+        let mut received_length = 0;
+        let mut bytes_available;
+
+        /* This receive function is used by both message buffers, which store
+        discrete messages, and stream buffers, which store a continuous stream of
+        bytes.  Discrete messages include an additional
+        sbBYTES_TO_STORE_MESSAGE_LENGTH bytes that hold the length of the
+        message. */
+        let bytes_to_store_message_length = self.bytes_to_store_message_length();
+
+        if ticks_to_wait != 0 {
+            /* Checking if there is data and clearing the notification state must
+            be performed atomically. */
+            taskENTER_CRITICAL!();
+            {
+                bytes_available = self.bytes_in_buffer();
+
+                /* If this function was invoked by a message buffer read then
+                bytes_to_store_message_length holds the number of bytes used to
+                hold the length of the next discrete message.  If this function
+                was invoked by a stream buffer read then
+                bytes_to_store_message_length will be 0. */
+                if bytes_available <= bytes_to_store_message_length {
+                    /* Clear notification state as going to wait for data. */
+                    let current_task = get_current_task_handle!();
+                    current_task.notify_state_clear();
+
+                    /* Should only be one reader. */
+                    assert!(self.task_waiting_to_receive.is_none());
+                    self.task_waiting_to_receive = Some(current_task);
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            }
+            taskEXIT_CRITICAL!();
+
+            if bytes_available <= bytes_to_store_message_length {
+                /* Wait for data to be available. */
+                traceBLOCKING_ON_STREAM_BUFFER_RECEIVE!(self);
+                let _ = task_notify::task_notify_wait(0, 0, ticks_to_wait);
+                self.task_waiting_to_receive = None;
+
+                /* Recheck the data available after blocking. */
+                bytes_available = self.bytes_in_buffer();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            bytes_available = self.bytes_in_buffer();
+        }
+
+        /* Whether receiving a discrete message (where bytes_to_store_message_length
+        holds the number of bytes used to store the message length) or a stream of
+        bytes (where bytes_to_store_message_length is zero), the number of bytes
+        available must be greater than bytes_to_store_message_length to be able to
+        read bytes from the buffer. */
+        if bytes_available > bytes_to_store_message_length {
+            received_length = self.read_message_from_buffer(
+                rx_data,
+                bytes_available,
+                bytes_to_store_message_length,
+            );
+
+            /* Was a task waiting for space in the buffer? */
+            if received_length != 0 {
+                traceSTREAM_BUFFER_RECEIVE!(self, received_length);
+                self.receive_completed();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            traceSTREAM_BUFFER_RECEIVE_FAILED!(self);
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        received_length
+    }
+
+    /* C implementation: xStreamBufferReceiveFromISR() in stream_buffer.c */
+    fn receive_from_isr(&mut self, rx_data: &mut [u8]) -> (usize, bool) {
+        let mut received_length = 0;
+        let mut higher_priority_task_woken = false;
+        let bytes_to_store_message_length = self.bytes_to_store_message_length();
+
+        let bytes_available = self.bytes_in_buffer();
+
+        /* Whether receiving a discrete message or a stream of bytes, the number
+        of bytes available must be greater than bytes_to_store_message_length
+        to be able to read bytes from the buffer. */
+        if bytes_available > bytes_to_store_message_length {
+            received_length = self.read_message_from_buffer(
+                rx_data,
+                bytes_available,
+                bytes_to_store_message_length,
+            );
+
+            /* Was a task waiting for space in the buffer? */
+            if received_length != 0 {
+                higher_priority_task_woken = self.receive_completed_from_isr();
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        traceSTREAM_BUFFER_RECEIVE_FROM_ISR!(self, received_length);
+
+        (received_length, higher_priority_task_woken)
+    }
+
+    /* C implementation: prvReadMessageFromBuffer() in stream_buffer.c */
+    fn read_message_from_buffer(
+        &mut self,
+        rx_data: &mut [u8],
+        bytes_available: usize,
+        bytes_to_store_message_length: usize,
+    ) -> usize {
+        let mut bytes_available = bytes_available;
+        let mut next_message_length;
+
+        if bytes_to_store_message_length != 0 {
+            /* A discrete message is being received.  First receive the length
+            of the message.  A copy of the tail is stored so the buffer can be
+            returned to its prior state if the length of the message is too
+            large for the provided buffer. */
+            let original_tail = self.tail;
+            let mut message_length = [0u8; sbBYTES_TO_STORE_MESSAGE_LENGTH];
+            self.read_bytes_from_buffer(&mut message_length, bytes_available);
+            next_message_length = usize::from_ne_bytes(message_length);
+
+            /* Reduce the number of bytes available by the number of bytes just
+            read out. */
+            bytes_available -= bytes_to_store_message_length;
+
+            /* Check there is enough space in the buffer provided by the
+            user. */
+            if next_message_length > rx_data.len() {
+                /* The user has provided insufficient space to read the message
+                so return the buffer to its previous state (so the length of
+                the message is in the buffer again). */
+                self.tail = original_tail;
+                next_message_length = 0;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            /* A stream of bytes is being received (as opposed to a discrete
+            message), so read as many bytes as possible. */
+            next_message_length = rx_data.len();
+        }
+
+        /* Read the actual data. */
+        self.read_bytes_from_buffer(&mut rx_data[..next_message_length], bytes_available)
+    }
+
+    /* C implementation: prvReadBytesFromBuffer() in stream_buffer.c */
+    fn read_bytes_from_buffer(&mut self, rx_data: &mut [u8], bytes_available: usize) -> usize {
+        /* Use the minimum of the wanted bytes and the available bytes. */
+        let count = cmp::min(bytes_available, rx_data.len());
+
+        if count > 0 {
+            let mut next_tail = self.tail;
+
+            /* Calculate the number of bytes that can be read - which may be
+            less than the number wanted if the data wraps around to the start of
+            the buffer. */
+            let first_length = cmp::min(self.length - next_tail, count);
+
+            /* Obtain the number of bytes it is possible to obtain in the first
+            read. */
+            assert!(first_length <= rx_data.len());
+            assert!(next_tail + first_length <= self.length);
+            rx_data[..first_length]
+                .copy_from_slice(&self.buffer[next_tail..next_tail + first_length]);
+
+            /* If the total number of wanted bytes is greater than the number
+            that could be read in the first read... */
+            if count > first_length {
+                /* ...then read the remaining bytes from the start of the
+                buffer. */
+                rx_data[first_length..count].copy_from_slice(&self.buffer[..count - first_length]);
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+
+            /* Move the tail pointer to effectively remove the data read from
+            the buffer. */
+            next_tail += count;
+
+            if next_tail >= self.length {
+                next_tail -= self.length;
+            }
+
+            self.tail = next_tail;
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        count
+    }
+
+    /* C implementation: xStreamBufferNextMessageLengthBytes() in stream_buffer.c */
+    fn next_message_length_bytes(&self) -> usize {
+        assert!(self.is_message_buffer);
+
+        /* Ensure the stream buffer is being used as a message buffer. */
+        if self.bytes_in_buffer() > sbBYTES_TO_STORE_MESSAGE_LENGTH {
+            /* The number of bytes available is greater than the number of bytes
+            required to hold the length of the next message, so another message
+            is available.  Peek the length without moving the tail. */
+            let mut message_length = [0u8; sbBYTES_TO_STORE_MESSAGE_LENGTH];
+            for (i, byte) in message_length.iter_mut().enumerate() {
+                *byte = self.buffer[(self.tail + i) % self.length];
+            }
+            usize::from_ne_bytes(message_length)
+        } else {
+            /* The minimum amount of bytes in a message buffer is
+            (sbBYTES_TO_STORE_MESSAGE_LENGTH + 1), so if bytes_in_buffer is
+            less than that, the buffer is empty. */
+            0
+        }
+    }
+
+    /* If a task is waiting for data, unblock it.  This is the sbSEND_COMPLETED
+    macro of stream_buffer.c. */
+    fn send_completed(&mut self) {
+        kernel::task_suspend_all();
+        {
+            if let Some(task) = self.task_waiting_to_receive.take() {
+                task.notify(0, NotifyAction::NoAction).unwrap();
+            }
+        }
+        kernel::task_resume_all().unwrap();
+    }
+
+    /* sbSEND_COMPLETE_FROM_ISR macro of stream_buffer.c. */
+    fn send_completed_from_isr(&mut self) -> bool {
+        let mut higher_priority_task_woken = false;
+        let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+        {
+            if let Some(task) = self.task_waiting_to_receive.take() {
+                let (_, woken) = task.notify_from_isr(0, NotifyAction::NoAction);
+                higher_priority_task_woken = woken;
+            }
+        }
+        portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+        higher_priority_task_woken
+    }
+
+    /* If a task is waiting for space, unblock it.  This is the
+    sbRECEIVE_COMPLETED macro of stream_buffer.c. */
+    fn receive_completed(&mut self) {
+        kernel::task_suspend_all();
+        {
+            if let Some(task) = self.task_waiting_to_send.take() {
+                task.notify(0, NotifyAction::NoAction).unwrap();
+            }
+        }
+        kernel::task_resume_all().unwrap();
+    }
+
+    /* sbRECEIVE_COMPLETED_FROM_ISR macro of stream_buffer.c. */
+    fn receive_completed_from_isr(&mut self) -> bool {
+        let mut higher_priority_task_woken = false;
+        let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+        {
+            if let Some(task) = self.task_waiting_to_send.take() {
+                let (_, woken) = task.notify_from_isr(0, NotifyAction::NoAction);
+                higher_priority_task_woken = woken;
+            }
+        }
+        portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+        higher_priority_task_woken
+    }
+}
+
+/// A stream buffer passes a continuous stream of bytes from a single writer
+/// (a task or an interrupt) to a single reader.  A reader blocked on an empty
+/// stream buffer is unblocked once at least the trigger level number of bytes
+/// are in the buffer.
+pub struct StreamBuffer(UnsafeCell<StreamBufferDefinition>);
+unsafe impl Send for StreamBuffer {}
+unsafe impl Sync for StreamBuffer {}
+
+impl StreamBuffer {
+    /// # Description
+    /// Create a new stream buffer.
+    ///
+    /// * C implementation: xStreamBufferCreate() in stream_buffer.h
+    ///
+    /// # Arguments:
+    /// `buffer_size_bytes` - The total number of bytes the stream buffer will
+    /// be able to hold at any one time.
+    ///
+    /// `trigger_level_bytes` - The number of bytes that must be in the stream
+    /// buffer before a task that is blocked waiting for data is unblocked.  A
+    /// value of 0 is treated as 1.
+    ///
+    /// # Return:
+    /// The created stream buffer.
+    pub fn new(buffer_size_bytes: usize, trigger_level_bytes: usize) -> Self {
+        let stream_buffer = StreamBuffer(UnsafeCell::new(StreamBufferDefinition::new(
+            buffer_size_bytes,
+            trigger_level_bytes,
+            false,
+        )));
+        traceSTREAM_BUFFER_CREATE!(&stream_buffer, false);
+        stream_buffer
+    }
+
+    /// # Description
+    /// Send bytes to the stream buffer, blocking for up to `ticks_to_wait`
+    /// ticks for space to become available.
+    ///
+    /// * C implementation: xStreamBufferSend() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `data` - The bytes to copy into the stream buffer.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time the task should remain in
+    /// the Blocked state to wait for enough space to become available.
+    ///
+    /// # Return:
+    /// The number of bytes written to the stream buffer.  If the call timed
+    /// out before all the bytes could be written, as many bytes as possible
+    /// are still written.
+    pub fn send(&self, data: &[u8], ticks_to_wait: TickType) -> usize {
+        unsafe { (*self.0.get()).send(data, ticks_to_wait) }
+    }
+
+    /// # Description
+    /// A version of send() that can be called from an ISR.
+    ///
+    /// * C implementation: xStreamBufferSendFromISR() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `data` - The bytes to copy into the stream buffer.
+    ///
+    /// # Return:
+    /// The number of bytes written to the stream buffer, and whether a task
+    /// with a priority higher than the running task was unblocked, in which
+    /// case a context switch should be requested before the interrupt exits.
+    pub fn send_from_isr(&self, data: &[u8]) -> (usize, bool) {
+        unsafe { (*self.0.get()).send_from_isr(data) }
+    }
+
+    /// # Description
+    /// Receive bytes from the stream buffer, blocking for up to
+    /// `ticks_to_wait` ticks if the buffer is empty.
+    ///
+    /// * C implementation: xStreamBufferReceive() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `rx_data` - The buffer into which the received bytes are copied.  At
+    /// most `rx_data.len()` bytes are received.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time the task should remain in
+    /// the Blocked state to wait for data to become available.
+    ///
+    /// # Return:
+    /// The number of bytes read from the stream buffer, 0 if the call timed
+    /// out.
+    pub fn receive(&self, rx_data: &mut [u8], ticks_to_wait: TickType) -> usize {
+        unsafe { (*self.0.get()).receive(rx_data, ticks_to_wait) }
+    }
+
+    /// # Description
+    /// A version of receive() that can be called from an ISR.
+    ///
+    /// * C implementation: xStreamBufferReceiveFromISR() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `rx_data` - The buffer into which the received bytes are copied.
+    ///
+    /// # Return:
+    /// The number of bytes read from the stream buffer, and whether a task
+    /// with a priority higher than the running task was unblocked, in which
+    /// case a context switch should be requested before the interrupt exits.
+    pub fn receive_from_isr(&self, rx_data: &mut [u8]) -> (usize, bool) {
+        unsafe { (*self.0.get()).receive_from_isr(rx_data) }
+    }
+
+    /// # Description
+    /// Reset the stream buffer to its initial, empty, state.  A stream buffer
+    /// can only be reset if no tasks are blocked on it.
+    ///
+    /// * C implementation: xStreamBufferReset() in stream_buffer.c
+    ///
+    /// # Return:
+    /// Ok if the stream buffer was reset, `FreeRtosError::TasksBlocked` if a
+    /// task is blocked on it.
+    pub fn reset(&self) -> Result<(), FreeRtosError> {
+        unsafe { (*self.0.get()).reset() }
+    }
+
+    /// # Description
+    /// Set the number of bytes that must be in the stream buffer before a task
+    /// blocked waiting for data is unblocked.
+    ///
+    /// * C implementation: xStreamBufferSetTriggerLevel() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `trigger_level` - The new trigger level.  A value of 0 is treated as 1.
+    ///
+    /// # Return:
+    /// `FreeRtosError::InvalidTriggerLevel` if the trigger level is larger
+    /// than the buffer size (and was not set), otherwise Ok.
+    pub fn set_trigger_level(&self, trigger_level: usize) -> Result<(), FreeRtosError> {
+        unsafe { (*self.0.get()).set_trigger_level(trigger_level) }
+    }
+
+    /// # Description
+    /// Query the number of bytes that can be written to the stream buffer
+    /// before it is full.
+    ///
+    /// * C implementation: xStreamBufferSpacesAvailable() in stream_buffer.c
+    pub fn spaces_available(&self) -> usize {
+        unsafe { (*self.0.get()).spaces_available() }
+    }
+
+    /// # Description
+    /// Query the number of bytes that can be read from the stream buffer
+    /// before it is empty.
+    ///
+    /// * C implementation: xStreamBufferBytesAvailable() in stream_buffer.c
+    pub fn bytes_available(&self) -> usize {
+        unsafe { (*self.0.get()).bytes_in_buffer() }
+    }
+
+    /// # Description
+    /// Query whether the stream buffer is empty.
+    ///
+    /// * C implementation: xStreamBufferIsEmpty() in stream_buffer.c
+    pub fn is_empty(&self) -> bool {
+        /* True if no bytes are in the stream buffer. */
+        let inner = self.0.get();
+        unsafe { (*inner).head == (*inner).tail }
+    }
+
+    /// # Description
+    /// Query whether the stream buffer is full.  A message buffer is full when
+    /// there is no room for another message length.
+    ///
+    /// * C implementation: xStreamBufferIsFull() in stream_buffer.c
+    pub fn is_full(&self) -> bool {
+        let inner = self.0.get();
+
+        /* This generic version of the receive function is used by both message
+        buffers, which store discrete messages, and stream buffers, which store a
+        continuous stream of bytes.  Discrete messages include an additional
+        sbBYTES_TO_STORE_MESSAGE_LENGTH bytes that hold the length of the
+        message. */
+        unsafe { (*inner).spaces_available() <= (*inner).bytes_to_store_message_length() }
+    }
+}
+
+/// A message buffer passes discrete, variable length messages from a single
+/// writer (a task or an interrupt) to a single reader.  Each message is stored
+/// after its length, so a message is always received as a whole.
+pub struct MessageBuffer(StreamBuffer);
+
+impl MessageBuffer {
+    /// # Description
+    /// Create a new message buffer.  Each message needs
+    /// `mem::size_of::<usize>()` bytes of space in addition to its data, to
+    /// store its length.
+    ///
+    /// * C implementation: xMessageBufferCreate() in message_buffer.h
+    ///
+    /// # Arguments:
+    /// `buffer_size_bytes` - The total number of bytes (not messages) the
+    /// message buffer will be able to hold at any one time.
+    ///
+    /// # Return:
+    /// The created message buffer.
+    pub fn new(buffer_size_bytes: usize) -> Self {
+        let stream_buffer = StreamBuffer(UnsafeCell::new(StreamBufferDefinition::new(
+            buffer_size_bytes,
+            1,
+            true,
+        )));
+        traceSTREAM_BUFFER_CREATE!(&stream_buffer, true);
+        MessageBuffer(stream_buffer)
+    }
+
+    /// # Description
+    /// Send a discrete message to the message buffer, blocking for up to
+    /// `ticks_to_wait` ticks for enough space for the whole message.
+    ///
+    /// * C implementation: xStreamBufferSend() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `data` - The message to copy into the message buffer.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time the task should remain in
+    /// the Blocked state to wait for enough space to become available.
+    ///
+    /// # Return:
+    /// The number of bytes written, which is either `data.len()` or 0 if the
+    /// call timed out before the message could be written.
+    pub fn send(&self, data: &[u8], ticks_to_wait: TickType) -> usize {
+        self.0.send(data, ticks_to_wait)
+    }
+
+    /// # Description
+    /// A version of send() that can be called from an ISR.
+    ///
+    /// * C implementation: xStreamBufferSendFromISR() in stream_buffer.c
+    ///
+    /// # Return:
+    /// The number of bytes written (`data.len()` or 0), and whether a context
+    /// switch should be requested before the interrupt exits.
+    pub fn send_from_isr(&self, data: &[u8]) -> (usize, bool) {
+        self.0.send_from_isr(data)
+    }
+
+    /// # Description
+    /// Receive a discrete message from the message buffer, blocking for up to
+    /// `ticks_to_wait` ticks if the buffer is empty.
+    ///
+    /// * C implementation: xStreamBufferReceive() in stream_buffer.c
+    ///
+    /// # Arguments:
+    /// `rx_data` - The buffer into which the received message is copied.  If
+    /// the next message is longer than `rx_data.len()` it is left in the
+    /// message buffer.
+    ///
+    /// `ticks_to_wait` - The maximum amount of time the task should remain in
+    /// the Blocked state to wait for a message.
+    ///
+    /// # Return:
+    /// The length of the received message, 0 if the call timed out or the
+    /// message did not fit into `rx_data`.
+    pub fn receive(&self, rx_data: &mut [u8], ticks_to_wait: TickType) -> usize {
+        self.0.receive(rx_data, ticks_to_wait)
+    }
+
+    /// # Description
+    /// A version of receive() that can be called from an ISR.
+    ///
+    /// * C implementation: xStreamBufferReceiveFromISR() in stream_buffer.c
+    ///
+    /// # Return:
+    /// The length of the received message (or 0), and whether a context switch
+    /// should be requested before the interrupt exits.
+    pub fn receive_from_isr(&self, rx_data: &mut [u8]) -> (usize, bool) {
+        self.0.receive_from_isr(rx_data)
+    }
+
+    /// # Description
+    /// Query the length of the next message in the message buffer, which is
+    /// the size `rx_data` must have at least for receive() to succeed.
+    ///
+    /// * C implementation: xStreamBufferNextMessageLengthBytes() in stream_buffer.c
+    ///
+    /// # Return:
+    /// The length of the next message, 0 if the message buffer is empty.
+    pub fn next_length_bytes(&self) -> usize {
+        unsafe { (*(self.0).0.get()).next_message_length_bytes() }
+    }
+
+    /// # Description
+    /// Reset the message buffer to its initial, empty, state.  A message
+    /// buffer can only be reset if no tasks are blocked on it.
+    ///
+    /// # Return:
+    /// Ok if the message buffer was reset, `FreeRtosError::TasksBlocked` if a
+    /// task is blocked on it.
+    pub fn reset(&self) -> Result<(), FreeRtosError> {
+        self.0.reset()
+    }
+
+    /// # Description
+    /// Query the number of free bytes in the message buffer.  Writing a
+    /// message needs `mem::size_of::<usize>()` bytes more than its length.
+    pub fn spaces_available(&self) -> usize {
+        self.0.spaces_available()
+    }
+
+    /// # Description
+    /// Query whether the message buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// # Description
+    /// Query whether the message buffer is full.
+    pub fn is_full(&self) -> bool {
+        self.0.is_full()
+    }
+}
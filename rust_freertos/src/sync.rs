@@ -0,0 +1,240 @@
+// sync.rs - The locks the kernel keeps its state behind.
+// There is no C counterpart to this file.
+//
+// These are the locks of std, except that they count how many of them each
+// thread holds.  The POSIX port preempts a task from a signal handler, which
+// runs kernel code on the thread of the task, at whatever point the task was
+// stopped.  It must not stop a task that holds one of these locks: the handler
+// would then wait forever for a lock its own thread holds, and so would any
+// task that runs after it and needs the lock.  The port checks holds_lock()
+// first, and leaves the tick pending until the task enables interrupts again
+// if it is not safe to take it.
+//
+// The lock of the logger is counted too, as the kernel logs through the
+// macros at the top of lib.rs.  The port may also ask to be called before a
+// thread takes a lock, which is how a task that runs on a lent processor is
+// stopped before it enters the kernel.
+use std::cell::Cell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+
+thread_local! {
+    /* How many locks the thread holds. */
+    static LOCKS_HELD: Cell<usize> = const { Cell::new(0) };
+    /* Set while the thread runs an interrupt handler that stopped it at an
+    arbitrary point, see interrupt(). */
+    static IN_INTERRUPT: Cell<bool> = const { Cell::new(false) };
+    /* Called before the thread takes its next lock, see before_next_lock(). */
+    static BEFORE_NEXT_LOCK: Cell<Option<fn()>> = const { Cell::new(None) };
+}
+
+/// Counts as a lock held by the calling thread for as long as it lives.
+pub(crate) struct LockHeld(());
+
+impl LockHeld {
+    pub(crate) fn new() -> Self {
+        if !holds_lock() {
+            if let Some(hook) = BEFORE_NEXT_LOCK.with(|hook| hook.take()) {
+                hook();
+            }
+        }
+        LOCKS_HELD.with(|held| held.set(held.get() + 1));
+        LockHeld(())
+    }
+
+    /// The lock of the logger, to be held while the kernel logs.  None in an
+    /// interrupt handler: the thread may have been stopped in the middle of
+    /// logging, so the handler must not log at all.
+    pub(crate) fn for_logging() -> Option<Self> {
+        if in_interrupt() {
+            None
+        } else {
+            Some(LockHeld::new())
+        }
+    }
+}
+
+impl Drop for LockHeld {
+    fn drop(&mut self) {
+        LOCKS_HELD.with(|held| held.set(held.get() - 1));
+    }
+}
+
+/// Whether the calling thread holds one of the locks of this module.
+pub(crate) fn holds_lock() -> bool {
+    LOCKS_HELD.with(|held| held.get() > 0)
+}
+
+/// Have `hook` called once, the next time the calling thread takes a lock
+/// while it holds none, before it takes the lock.  None cancels the call.
+pub(crate) fn before_next_lock(hook: Option<fn()>) {
+    BEFORE_NEXT_LOCK.with(|next| next.set(hook));
+}
+
+/// Whether the calling thread is in an interrupt handler, see interrupt().
+pub(crate) fn in_interrupt() -> bool {
+    IN_INTERRUPT.with(|in_interrupt| in_interrupt.get())
+}
+
+/// Run `handler` as the handler of an interrupt that stopped the thread at an
+/// arbitrary point of its code.
+pub(crate) fn interrupt<R>(handler: impl FnOnce() -> R) -> R {
+    let was_in_interrupt = IN_INTERRUPT.with(|in_interrupt| in_interrupt.replace(true));
+    let result = handler();
+    IN_INTERRUPT.with(|in_interrupt| in_interrupt.set(was_in_interrupt));
+    result
+}
+
+/* Wrap the guard of a std lock, poisoned or not, together with the count of
+the lock.  The guard is declared first so that it is dropped, and the lock
+released, before the count goes down. */
+fn wrap<G, W>(result: LockResult<G>, held: LockHeld, make: impl FnOnce(G, LockHeld) -> W) -> LockResult<W> {
+    match result {
+        Ok(guard) => Ok(make(guard, held)),
+        Err(poisoned) => Err(PoisonError::new(make(poisoned.into_inner(), held))),
+    }
+}
+
+fn wrap_try<G, W>(
+    result: TryLockResult<G>,
+    held: LockHeld,
+    make: impl FnOnce(G, LockHeld) -> W,
+) -> TryLockResult<W> {
+    match result {
+        Ok(guard) => Ok(make(guard, held)),
+        Err(TryLockError::Poisoned(poisoned)) => Err(TryLockError::Poisoned(PoisonError::new(
+            make(poisoned.into_inner(), held),
+        ))),
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+/// std::sync::Mutex, counted.
+#[derive(Default)]
+pub struct Mutex<T: ?Sized> {
+    inner: std::sync::Mutex<T>,
+}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    guard: std::sync::MutexGuard<'a, T>,
+    _held: LockHeld,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Mutex {
+            inner: std::sync::Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        /* Counted before it is taken, so that no signal handler finds the
+        lock taken but not counted. */
+        let held = LockHeld::new();
+        wrap(self.inner.lock(), held, |guard, _held| MutexGuard { guard, _held })
+    }
+
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        let held = LockHeld::new();
+        wrap_try(self.inner.try_lock(), held, |guard, _held| MutexGuard { guard, _held })
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let _held = LockHeld::new();
+        self.inner.fmt(f)
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+/// std::sync::RwLock, counted.
+#[derive(Default)]
+pub struct RwLock<T: ?Sized> {
+    inner: std::sync::RwLock<T>,
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    guard: std::sync::RwLockReadGuard<'a, T>,
+    _held: LockHeld,
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    guard: std::sync::RwLockWriteGuard<'a, T>,
+    _held: LockHeld,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        RwLock {
+            inner: std::sync::RwLock::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let held = LockHeld::new();
+        wrap(self.inner.read(), held, |guard, _held| RwLockReadGuard { guard, _held })
+    }
+
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let held = LockHeld::new();
+        wrap(self.inner.write(), held, |guard, _held| RwLockWriteGuard { guard, _held })
+    }
+
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        let held = LockHeld::new();
+        wrap_try(self.inner.try_read(), held, |guard, _held| RwLockReadGuard { guard, _held })
+    }
+
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let held = LockHeld::new();
+        wrap_try(self.inner.try_write(), held, |guard, _held| RwLockWriteGuard { guard, _held })
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let _held = LockHeld::new();
+        self.inner.fmt(f)
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
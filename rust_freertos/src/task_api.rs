@@ -1,13 +1,16 @@
+// task_api.rs - Task introspection and priority APIs.
+// Functions defined in this file are adapted from tasks.c.
+
 use crate::kernel;
 use crate::list;
-use crate::port;
-use crate::port::{BaseType, TickType, UBaseType};
-use crate::task_control;
+use crate::list::ListLink;
+use crate::port::{BaseType, CVoidPointer, TickType, UBaseType};
+use crate::projdefs::pdFAIL;
 use crate::task_control::TaskHandle;
+#[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+use crate::task_control::TaskHookFunction;
 use crate::task_global::*;
-use crate::task_queue;
 use crate::task_queue::taskEVENT_LIST_ITEM_VALUE_IN_USE;
-use crate::trace::*;
 use crate::*;
 
 macro_rules! get_tcb_from_handle_inAPI {
@@ -24,26 +27,30 @@ macro_rules! get_tcb_from_handle_inAPI {
 ///
 ///  Obtain the priority of any task.
 ///
-///
-/// * Implemented by: Fan Jinhao
+/// * C implementation: tasks.c 1340-1355
 ///
 /// # Arguments:
-///  `xTask` Handle of the task to be queried.  Passing a NULL
-///  handle results in the priority of the calling task being returned.
-///
+///  `xTask` Handle of the task to be queried.  Passing None results in the
+///  priority of the calling task being returned.
 ///
 /// * Return:
-///  The priority of xTask.
+///  The priority of xTask.  While the task holds a mutex and has inherited a
+///  higher priority this is the inherited priority.
 ///
+#[cfg(feature = "INCLUDE_uxTaskPriorityGet")]
 pub fn task_priority_get(xTask: Option<TaskHandle>) -> UBaseType {
-    let mut uxReturn: UBaseType = 0;
+    let uxReturn: UBaseType;
+
     taskENTER_CRITICAL!();
     {
+        /* If None is passed in here then it is the priority of the task
+        that called task_priority_get() that is being queried. */
         let pxTCB = get_tcb_from_handle_inAPI!(xTask);
         uxReturn = pxTCB.get_priority();
     }
     taskEXIT_CRITICAL!();
-    return uxReturn;
+
+    uxReturn
 }
 
 ///  INCLUDE_vTaskPrioritySet must be defined as 1 for this function to be available.
@@ -54,314 +61,356 @@ pub fn task_priority_get(xTask: Option<TaskHandle>) -> UBaseType {
 ///  A context switch will occur before the function returns if the priority
 ///  being set is higher than the currently executing task.
 ///
+///  When configUSE_MUTEXES is enabled the new priority becomes the base
+///  priority of the task.  If the task currently holds a mutex and runs at an
+///  inherited priority, the inherited priority is kept until the mutex is given
+///  back, at which point the task drops to the new base priority.
 ///
-/// * Implemented by: Fan Jinhao
+/// * C implementation: tasks.c 1402-1559
 ///
 /// # Arguments:
 ///  `xTask` Handle to the task for which the priority is being set.
-///  Passing a NULL handle results in the priority of the calling task being set.
+///  Passing None results in the priority of the calling task being set.
 ///
 ///  `uxNewPriority` The priority to which the task will be set.
 ///
-///
 /// * Return:
 ///
+#[cfg(feature = "INCLUDE_vTaskPrioritySet")]
 pub fn task_priority_set(xTask: Option<TaskHandle>, uxNewPriority: UBaseType) {
     let mut uxNewPriority = uxNewPriority;
     let mut xYieldRequired: bool = false;
-    let mut uxCurrentBasePriority: UBaseType = 0;
-    let mut uxPriorityUsedOnEntry: UBaseType = 0;
+    let uxCurrentBasePriority: UBaseType;
 
-    //valid ensure
+    /* Ensure the new priority is valid. */
     if uxNewPriority >= configMAX_PRIORITIES!() as UBaseType {
         uxNewPriority = configMAX_PRIORITIES!() as UBaseType - 1 as UBaseType;
     } else {
         mtCOVERAGE_TEST_MARKER!();
     }
-    taskENTER_CRITICAL!();
 
+    taskENTER_CRITICAL!();
     {
-        let mut pxTCB = get_tcb_from_handle_inAPI!(xTask);
-        traceTASK_PRIORITY_SET!(&pxTCB, &uxNewPriority); // crate?
+        /* If None is passed in here then it is the priority of the calling
+        task that is being changed. */
+        let pxTCB = get_tcb_from_handle_inAPI!(xTask);
 
+        traceTASK_PRIORITY_SET!(&pxTCB, &uxNewPriority);
+
+        #[cfg(feature = "configUSE_MUTEXES")]
         {
-            #![cfg(feature = "configUSE_MUTEXES")]
             uxCurrentBasePriority = pxTCB.get_base_priority();
         }
-
+        #[cfg(not(feature = "configUSE_MUTEXES"))]
         {
-            #![cfg(not(feature = "configUSE_MUTEXES"))]
             uxCurrentBasePriority = pxTCB.get_priority();
         }
 
         if uxCurrentBasePriority != uxNewPriority {
-            // change the Priority ;
-            if pxTCB != get_current_task_handle!() {
-                if uxNewPriority >= get_current_task_priority!() {
-                    xYieldRequired = true;
+            /* The priority change may have readied a task of higher
+            priority than the calling task. */
+            if uxNewPriority > uxCurrentBasePriority {
+                if pxTCB != get_current_task_handle!() {
+                    /* The priority of a task other than the currently
+                    running task is being raised.  Is the priority being
+                    raised above that of the running task? */
+                    if uxNewPriority >= get_current_task_priority!() {
+                        xYieldRequired = true;
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+                } else {
+                    /* The priority of the running task is being raised,
+                    but the running task must already be the highest
+                    priority task able to run so no yield is required. */
+                }
+            } else if pxTCB == get_current_task_handle!() {
+                /* Setting the priority of the running task down means
+                there may now be another task of higher priority that
+                is ready to execute. */
+                xYieldRequired = true;
+            } else {
+                /* Setting the priority of any other task down does not
+                require a yield as the running task must be above the
+                new priority of the task being modified. */
+            }
+
+            /* Remember the ready list the task might be referenced from
+            before its uxPriority member is changed so the
+            taskRESET_READY_PRIORITY() macro can function correctly. */
+            let uxPriorityUsedOnEntry = pxTCB.get_priority();
+
+            #[cfg(feature = "configUSE_MUTEXES")]
+            {
+                /* Only change the priority being used if the task is not
+                currently using an inherited priority. */
+                if pxTCB.get_base_priority() == pxTCB.get_priority() {
+                    pxTCB.set_priority(uxNewPriority);
                 } else {
                     mtCOVERAGE_TEST_MARKER!();
                 }
-            } else {;            } // 当前正在执行的task已是最高优先级
-        } else if pxTCB == get_current_task_handle!() {
-            xYieldRequired = true;
-        } else {;        }
-        // 其他task优先级设置不需要yield    ???
 
-        {
-            #![cfg(feature = "configUSE_MUTEXES")]
-            if pxTCB.get_base_priority() == pxTCB.get_priority() {
+                /* The base priority gets set whatever. */
+                pxTCB.set_base_priority(uxNewPriority);
+            }
+            #[cfg(not(feature = "configUSE_MUTEXES"))]
+            {
                 pxTCB.set_priority(uxNewPriority);
+            }
+
+            /* Only reset the event list item value if the value is not
+            being used for anything else. */
+            let event_list_item = pxTCB.get_event_list_item();
+            if (list::get_list_item_value(&event_list_item) & taskEVENT_LIST_ITEM_VALUE_IN_USE) == 0
+            {
+                list::set_list_item_value(
+                    &event_list_item,
+                    configMAX_PRIORITIES!() as TickType - pxTCB.get_priority() as TickType,
+                );
             } else {
                 mtCOVERAGE_TEST_MARKER!();
             }
-            pxTCB.set_base_priority(uxNewPriority);
-        }
-        #[cfg(not(feature = "configUSE_MUTEXS"))]
-        pxTCB.set_priority(uxNewPriority);
-
-        let event_list_item = pxTCB.get_event_list_item();
-        let state_list_item = pxTCB.get_state_list_item();
 
-        if (list::get_list_item_value(&event_list_item) & taskEVENT_LIST_ITEM_VALUE_IN_USE) == 0 {
-            list::set_list_item_value(
-                &event_list_item,
-                (configMAX_PRIORITIES!() as TickType - uxNewPriority as TickType),
-            );
-        } else {
-            mtCOVERAGE_TEST_MARKER!();
-        }
-
-        if list::is_contained_within(
-            &READY_TASK_LISTS[uxPriorityUsedOnEntry as usize],
-            &state_list_item,
-        ) {
-            if list::list_remove(state_list_item) == 0 as UBaseType {
-                portRESET_READY_PRIORITY!(uxPriorityUsedOnEntry, uxTopReadyPriority);
+            /* If the task is in the blocked or suspended list we need do
+            nothing more than change its priority variable.  However, if
+            the task is in a ready list it needs to be removed and placed
+            in the list appropriate to its new priority. */
+            let state_list_item = pxTCB.get_state_list_item();
+            if list::is_contained_within(
+                &READY_TASK_LISTS[uxPriorityUsedOnEntry as usize],
+                &state_list_item,
+            ) {
+                /* The task is currently in its ready list - remove before
+                adding it to it's new ready list.  As we are in a critical
+                section we can do this even if the scheduler is suspended. */
+                if list::list_remove(state_list_item) == 0 as UBaseType {
+                    /* It is known that the task is in its ready list so
+                    there is no need to check again and the port level
+                    reset macro can be called directly. */
+                    portRESET_READY_PRIORITY!(uxPriorityUsedOnEntry, get_top_ready_priority!());
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+                pxTCB
+                    .add_task_to_ready_list()
+                    .unwrap_or_else(|err| panic!("Failed to ready the task: {:?}", err));
             } else {
                 mtCOVERAGE_TEST_MARKER!();
             }
-            pxTCB.add_task_to_ready_list();
-        } else {
-            mtCOVERAGE_TEST_MARKER!();
-        }
-
-        if xYieldRequired != false {
-            taskYIELD_IF_USING_PREEMPTION!();
         } else {
             mtCOVERAGE_TEST_MARKER!();
         }
     }
-
     taskEXIT_CRITICAL!();
-}
-
-/*
-   pub fn task_get_system_state(pxTaskStatusArray:&TaskStatus , uxArraySize:UBaseType , pulTotalRunTime:u32) -> UBaseType
-   {
-   let mut uxtask:UBaseType = 0 ;
-   let mut uxqueue = configmax_priorities!();
-   kernel::task_suspend_all();      // ???
-   {
-/* is there a space in the array for each task in the system? */
-if uxarraysize >= uxcurrentnumberoftasks
-{
-    // while 实现do while
-    uxqueue = uxqueue - 1;
-    uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), &( pxreadytaskslists[ uxqueue ] ), eready );
-    while uxqueue > ( ubasetype ) tskidle_priority  /*lint !e961 misra exception as the casts are only redundant for some ports. */
-    {
-        uxqueue--;
-        uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), &( pxreadytaskslists[ uxqueue ] ), eready );
-    }
-
-    uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), pxdelayedtasklist as &list , eblocked );
-    uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), pxoverflowdelayedtasklist as &list , eblocked );
-
-    #[cfg( feature = "include_vtaskdelete" )]
-    uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), &xtaskswaitingtermination, edeleted );
-
-    #[cfg( feature = "include_vtasksuspend" )]
-    uxtask += prvlisttaskswithinsinglelist( &( pxtaskstatusarray[ uxtask ] ), &xsuspendedtasklist, esuspended );
-
-    {
-        #![cfg( feature = "configgenerate_run_time_stats" )]
-        if pultotalruntime != null
-        {
-            #[cfg( feature = "portalt_get_run_time_counter_value" )]
-            portalt_get_run_time_counter_value!( ( &pultotalruntime ) );
-            #[cfg(not( feature = "portalt_get_run_time_counter_value" ))]
-            &pultotalruntime = portget_run_time_counter_value!();
-        }
-    }
-    {
-        #![cfg(not( feature = "configgenrate_run_time_stats" ))]
-        if( pultotalruntime != null )
-            &pultotalruntime = 0;                               // 用&解除引用
-    }
-}
-else {
-    mtcoverage_test_marker!();
-}
-}
-kernel::xtaskresumeall();
-return uxtask;
-}
-
-pub fn task_test_info(xTask:Option<&TaskHandle>, pxTaskStatus:&TaskStatus, xGetFreeStackSpace:BaseType, eState:TaskState)
-{
-
-    /* xTask is NULL then get the state of the calling task. */
-    let pxTCB = get_tcb_from_handle!( xTask );
-
-    pxTaskStatus.xHandle = pxTCB;
-    pxTaskStatus.task_name = &( pxTCB.task_name [ 0 ] );
-    pxTaskStatus.uxCurrentPriority = pxTCB.task_priority;
-    pxTaskStatus.pxStackBase = pxTCB.stack_pose;
-    pxTaskStatus.xTaskNumber = pxTCB.tcb_Number;
-
-    #[cfg( feature = "configUSE_MUTEXES" )]
-    pxTaskStatus.base_priority = pxTCB.base_priority;
-    #[cfg(not( feature = "configUSE_MUTEXES" ))]
-    pxTaskStatus.base_priority = 0;
-
-    #[cfg ( feature = "configGENERATE_RUN_TIME_STATS" )]
-    pxTaskStatus.ulRunTimeCounter = pxTCB.ulRunTimeCounter;
-    #[cfg(not( feature = "configGENERATE_RUN_TIME_STATS" ))]
-    pxTaskStatus.ulRunTimeCounter = 0;
-
-    /* Obtaining the task state is a little fiddly, so is only done if the
-       value of eState passed into this function is eInvalid - otherwise the
-       state is just set to whatever is passed in. */
-    if eState != eInvalid
-    {
-        if pxTCB == &CurrentTCB {
-            pxTaskStatus.eCurrentState = eRunning;
-        } else
-        {
-            pxTaskStatus.eCurrentState = eState;
-
-            {
-                #![cfg(feature = "INCLUDE_vTaskSuspend" )]
-                /* If the task is in the suspended list then there is a
-                   chance it is actually just blocked indefinitely - so really
-                   it should be reported as being in the Blocked state. */
-                if eState == eSuspended
-                {
-                    vTaskSuspendAll();
-
-                    {
-                        if listLIST_ITEM_CONTAINER( &( pxTCB.xEventListItem ) ) != NULL
-                        {
-                            pxTaskStatus.eCurrentState = eBlocked;
-                        }
-                    }
-
-                    ( void ) xTaskResumeAll();
-                }
-            }
-        }
-    }
-    else
-    {
-        pxTaskStatus.eCurrentState = eTaskGetState( pxTCB );
-    }
 
-    /* Obtaining the stack space takes some time, so the xGetFreeStackSpace
-       parameter is provided to allow it to be skipped. */
-    if xGetFreeStackSpace != pdFALSE
-    {
-        if portSTACK_GROWTH > 0 {
-            pxTaskStatus.usStackHighWaterMark = prvTaskCheckFreeStackSpace( pxTCB.pxEndOfStack as &i8 );
-        } else {
-            pxTaskStatus.usStackHighWaterMark = prvTaskCheckFreeStackSpace( pxTCB.pxStack as &i8 );
-        }
-    }
-    else
-    {
-        pxTaskStatus.usStackHighWaterMark = 0;
+    if xYieldRequired {
+        taskYIELD_IF_USING_PREEMPTION!();
+    } else {
+        mtCOVERAGE_TEST_MARKER!();
     }
 }
 
-
-pub fn task_get_application_task_tag(xTask:TaskHandle) -> UBaseType
-{
-    let mut xReturn:UBaseType = 0 ;      // TaskHookFunction
-    let mut pxTCB = get_tcb_from_handle_inAPI!(&xTask) ;
-    taskENTER_CRITICAL!() ;
-    xReturn = pxTCB.get_task_tag ;
-    taskEXIT_CRITICAL!() ;
-    xReturn ;
+/* Search a single task list for a task with the given name. */
+#[cfg(feature = "INCLUDE_xTaskGetHandle")]
+fn search_for_name_within_single_list(
+    pxList: &ListLink,
+    pcNameToQuery: &str,
+) -> Option<TaskHandle> {
+    list::get_list_items(pxList)
+        .iter()
+        .map(|item| list::get_list_item_owner(item))
+        .find(|task| task.get_name() == pcNameToQuery)
 }
 
-pub fn task_get_handle(pcNameToQuery:&char) -> &TaskHandle
-{
-    let mut uxQueue:UBaseType = configMAX_PRIORITIES;
-    let mut pxTCB:&TCB = 0 ;
+///  INCLUDE_xTaskGetHandle must be defined as 1 for this function to be available.
+///
+///  Looks up the handle of a task from the task's name.
+///
+///  NOTE:  This function takes a relatively long time to complete and should be
+///  used sparingly.
+///
+/// * C implementation: tasks.c 2256-2314
+///
+/// # Arguments:
+///  `pcNameToQuery` The text name (as a standard string) of the task for which
+///  the handle will be returned.
+///
+/// * Return:
+///  If a task that has the name passed in pcNameToQuery can be located then the
+///  handle of the task is returned, otherwise None is returned.
+///
+#[cfg(feature = "INCLUDE_xTaskGetHandle")]
+pub fn task_get_handle(pcNameToQuery: &str) -> Option<TaskHandle> {
+    let mut pxTCB: Option<TaskHandle> = None;
 
-    vTaskSuspendAll();
+    kernel::task_suspend_all();
     {
         /* Search the ready lists. */
-        while uxQueue > ( UBaseType_t ) tskIDLE_PRIORITY  /*lint !e961 MISRA exception as the casts are only redundant for some ports. */
-        {
-            uxQueue--;
-            pxTCB = prvSearchForNameWithinSingleList( ( List_t * ) &( pxReadyTasksLists[ uxQueue ] ), pcNameToQuery );
+        for uxQueue in (0..configMAX_PRIORITIES!()).rev() {
+            pxTCB = search_for_name_within_single_list(&READY_TASK_LISTS[uxQueue], pcNameToQuery);
 
-            if pxTCB != NULL
-            {
+            if pxTCB.is_some() {
                 /* Found the handle. */
                 break;
             }
         }
 
         /* Search the delayed lists. */
-        if pxTCB == NULL {
-            pxTCB = prvSearchForNameWithinSingleList( ( List_t * ) pxDelayedTaskList, pcNameToQuery );
-        } else {
-            pxTCB = prvSearchForNameWithinSingleList( ( List_t * ) pxOverflowDelayedTaskList, pcNameToQuery );
+        if pxTCB.is_none() {
+            pxTCB = search_for_name_within_single_list(&DELAYED_TASK_LIST, pcNameToQuery);
         }
 
+        if pxTCB.is_none() {
+            pxTCB = search_for_name_within_single_list(&OVERFLOW_DELAYED_TASK_LIST, pcNameToQuery);
+        }
+
+        #[cfg(feature = "INCLUDE_vTaskSuspend")]
         {
-            #![cfg ( INCLUDE_vTaskSuspend == 1 )]
-            if pxTCB == NULL
-                pxTCB = prvSearchForNameWithinSingleList( &xSuspendedTaskList, pcNameToQuery );
+            if pxTCB.is_none() {
+                /* Search the suspended list. */
+                pxTCB = search_for_name_within_single_list(&SUSPENDED_TASK_LIST, pcNameToQuery);
+            }
         }
 
+        #[cfg(feature = "INCLUDE_vTaskDelete")]
         {
-            #![cfg( INCLUDE_vTaskDelete == 1 )]
-            if pxTCB == NULL
-                /* Search the deleted list. */{
-                    pxTCB = prvSearchForNameWithinSingleList( &xTasksWaitingTermination, pcNameToQuery );
-                }
+            if pxTCB.is_none() {
+                /* Search the deleted list. */
+                pxTCB =
+                    search_for_name_within_single_list(&TASKS_WAITING_TERMINATION, pcNameToQuery);
+            }
         }
     }
-    xTaskResumeAll();
+    kernel::task_resume_all().unwrap();
 
-    return pxTCB;
+    pxTCB
 }
 
-pub fn task_get_idle_task_handle() -> &TaskHandle
-{
+///  INCLUDE_xTaskGetIdleTaskHandle must be defined as 1 for this function to be available.
+///
+///  Returns the handle of the idle task.  It is not valid to call this function
+///  before the scheduler has been started.
+///
+/// * C implementation: tasks.c 2395-2401
+///
+/// * Return:
+///  The handle of the idle task.
+///
+#[cfg(feature = "INCLUDE_xTaskGetIdleTaskHandle")]
+pub fn task_get_idle_task_handle() -> TaskHandle {
     /* If xTaskGetIdleTaskHandle() is called before the scheduler has been
-       started, then xIdleTaskHandle will be NULL. */
-    return IdleTaskHandle;
+    started, then xIdleTaskHandle will be NULL. */
+    IDLE_TASK_HANDLE
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| panic!("Idle task has not been created"))
+}
+
+///  Sets the application task tag of a task.  The tag is a hook function
+///  that can be called later through task_call_application_task_hook(), and
+///  is typically used by trace tools.
+///
+/// * C implementation: tasks.c 2673-2693
+///
+/// # Arguments:
+///  `xTask` The task whose tag is set.  Passing None sets the tag of the
+///  calling task.
+///
+///  `pxHookFunction` The tag, None clears it.
+///
+/// * Return:
+///
+#[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+pub fn task_set_application_task_tag(
+    xTask: Option<TaskHandle>,
+    pxHookFunction: Option<TaskHookFunction>,
+) {
+    /* If xTask is None then it is the task hook of the calling task that is
+    getting set. */
+    let xTCB = get_tcb_from_handle_inAPI!(xTask);
+
+    /* Save the hook function in the TCB.  A critical section is required as
+    the value can be accessed from an interrupt. */
+    taskENTER_CRITICAL!();
+    xTCB.set_task_tag(pxHookFunction);
+    taskEXIT_CRITICAL!();
 }
 
-pub fn task_get_stack_high_water_mark(xtask:Option<&TaskHandle>) -> UBaseType
-{
-    let mut pucEndOfStack = 0;
-    let mut uxReturn:UBaseType = 0;
+///  Returns the application task tag of a task.
+///
+/// * C implementation: tasks.c 2700-2724
+///
+/// # Arguments:
+///  `xTask` The task whose tag is returned.  Passing None returns the tag of
+///  the calling task.
+///
+/// * Return:
+///  The tag of the task, or None if no tag has been set.
+///
+#[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+pub fn task_get_application_task_tag(xTask: Option<TaskHandle>) -> Option<TaskHookFunction> {
+    let xReturn: Option<TaskHookFunction>;
 
-    let pxTCB:&TCB = get_tcb_from_handle(xtask);
+    /* If xTask is None then we are getting our own task hook. */
+    let pxTCB = get_tcb_from_handle_inAPI!(xTask);
 
-    if portSTACK_GROWTH < 0 {
-        pucEndOfStack = pxTCB.pxStack;
-    } else {
-        pucEndOfStack = pxTCB.pxEndOfStack;
+    /* A critical section is required as the value can be accessed from an
+    interrupt. */
+    taskENTER_CRITICAL!();
+    {
+        xReturn = pxTCB.get_task_tag();
+    }
+    taskEXIT_CRITICAL!();
+
+    xReturn
+}
+
+///  Calls the hook function associated with xTask.
+///
+/// * C implementation: tasks.c 2731-2756
+///
+/// # Arguments:
+///  `xTask` The task whose hook is called.  Passing None calls the hook of
+///  the calling task.
+///
+///  `pvParameter` The value passed to the hook function.
+///
+/// * Return:
+///  The value returned by the hook function, or pdFAIL if the task has no hook.
+///
+#[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+pub fn task_call_application_task_hook(
+    xTask: Option<TaskHandle>,
+    pvParameter: CVoidPointer,
+) -> BaseType {
+    let xTCB = get_tcb_from_handle_inAPI!(xTask);
+
+    match xTCB.get_task_tag() {
+        Some(hook) => hook(pvParameter),
+        None => pdFAIL,
     }
+}
 
-    uxReturn = ( UBaseType )prvTaskCheckFreeStackSpace( pucEndOfStack );
+///  Returns the high water mark of the stack associated with xTask, see
+///  TaskHandle::stack_high_water_mark().
+///
+///  INCLUDE_uxTaskGetStackHighWaterMark must be defined as 1 for this
+///  function to be available.
+///
+/// * C implementation: tasks.c 3553-3574
+///
+/// # Arguments:
+///  `xTask` The task whose stack is checked.  Passing None checks the stack
+///  of the calling task.
+///
+/// * Return:
+///  The smallest amount of free stack space there has been (in words) since
+///  the task started.
+///
+#[cfg(feature = "INCLUDE_uxTaskGetStackHighWaterMark")]
+pub fn task_get_stack_high_water_mark(xTask: Option<TaskHandle>) -> UBaseType {
+    let pxTCB = get_tcb_from_handle_inAPI!(xTask);
 
-    return uxReturn;
+    pxTCB.stack_high_water_mark()
 }
-*/
@@ -1,16 +1,21 @@
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+use crate::event_groups::{EventBits, EventGroup};
 use crate::kernel::*;
 use crate::list;
 use crate::list::ItemLink; use crate::list::*;
 use crate::port::*;
 use crate::projdefs::FreeRtosError;
+#[cfg(feature = "portSTACK_GUARD_PAGE")]
+use crate::stack_guard;
+use crate::sync::{Mutex, RwLock};
 use crate::task_global::*;
 use crate::*;
-use std::boxed::FnBox;
-use std::mem;
-use std::sync::{Arc, RwLock, Weak};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Weak};
+use std::{fmt, mem, thread};
 
 /* Task states returned by eTaskGetState. */
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum task_state {
     running = 0,
@@ -20,6 +25,71 @@ pub enum task_state {
     deleted = 4,
 }
 
+/* Value used to fill the stack of a task when the task is created.  This is
+used purely for checking the high water mark for tasks. */
+pub const tskSTACK_FILL_BYTE: u8 = 0xa5;
+
+/* Number of bytes at the stack limit that stack overflow checking method 2
+expects to still hold tskSTACK_FILL_BYTE. */
+#[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+const taskSTACK_OVERFLOW_CHECK_BYTES: usize = 16;
+
+/* Bit of the exit event of a task that is set once the task has been deleted,
+see TaskHandle::wait_for_exit(). */
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+const taskEXITED_BIT: EventBits = 0x01;
+
+/* Values that can be assigned to the notify_state member of the TCB. */
+#[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+pub const taskNOT_WAITING_NOTIFICATION: u8 = 0;
+#[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+pub const taskWAITING_NOTIFICATION: u8 = 1;
+#[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+pub const taskNOTIFICATION_RECEIVED: u8 = 2;
+
+/* Prototype of the functions that can be used as a task tag, see
+task_set_application_task_tag() in task_api.rs. */
+#[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+pub type TaskHookFunction = fn(CVoidPointer) -> BaseType;
+
+/// What happens to a task whose closure panics, see TCB::panic_policy().  The
+/// panic is reported to the hook registered with kernel::set_task_panic_hook()
+/// first.
+///
+/// Only the task is dealt with.  A panic inside a critical section, or while
+/// the scheduler is suspended, leaves the kernel in that state.
+#[derive(Clone)]
+pub enum PanicPolicy {
+    /// Delete the task, as if its closure had returned.
+    Delete,
+    /// Suspend the task, so it can be inspected.  It is deleted if resumed.
+    #[cfg(feature = "INCLUDE_vTaskSuspend")]
+    Suspend,
+    /// Run the given function in place of the closure, which cannot be called
+    /// again.  The policy still applies if the function panics too.
+    Restart(Arc<dyn Fn() + Send + Sync>),
+    /// End the scheduler, and with it every task.
+    AbortScheduler,
+}
+
+impl Default for PanicPolicy {
+    fn default() -> Self {
+        PanicPolicy::Delete
+    }
+}
+
+impl fmt::Debug for PanicPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PanicPolicy::Delete => write!(f, "Delete"),
+            #[cfg(feature = "INCLUDE_vTaskSuspend")]
+            PanicPolicy::Suspend => write!(f, "Suspend"),
+            PanicPolicy::Restart(_) => write!(f, "Restart"),
+            PanicPolicy::AbortScheduler => write!(f, "AbortScheduler"),
+        }
+    }
+}
+
 pub enum updated_top_priority {
     Updated,
     Notupdated,
@@ -35,6 +105,19 @@ pub struct task_control_block {
     task_name: String,
     // `stack_pos` is StackType because raw pointer can't be sent between threads safely.
     stack_pos: StackType,
+    // The last stack pointer the port saved for this task, stored the same way.
+    top_of_stack: StackType,
+    // The boxed task closure, until run_wrapper() takes it over. Stored as an
+    // address for the same reason as `stack_pos`, 0 once it has been taken.
+    task_function: usize,
+    // Tasks waiting for this task to end block on this event group.
+    #[cfg(feature = "INCLUDE_vTaskDelete")]
+    exit_event: Arc<EventGroup>,
+    panic_policy: PanicPolicy,
+    // The stack was given by initialise_with_stack(), so it is not freed when
+    // the task is deleted.
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    stack_given: bool,
 
     //* end of stack
     // #[cfg(portStack_GROWTH)]{}
@@ -51,7 +134,7 @@ pub struct task_control_block {
     mutexes_held: UBaseType,
 
     #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-    runtime_counter: TickType,
+    runtime_counter: u64,
 
     //* notify information
     #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
@@ -60,6 +143,14 @@ pub struct task_control_block {
     notify_state: u8,
     #[cfg(feature = "INCLUDE_xTaskAbortDelay")]
     delay_aborted: bool,
+
+    #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+    task_tag: Option<TaskHookFunction>,
+
+    /* Stores a number that increments each time a TCB is created.  It allows
+    debuggers to determine when a task has been deleted and then recreated. */
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    task_number: UBaseType,
 }
 
 pub type TCB = task_control_block;
@@ -73,6 +164,13 @@ impl task_control_block {
             task_stacksize: configMINIMAL_STACK_SIZE!(),
             task_name: String::from("Unnamed"),
             stack_pos: 0,
+            top_of_stack: 0,
+            task_function: 0,
+            #[cfg(feature = "INCLUDE_vTaskDelete")]
+            exit_event: Arc::new(EventGroup::new()),
+            panic_policy: Default::default(),
+            #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+            stack_given: false,
 
             //* nesting
             #[cfg(feature = "portCRITICAL_NESTING_IN_TCB")]
@@ -94,6 +192,12 @@ impl task_control_block {
             notify_state: 0,
             #[cfg(feature = "INCLUDE_xTaskAbortDelay")]
             delay_aborted: false,
+
+            #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+            task_tag: None,
+
+            #[cfg(feature = "configUSE_TRACE_FACILITY")]
+            task_number: 0,
         }
     }
 
@@ -164,6 +268,20 @@ impl task_control_block {
         self
     }
 
+    /// * Descrpition:
+    /// Set what happens to the task if its closure panics.  By default the
+    /// task is deleted.
+    ///
+    /// # Arguments:
+    ///  `policy` The policy to apply.
+    ///
+    /// # Return:
+    /// Return a TCB with the new panic policy.
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
     /// * Descrpition:
     ///
     ///  Internally, within the FreeRTOS implementation, tasks use two blocks of
@@ -187,8 +305,8 @@ impl task_control_block {
     /// * Implemented by: Fan Jinhao
     ///
     /// # Arguments:
-    ///  `func` Pointer to the task entry function.  Tasks
-    ///  must be implemented to never return (i.e. continuous loop).
+    ///  `func` Pointer to the task entry function.  Unlike in FreeRTOS a task
+    ///  may return, which deletes it as if it had called task_delete(None).
     ///
     /// # Return:
     ///  `pdPASS` if the task was successfully created and added to a ready
@@ -200,15 +318,101 @@ impl task_control_block {
         F: FnOnce() -> () + Send + 'static,
     {
         let size_of_stacktype = std::mem::size_of::<StackType>();
-        let stacksize_as_bytes = size_of_stacktype * self.task_stacksize as usize;
+        let mut stacksize_as_bytes = size_of_stacktype * self.task_stacksize as usize;
+
+        // The task runs on this stack, so it may be larger than asked for.
+        #[cfg(not(feature = "portSTACK_GUARD_PAGE"))]
+        let px_stack = {
+            if stacksize_as_bytes < portMINIMAL_STACK_BYTES {
+                stacksize_as_bytes = portMINIMAL_STACK_BYTES;
+                self.task_stacksize = (stacksize_as_bytes / size_of_stacktype) as UBaseType;
+            }
+            // Return `Err` if malloc fails.
+            port::port_malloc(stacksize_as_bytes)?
+        };
+
+        // The task really runs on this stack, which may be larger than asked for.
+        #[cfg(feature = "portSTACK_GUARD_PAGE")]
+        let px_stack = {
+            let (px_stack, size) = stack_guard::allocate_stack(stacksize_as_bytes)?;
+            stacksize_as_bytes = size;
+            self.task_stacksize = (size / size_of_stacktype) as UBaseType;
+            px_stack
+        };
+
+        self.initialise_new_task(func, px_stack, stacksize_as_bytes)
+    }
+
+    /// * Descrpition:
+    ///
+    ///  Create a new task like initialise() does, on a stack provided by the
+    ///  application instead of one allocated with port_malloc().  The stack
+    ///  size set with stacksize() is replaced by the length of `stack`.  The
+    ///  stack is not freed when the task is deleted.
+    ///
+    ///  Only the stack is provided, the TCB is allocated on the heap as it is
+    ///  by initialise(), together with the event group used to join the task
+    ///  and, on the POSIX port, the thread the task runs on.  The thread runs
+    ///  on `stack`, so `stack` must hold at least port::portMINIMAL_STACK_BYTES.
+    ///
+    /// * C implementation: xTaskCreateStatic() in tasks.c, which also takes
+    ///   the memory of the TCB.
+    ///
+    /// # Arguments:
+    ///  `func` Pointer to the task entry function.
+    ///  `stack` The stack of the task.
+    ///
+    /// # Return:
+    ///  The handle of the task if it was successfully created and added to a
+    ///  ready list, otherwise an error code defined in the file projdefs.h,
+    ///  `StackTooSmall` if `stack` is smaller than the minimum.
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    pub fn initialise_with_stack<F>(
+        mut self,
+        func: F,
+        stack: &'static mut [StackType],
+    ) -> Result<TaskHandle, FreeRtosError>
+    where
+        F: FnOnce() -> () + Send + 'static,
+    {
+        let stacksize_as_bytes = std::mem::size_of_val(stack);
+        if stacksize_as_bytes < portMINIMAL_STACK_BYTES {
+            return Err(FreeRtosError::StackTooSmall);
+        }
+        self.task_stacksize = stack.len() as UBaseType;
+        self.stack_given = true;
+        self.initialise_new_task(func, stack.as_mut_ptr() as CVoidPointer, stacksize_as_bytes)
+    }
+
+    /* The part of task creation that comes after the stack is allocated,
+    prvInitialiseNewTask() and prvAddNewTaskToReadyList() in tasks.c. */
+    fn initialise_new_task<F>(
+        mut self,
+        func: F,
+        px_stack: CVoidPointer,
+        stacksize_as_bytes: usize,
+    ) -> Result<TaskHandle, FreeRtosError>
+    where
+        F: FnOnce() -> () + Send + 'static,
+    {
+        let size_of_stacktype = std::mem::size_of::<StackType>();
         trace!(
             "Initialising Task: {}, stack size: {} bytes",
             self.task_name,
             stacksize_as_bytes
         );
 
-        // Return `Err` if malloc fails.
-        let px_stack = port::port_malloc(stacksize_as_bytes)?;
+        #[cfg(any(
+            feature = "configCHECK_FOR_STACK_OVERFLOW",
+            feature = "configUSE_TRACE_FACILITY",
+            feature = "INCLUDE_uxTaskGetStackHighWaterMark"
+        ))]
+        {
+            /* Fill the stack with a known value to assist debugging. */
+            unsafe {
+                std::ptr::write_bytes(px_stack as *mut u8, tskSTACK_FILL_BYTE, stacksize_as_bytes);
+            }
+        }
 
         // A trick here. By changing raw pointer `px_stack` to StackType,
         // avoid using unsafe `*mut` as a struct field.
@@ -221,10 +425,18 @@ impl task_control_block {
             self.stack_pos
         );
 
-        let mut top_of_stack = self.stack_pos + self.task_stacksize as StackType - 1;
-        top_of_stack = top_of_stack & portBYTE_ALIGNMENT_MASK as StackType;
+        /* Calculate the top of stack address.  This depends on whether the stack
+        grows from high memory to low (as per the 80x86) or vice versa.
+        portSTACK_GROWTH is used to make the result positive or negative as
+        required by the port. */
+        let mut top_of_stack = if portSTACK_GROWTH < 0 {
+            self.stack_pos + (stacksize_as_bytes - size_of_stacktype) as StackType
+        } else {
+            self.stack_pos
+        };
+        top_of_stack = top_of_stack & !(portBYTE_ALIGNMENT_MASK as StackType);
 
-        let f = Box::new(Box::new(func) as Box<FnBox()>); // Pass task function as a parameter.
+        let f = Box::new(Box::new(func) as Box<dyn FnOnce()>); // Pass task function as a parameter.
         let param_ptr = &*f as *const _ as *mut _; // Convert to raw pointer.
         trace!(
             "Function ptr of {} is at {:X}",
@@ -236,15 +448,23 @@ impl task_control_block {
          * this is how freertos.rs approaches this problem, and is explained here:
          * https://stackoverflow.com/questions/32270030/how-do-i-convert-a-rust-closure-to-a-c-style-callback
          */
+        /* Keep another task from creating a task between handing the stack
+        over and creating the thread that runs on it. */
+        taskENTER_CRITICAL!();
+        port::port_set_next_task_stack(px_stack, stacksize_as_bytes);
         let result =
             port::port_initialise_stack(top_of_stack as *mut _, Some(run_wrapper), param_ptr);
+        taskEXIT_CRITICAL!();
         match result {
-            Ok(_) => {
+            Ok(saved_top_of_stack) => {
                 trace!("Stack initialisation succeeded");
+                self.top_of_stack = saved_top_of_stack as StackType;
                 /* We MUST forget `f`, otherwise it will be freed at the end of this function.
                  * But we need to call `f` later in `run_wrapper`, which will lead to
                  * some unexpected behavior.
+                 * If the task is deleted before it runs, delete_tcb() frees it instead.
                  */
+                self.task_function = param_ptr as usize;
                 mem::forget(f);
             }
             Err(e) => return Err(e),
@@ -272,7 +492,7 @@ impl task_control_block {
             self.runtime_counter = 0;
         }
 
-        #[cfg(feature = "config_USE_TASK_NOTIFICATIONS")]
+        #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
         {
             self.notify_state = taskNOT_WAITING_NOTIFICATION;
             self.notified_value = 0;
@@ -289,11 +509,46 @@ impl task_control_block {
         let item_value = (configMAX_PRIORITIES!() - handle.get_priority()) as TickType;
         list::set_list_item_value(&state_list_item, item_value);
 
+        #[cfg(feature = "portSTACK_GUARD_PAGE")]
+        stack_guard::register_stack(px_stack, &handle);
+
         handle.add_new_task_to_ready_list()?;
 
         Ok(handle)
     }
 
+    /// * Descrpition:
+    /// Create a new task like initialise() does, from a closure that returns a
+    /// value.  The task ends when the closure returns, and another task can
+    /// retrieve the value through the returned JoinHandle.
+    ///
+    /// # Arguments:
+    ///  `func` The task entry function.
+    ///
+    /// # Return:
+    ///  The handle of the created task together with the JoinHandle for its
+    ///  result, or the error initialise() returned.
+    ///
+    #[cfg(feature = "INCLUDE_vTaskDelete")]
+    pub fn spawn<F, R>(self, func: F) -> Result<(TaskHandle, JoinHandle<R>), FreeRtosError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let task_result = Arc::clone(&result);
+        let handle = self.initialise(move || {
+            let value = func();
+            *task_result.lock().unwrap() = Some(value);
+        })?;
+
+        let join_handle = JoinHandle {
+            task: handle.clone(),
+            result,
+        };
+        Ok((handle, join_handle))
+    }
+
     pub fn get_state_list_item(&self) -> ItemLink {
         Arc::clone(&self.state_list_item)
     }
@@ -315,13 +570,13 @@ impl task_control_block {
     }
 
     #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-    pub fn get_run_time(&self) -> TickType {
+    pub fn get_run_time(&self) -> u64 {
         self.runtime_counter
     }
 
     #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-    pub fn set_run_time(&mut self, next_val: TickType) -> TickType {
-        let prev_val: u32 = self.runtime_counter;
+    pub fn set_run_time(&mut self, next_val: u64) -> u64 {
+        let prev_val: u64 = self.runtime_counter;
         self.runtime_counter = next_val;
         prev_val
     }
@@ -348,6 +603,26 @@ impl task_control_block {
         self.mutexes_held = new_count;
     }
 
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn get_notified_value(&self) -> u32 {
+        self.notified_value
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn set_notified_value(&mut self, new_val: u32) {
+        self.notified_value = new_val;
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn get_notify_state(&self) -> u8 {
+        self.notify_state
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn set_notify_state(&mut self, new_state: u8) {
+        self.notify_state = new_state;
+    }
+
     pub fn get_base_priority(&self) -> UBaseType {
         self.base_priority
     }
@@ -355,6 +630,77 @@ impl task_control_block {
     pub fn set_base_priority(&mut self, new_val: UBaseType) {
         self.base_priority = new_val
     }
+
+    #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+    pub fn get_task_tag(&self) -> Option<TaskHookFunction> {
+        self.task_tag
+    }
+
+    #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+    pub fn set_task_tag(&mut self, new_tag: Option<TaskHookFunction>) {
+        self.task_tag = new_tag;
+    }
+
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    pub fn get_task_number(&self) -> UBaseType {
+        self.task_number
+    }
+
+    /* Count the bytes of the stack that still hold tskSTACK_FILL_BYTE, starting
+    from the end the stack grows towards.  The result is in words, as for
+    prvTaskCheckFreeStackSpace() in tasks.c. */
+    #[cfg(any(
+        feature = "configUSE_TRACE_FACILITY",
+        feature = "INCLUDE_uxTaskGetStackHighWaterMark"
+    ))]
+    fn check_free_stack_space(&self) -> UBaseType {
+        let size_of_stacktype = std::mem::size_of::<StackType>();
+        let stacksize_as_bytes = size_of_stacktype * self.task_stacksize as usize;
+        let stack = unsafe { std::slice::from_raw_parts(self.stack_pos as *const u8, stacksize_as_bytes) };
+
+        let count = if portSTACK_GROWTH < 0 {
+            stack.iter().take_while(|byte| **byte == tskSTACK_FILL_BYTE).count()
+        } else {
+            stack.iter().rev().take_while(|byte| **byte == tskSTACK_FILL_BYTE).count()
+        };
+
+        (count / size_of_stacktype) as UBaseType
+    }
+}
+
+impl task_control_block {
+    /* Tell whether the stack of the task has overflowed, or looks like it
+    has overflowed in the past.  configCHECK_FOR_STACK_OVERFLOW!() selects the
+    method, see StackMacros.h:
+    1 - compare the stack pointer saved when the task last yielded with the
+        stack limit.
+    2 - check that the bytes at the stack limit still hold
+        tskSTACK_FILL_BYTE.  This does not guarantee that an overflowed
+        stack will always be recognised. */
+    #[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+    fn stack_overflowed(&self) -> bool {
+        let size_of_stacktype = std::mem::size_of::<StackType>();
+        let stacksize_as_bytes = size_of_stacktype * self.task_stacksize as usize;
+        let end_of_stack = self.stack_pos + (stacksize_as_bytes - size_of_stacktype) as StackType;
+
+        if configCHECK_FOR_STACK_OVERFLOW!() == 1 {
+            /* Is the currently saved stack pointer within the stack limit? */
+            if portSTACK_GROWTH < 0 {
+                self.top_of_stack <= self.stack_pos
+            } else {
+                self.top_of_stack >= end_of_stack
+            }
+        } else {
+            let stack =
+                unsafe { std::slice::from_raw_parts(self.stack_pos as *const u8, stacksize_as_bytes) };
+            let check_bytes = if portSTACK_GROWTH < 0 {
+                &stack[..taskSTACK_OVERFLOW_CHECK_BYTES]
+            } else {
+                &stack[stacksize_as_bytes - taskSTACK_OVERFLOW_CHECK_BYTES..]
+            };
+            check_bytes.iter().any(|byte| *byte != tskSTACK_FILL_BYTE)
+        }
+    }
 }
 
 impl PartialEq for TCB {
@@ -369,11 +715,86 @@ extern "C" fn run_wrapper(func_to_run: CVoidPointer) {
         "Run_wrapper: The function is at position: {:X}",
         func_to_run as u64
     );
-    unsafe {
-        let func_to_run = Box::from_raw(func_to_run as *mut Box<FnBox() + 'static>);
-        func_to_run();
-        // TODO: Delete this wrapper task.
+    #[cfg(feature = "portSTACK_GUARD_PAGE")]
+    {
+        /* The signal stack sits right above the task stack. */
+        let current_task = get_current_task_handle!();
+        let tcb = get_tcb_from_handle!(current_task);
+        let stack_top =
+            tcb.stack_pos as usize + tcb.task_stacksize as usize * mem::size_of::<StackType>();
+        stack_guard::install_signal_stack(stack_top);
+    }
+
+    /* From now on the closure belongs to this thread, so delete_tcb() must
+    not free it. */
+    taskENTER_CRITICAL!();
+    {
+        let current_task = get_current_task_handle!();
+        get_tcb_from_handle_mut!(current_task).task_function = 0;
+    }
+    taskEXIT_CRITICAL!();
+
+    let func_to_run = unsafe { Box::from_raw(func_to_run as *mut Box<dyn FnOnce() + 'static>) };
+
+    /* The port unwinds the thread of a task that is deleted while it is
+    switched out.  The unwinding ends here, and the thread returns to the
+    port to exit. */
+    match panic::catch_unwind(AssertUnwindSafe(move || run_task(func_to_run))) {
+        Err(ref payload) if payload.is::<port::TaskDeleted>() => {}
+        Err(payload) => panic::resume_unwind(payload),
+        Ok(()) => {}
+    }
+}
+
+/* Run the task function, then deal with its panics and its return. */
+fn run_task(func_to_run: Box<Box<dyn FnOnce() + 'static>>) {
+    let mut result = panic::catch_unwind(AssertUnwindSafe(move || func_to_run()));
+
+    /* A panic must not unwind into the port, which would take the whole
+    simulator down, so it ends here. */
+    while let Err(payload) = result {
+        /* The task was deleted, see run_wrapper(). */
+        if payload.is::<port::TaskDeleted>() {
+            panic::resume_unwind(payload);
+        }
+
+        let current_task = get_current_task_handle!();
+        let policy = get_tcb_from_handle!(current_task).panic_policy.clone();
+        kernel::call_task_panic_hook(current_task, &*payload);
+
+        result = match policy {
+            PanicPolicy::Delete => Ok(()),
+            #[cfg(feature = "INCLUDE_vTaskSuspend")]
+            PanicPolicy::Suspend => {
+                /* Do not hold CURRENT_TCB for the whole call, the task is
+                switched out before suspend_task() returns. */
+                let current_task = get_current_task_handle!();
+                suspend_task(current_task);
+                Ok(())
+            }
+            PanicPolicy::Restart(entry) => panic::catch_unwind(AssertUnwindSafe(|| entry())),
+            PanicPolicy::AbortScheduler => {
+                kernel::task_end_scheduler();
+                /* A port may return here, so keep the thread off the host's
+                processor for good. */
+                loop {
+                    thread::park();
+                }
+            }
+        };
     }
+
+    /* FreeRTOS does not allow a task to return.  Here the closure has been
+    consumed by now, so the task deletes itself and the idle task frees the
+    rest. */
+    #[cfg(feature = "INCLUDE_vTaskDelete")]
+    task_delete(None);
+
+    #[cfg(not(feature = "INCLUDE_vTaskDelete"))]
+    panic!(
+        "Task {} returned, which requires INCLUDE_vTaskDelete.",
+        get_current_task_handle!().get_name()
+    );
 }
 
 // * Record the Highest ready priority
@@ -424,7 +845,7 @@ pub fn initialize_task_list () {
 ///
 /// * Implemented by: Fan Jinhao
 ///
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct TaskHandle(Arc<RwLock<TCB>>);
 
 impl PartialEq for TaskHandle {
@@ -454,6 +875,11 @@ impl TaskHandle {
         TaskHandle(arc)
     }
 
+    /* Whether both handles refer to the same task, without locking either. */
+    pub(crate) fn ptr_eq(&self, other: &TaskHandle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     /// Construct a TaskHandle with a TCB. */
     /// * Implemented by: Fan Jinhao.
     /// * C implementation:
@@ -536,7 +962,7 @@ impl TaskHandle {
     ///
     /// TODO
     fn add_new_task_to_ready_list(&self) -> Result<(), FreeRtosError> {
-        let unwrapped_tcb = get_tcb_from_handle!(self);
+        let task_priority = self.get_priority();
 
         taskENTER_CRITICAL!();
         {
@@ -552,7 +978,7 @@ impl TaskHandle {
             } else {
                 let unwrapped_cur = get_current_task_handle!();
                 if !get_scheduler_running!() {
-                    if unwrapped_cur.get_priority() <= unwrapped_tcb.task_priority {
+                    if unwrapped_cur.get_priority() <= task_priority {
                         /* If the scheduler is not already running, make this task the
                         current task if it is the highest priority task to be created
                         so far. */
@@ -563,13 +989,19 @@ impl TaskHandle {
                 }
             }
             set_task_number!(get_task_number!() + 1);
+
+            #[cfg(feature = "configUSE_TRACE_FACILITY")]
+            {
+                /* Add a counter into the TCB for tracing only. */
+                get_tcb_from_handle_mut!(self).task_number = get_task_number!();
+            }
             traceTASK_CREATE!(self.clone());
             self.add_task_to_ready_list()?;
         }
         taskEXIT_CRITICAL!();
         if get_scheduler_running!() {
             let current_task_priority = get_current_task_handle!().get_priority();
-            if current_task_priority < unwrapped_tcb.task_priority {
+            if current_task_priority < task_priority {
                 taskYIELD_IF_USING_PREEMPTION!();
             } else {
                 mtCOVERAGE_TEST_MARKER!();
@@ -593,13 +1025,20 @@ impl TaskHandle {
         get_tcb_from_handle!(self).get_name()
     }
 
+    /* The name of the task, or None if its TCB is locked.  Never blocks or
+    panics, for heap_accounting, which runs inside the allocator. */
+    #[cfg(feature = "portHEAP_ACCOUNTING")]
+    pub(crate) fn try_get_name(&self) -> Option<String> {
+        self.0.try_read().ok().map(|tcb| tcb.get_name())
+    }
+
     #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-    pub fn get_run_time(&self) -> TickType {
+    pub fn get_run_time(&self) -> u64 {
         get_tcb_from_handle!(self).get_run_time()
     }
 
     #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-    pub fn set_run_time(&self, next_val: TickType) -> TickType {
+    pub fn set_run_time(&self, next_val: u64) -> u64 {
         get_tcb_from_handle_mut!(self).set_run_time(next_val)
     }
 
@@ -623,6 +1062,26 @@ impl TaskHandle {
         get_tcb_from_handle_mut!(self).set_mutex_held_count(new_count)
     }
 
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn get_notified_value(&self) -> u32 {
+        get_tcb_from_handle!(self).get_notified_value()
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn set_notified_value(&self, new_val: u32) {
+        get_tcb_from_handle_mut!(self).set_notified_value(new_val)
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn get_notify_state(&self) -> u8 {
+        get_tcb_from_handle!(self).get_notify_state()
+    }
+
+    #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+    pub fn set_notify_state(&self, new_state: u8) {
+        get_tcb_from_handle_mut!(self).set_notify_state(new_state)
+    }
+
     pub fn get_base_priority(&self) -> UBaseType {
         get_tcb_from_handle!(self).get_base_priority()
     }
@@ -630,6 +1089,65 @@ impl TaskHandle {
     pub fn set_base_priority(&self, new_val: UBaseType) {
         get_tcb_from_handle_mut!(self).set_base_priority(new_val)
     }
+
+    #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+    pub fn get_task_tag(&self) -> Option<TaskHookFunction> {
+        get_tcb_from_handle!(self).get_task_tag()
+    }
+
+    #[cfg(feature = "configUSE_APPLICATION_TASK_TAG")]
+    pub fn set_task_tag(&self, new_tag: Option<TaskHookFunction>) {
+        get_tcb_from_handle_mut!(self).set_task_tag(new_tag)
+    }
+
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    pub fn get_task_number(&self) -> UBaseType {
+        get_tcb_from_handle!(self).get_task_number()
+    }
+
+    #[cfg(any(
+        feature = "configUSE_TRACE_FACILITY",
+        feature = "INCLUDE_uxTaskGetStackHighWaterMark"
+    ))]
+    pub(crate) fn check_free_stack_space(&self) -> UBaseType {
+        get_tcb_from_handle!(self).check_free_stack_space()
+    }
+
+    #[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+    pub(crate) fn stack_overflowed(&self) -> bool {
+        get_tcb_from_handle!(self).stack_overflowed()
+    }
+
+    #[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+    pub(crate) fn set_top_of_stack(&self, top_of_stack: StackType) {
+        get_tcb_from_handle_mut!(self).top_of_stack = top_of_stack;
+    }
+
+    /// * Descrpition:
+    ///  Returns the high water mark of the stack of the task, that is the
+    ///  minimum free stack space there has been (in words, so on a 64 bit
+    ///  machine a value of 1 means 8 bytes) since the task started.  The
+    ///  smaller the returned number the closer the task has come to
+    ///  overflowing its stack, which makes it a good basis for choosing the
+    ///  `stacksize()` of the task.
+    ///
+    ///  The value is worked out from the bytes of the stack that still hold
+    ///  the fill pattern written when the task was created.
+    ///
+    ///  INCLUDE_uxTaskGetStackHighWaterMark must be defined as 1 for this
+    ///  function to be available.
+    ///
+    /// * C implementation: tasks.c 3553-3574
+    ///
+    /// # Arguments:
+    ///
+    /// # Return:
+    ///  The smallest amount of free stack space there has been, in words.
+    ///
+    #[cfg(feature = "INCLUDE_uxTaskGetStackHighWaterMark")]
+    pub fn stack_high_water_mark(&self) -> UBaseType {
+        self.check_free_stack_space()
+    }
 }
 
 #[macro_export]
@@ -706,7 +1224,7 @@ pub fn add_current_task_to_delayed_list(ticks_to_wait: TickType, can_block_indef
             /* Calculate the time at which the task should be woken if the event
             does not occur.  This may overflow but this doesn't matter, the
             kernel will manage it correctly. */
-            let time_to_wake = get_tick_count!() + ticks_to_wait;
+            let time_to_wake = get_tick_count!().wrapping_add(ticks_to_wait);
 
             /* The list item will be inserted in wake time order. */
             let cur_state_list_item = unwrapped_cur.get_state_list_item();
@@ -738,7 +1256,7 @@ pub fn add_current_task_to_delayed_list(ticks_to_wait: TickType, can_block_indef
         /* Calculate the time at which the task should be woken if the event
         does not occur.  This may overflow but this doesn't matter, the kernel
         will manage it correctly. */
-        let time_to_wake = get_tick_count!() + ticks_to_wait;
+        let time_to_wake = get_tick_count!().wrapping_add(ticks_to_wait);
 
         let cur_state_list_item = unwrapped_cur.get_state_list_item();
         /* The list item will be inserted in wake time order. */
@@ -826,6 +1344,10 @@ pub fn task_delete(task_to_delete: Option<TaskHandle>) {
     being deleted. */
     let pxtcb = get_handle_from_option!(task_to_delete);
 
+    /* Keep the scheduler suspended until the tasks waiting for this task to
+    end are woken, otherwise a tick taken as the critical section is left
+    could switch away from a task deleting itself before it wakes them. */
+    task_suspend_all();
     taskENTER_CRITICAL!();
     {
         /* Remove task from the ready list. */
@@ -869,12 +1391,7 @@ pub fn task_delete(task_to_delete: Option<TaskHandle>) {
             portPRE_TASK_DELETE_HOOK!(pxtcb, get_yield_pending!());
         } else {
             set_current_number_of_tasks!(get_current_number_of_tasks!() - 1);
-
-            let stack_pos = get_tcb_from_handle!(pxtcb).stack_pos;
-            /* This call is required specifically for the TriCore port.  It must be
-            above the vPortFree() calls.  The call is also used by ports/demos that
-            want to allocate and clean RAM statically. */
-            port::port_free(stack_pos as *mut _);
+            delete_tcb(pxtcb.clone());
 
             /* Reset the next expected unblock time in case it referred to
             the task that has just been deleted. */
@@ -885,9 +1402,16 @@ pub fn task_delete(task_to_delete: Option<TaskHandle>) {
     }
     taskEXIT_CRITICAL!();
 
+    /* Wake the tasks waiting for this task to end.  The task is no longer on
+    a ready list, so if one of them preempts a task deleting itself, it never
+    runs again. */
+    let exit_event = Arc::clone(&get_tcb_from_handle!(pxtcb).exit_event);
+    exit_event.set_bits(taskEXITED_BIT);
+    task_resume_all().unwrap();
+
     /* Force a reschedule if it is the currently running task that has just
     been deleted. */
-    if get_scheduler_suspended!() > 0 {
+    if get_scheduler_running!() {
         if pxtcb == get_current_task_handle!() {
             assert!(get_scheduler_suspended!() == 0);
             portYIELD_WITHIN_API!();
@@ -897,6 +1421,63 @@ pub fn task_delete(task_to_delete: Option<TaskHandle>) {
     }
 }
 
+/// * Descrpition:
+///  Free the memory the scheduler allocated for a deleted task: its stack and,
+///  if the task never ran, its closure.  The TCB itself is freed once the last
+///  `TaskHandle` referring to it is dropped.
+///
+///  The closure of a task that has already run belongs to the thread of the
+///  task.  The POSIX port wakes the thread, which unwinds, dropping whatever
+///  the closure still owns, and exits.  Only then does the port free the
+///  stack.
+///
+/// * C implementation: tasks.c 3581-3627
+///
+/// # Arguments:
+///  `pxtcb` The task to free.  It must have been removed from all lists.
+///
+/// # Return:
+///
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+pub(crate) fn delete_tcb(pxtcb: TaskHandle) {
+    /* The thread of the task may still be unwinding on its stack, so the
+    port frees the stack once nothing runs on it.  A stack given by the
+    application is left to it. */
+    {
+        let tcb = get_tcb_from_handle!(pxtcb);
+        #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+        let free_stack = !tcb.stack_given;
+        #[cfg(not(feature = "configSUPPORT_STATIC_ALLOCATION"))]
+        let free_stack = true;
+        let stack_pos = tcb.stack_pos;
+        drop(tcb);
+
+        #[cfg(not(feature = "portSTACK_GUARD_PAGE"))]
+        let free_fn: fn(CVoidPointer) = port::port_free;
+        #[cfg(feature = "portSTACK_GUARD_PAGE")]
+        let free_fn: fn(CVoidPointer) = stack_guard::free_stack;
+
+        if free_stack {
+            port::port_free_task_stack(&pxtcb, stack_pos as CVoidPointer, free_fn);
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+
+    portCLEAN_UP_TCB!(&pxtcb);
+    let mut tcb = get_tcb_from_handle_mut!(pxtcb);
+
+    if tcb.task_function != 0 {
+        /* The task was deleted before run_wrapper() took over its closure. */
+        unsafe {
+            drop(Box::from_raw(tcb.task_function as *mut Box<dyn FnOnce()>));
+        }
+        tcb.task_function = 0;
+    } else {
+        mtCOVERAGE_TEST_MARKER!();
+    }
+}
+
 ///  INCLUDE_vTaskSuspend must be defined as 1 for this function to be available.
 ///  See the configuration section for more information.
 ///
@@ -943,6 +1524,20 @@ pub fn suspend_task(task_to_suspend: TaskHandle) {
             mtCOVERAGE_TEST_MARKER!();
         }
         list_insert_end(&SUSPENDED_TASK_LIST, unwrapped_tcb.get_state_list_item());
+        /* Do not keep the TCB locked while the task is suspended, leaving
+        the critical section may already switch to another task. */
+        drop(unwrapped_tcb);
+
+        #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+        {
+            if task_to_suspend.get_notify_state() == taskWAITING_NOTIFICATION {
+                /* The task was blocked to wait for a notification, but is
+                now suspended, so no notification was received.  Otherwise
+                state() would report it as blocked, and a notification
+                would move it to the ready list. */
+                task_to_suspend.set_notify_state(taskNOT_WAITING_NOTIFICATION);
+            }
+        }
     }
     taskEXIT_CRITICAL!();
 
@@ -1064,3 +1659,225 @@ pub fn resume_task(task_to_resume: TaskHandle) {
         mtCOVERAGE_TEST_MARKER!();
     }
 }
+
+/// * Descrpition:
+///  Forces a task to leave the Blocked state, and enter the Ready state,
+///  even if the event the task was in the Blocked state to wait for has not
+///  occurred, and any specified timeout has not expired.
+///
+///  The blocking call of the woken task returns early with an error that
+///  tells an abort from a plain timeout: `FreeRtosError::DelayAborted`, from
+///  the queue and semaphore functions as from task_notify_wait().
+///  task_delay() and task_delay_until() simply return.
+///
+///  INCLUDE_xTaskAbortDelay must be defined as 1 for this function to be available.
+///
+/// * C implementation: tasks.c 2425-2496
+///
+/// # Arguments:
+///  `task` The handle of the task to remove from the Blocked state.
+///
+/// # Return:
+///  `FreeRtosError::NotBlocked` if the task was not in the Blocked state,
+///  otherwise Ok.
+///
+#[cfg(feature = "INCLUDE_xTaskAbortDelay")]
+pub fn task_abort_delay(task: TaskHandle) -> Result<(), FreeRtosError> {
+    let mut ret_val = Err(FreeRtosError::NotBlocked);
+
+    task_suspend_all();
+    {
+        /* A task can only be prematurely removed from the Blocked state if
+        it is actually in the Blocked state. */
+        if task.state() == task_state::blocked {
+            ret_val = Ok(());
+
+            /* Remove the reference to the task from the blocked list.  An
+            interrupt won't touch the state list item because the
+            scheduler is suspended. */
+            list_remove(task.get_state_list_item());
+
+            /* Is the task waiting on an event also?  If so remove it from
+            the event list too.  Interrupts can touch the event list item,
+            even though the scheduler is suspended, so a critical section
+            is used. */
+            taskENTER_CRITICAL!();
+            {
+                if get_list_item_container(&task.get_event_list_item()).is_some() {
+                    list_remove(task.get_event_list_item());
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+
+                /* Unlike the C implementation the flag is also set for
+                tasks that are not waiting on an event list, so that
+                task_notify_wait() can report the abort as well. */
+                task.set_delay_aborted(true);
+            }
+            taskEXIT_CRITICAL!();
+
+            /* Place the unblocked task into the appropriate ready list. */
+            task.add_task_to_ready_list().unwrap();
+
+            /* A task being unblocked cannot cause an immediate context
+            switch if preemption is turned off. */
+            #[cfg(feature = "configUSE_PREEMPTION")]
+            {
+                /* Preemption is on, but a context switch should only be
+                performed if the unblocked task has a priority that is
+                higher than the currently executing task. */
+                if task.get_priority() > get_current_task_handle!().get_priority() {
+                    /* Pend the yield to be performed when the scheduler
+                    is unsuspended. */
+                    set_yield_pending!(true);
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+    task_resume_all().unwrap();
+
+    ret_val
+}
+
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+impl TaskHandle {
+    /// * Descrpition:
+    ///  Wait for the task to end, either because it was deleted or because its
+    ///  closure returned.  A task must not wait for itself.
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` The maximum amount of time to wait for the task to end.
+    ///
+    /// # Return:
+    ///  `Ok(())` once the task has ended, or `Err(FreeRtosError::Timeout)` if
+    ///  it had not ended when the timeout expired.
+    ///
+    pub fn wait_for_exit(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
+        assert!(*self != get_current_task_handle!());
+
+        let exit_event = Arc::clone(&get_tcb_from_handle!(self).exit_event);
+        if exit_event.wait_bits(taskEXITED_BIT, false, false, ticks_to_wait) & taskEXITED_BIT != 0 {
+            Ok(())
+        } else {
+            Err(FreeRtosError::Timeout)
+        }
+    }
+}
+
+/// The result of a task created by TCB::spawn().  Another task blocks on
+/// join() until the task has returned its value.
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+pub struct JoinHandle<R> {
+    task: TaskHandle,
+    /* Written by the task just before it returns. */
+    result: Arc<Mutex<Option<R>>>,
+}
+
+#[cfg(feature = "INCLUDE_vTaskDelete")]
+impl<R> JoinHandle<R> {
+    /// * Descrpition:
+    ///  Wait for the task to end and take the value its closure returned.  A
+    ///  task must not join itself.
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` The maximum amount of time to wait for the task to end.
+    ///
+    /// # Return:
+    ///  The value returned by the task, `Err(FreeRtosError::Timeout)` if the
+    ///  task had not ended when the timeout expired, or
+    ///  `Err(FreeRtosError::TaskNotFound)` if the task was deleted before it
+    ///  returned, or its value was already taken by an earlier call.
+    ///
+    pub fn join(&mut self, ticks_to_wait: TickType) -> Result<R, FreeRtosError> {
+        self.task.wait_for_exit(ticks_to_wait)?;
+        self.result
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(FreeRtosError::TaskNotFound)
+    }
+}
+
+#[cfg(any(feature = "INCLUDE_eTaskGetState", feature = "INCLUDE_xTaskAbortDelay"))]
+impl TaskHandle {
+    /// * Descrpition:
+    ///  Obtain the state of the task.  The state is worked out from the kernel
+    ///  list that references the state list item of the task.
+    ///
+    ///  INCLUDE_eTaskGetState must be defined as 1 for this function to be available.
+    ///
+    /// * C implementation: tasks.c 1269-1333
+    ///
+    /// # Arguments:
+    ///
+    /// # Return:
+    ///  The state of the task at the time the function was called.  A task
+    ///  that waits for an event or a notification without a timeout sits in
+    ///  the suspended list, but is reported as blocked.
+    ///
+    pub fn state(&self) -> task_state {
+        if *self == get_current_task_handle!() {
+            /* The task calling this function is querying its own state. */
+            return task_state::running;
+        }
+
+        let state_list: Option<ListLink>;
+        taskENTER_CRITICAL!();
+        {
+            state_list = get_list_item_container(&self.get_state_list_item());
+        }
+        taskEXIT_CRITICAL!();
+
+        let is_state_list = |list: &ListLink| match state_list {
+            Some(ref state_list) => Arc::ptr_eq(state_list, list),
+            None => false,
+        };
+
+        if is_state_list(&DELAYED_TASK_LIST) || is_state_list(&OVERFLOW_DELAYED_TASK_LIST) {
+            /* The task being queried is referenced from one of the Blocked
+            lists. */
+            return task_state::blocked;
+        }
+
+        #[cfg(feature = "INCLUDE_vTaskSuspend")]
+        {
+            if is_state_list(&SUSPENDED_TASK_LIST) {
+                /* The task being queried is referenced from the suspended
+                list.  Is it genuinely suspended or is it block
+                indefinitely? */
+                if get_list_item_container(&self.get_event_list_item()).is_some() {
+                    return task_state::blocked;
+                }
+
+                /* A task waiting for a notification without a timeout is
+                not on an event list either. */
+                #[cfg(feature = "configUSE_TASK_NOTIFICATIONS")]
+                {
+                    if self.get_notify_state() == taskWAITING_NOTIFICATION {
+                        return task_state::blocked;
+                    }
+                }
+
+                return task_state::suspended;
+            }
+        }
+
+        #[cfg(feature = "INCLUDE_vTaskDelete")]
+        {
+            if is_state_list(&TASKS_WAITING_TERMINATION) || state_list.is_none() {
+                /* The task being queried is referenced from the deleted
+                tasks list, or it is not referenced from any lists at
+                all. */
+                return task_state::deleted;
+            }
+        }
+
+        /* If the task is not in any other state, it must be in the
+        Ready (including pending ready) state. */
+        task_state::ready
+    }
+}
@@ -1,8 +1,10 @@
 use crate::list::ListLink;
 use crate::port::{BaseType, TickType, UBaseType};
+use crate::sync::{Mutex, RwLock};
 use crate::task_control::TaskHandle;
 use crate::*;
-use std::sync::RwLock;
+use std::any::Any;
+use std::sync::Arc;
 
 /* Some global variables. */
 pub static mut TICK_COUNT: TickType = 0;
@@ -43,6 +45,59 @@ lazy_static! {
     pub static ref PENDING_READY_LIST: ListLink = Default::default();
 }
 
+lazy_static! {
+    /* Holds the handle of the idle task.  The idle task is created automatically
+     * when the scheduler is started.
+     */
+    pub static ref IDLE_TASK_HANDLE: RwLock<Option<TaskHandle>> = RwLock::new(None);
+}
+
+/* The stack given by kernel::set_idle_task_stack(), taken when the idle task
+ * is created.
+ */
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+lazy_static! {
+    pub static ref IDLE_TASK_STACK: Mutex<Option<&'static mut [crate::port::StackType]>> = Mutex::new(None);
+}
+
+/* Hooks registered by the application, see kernel::set_idle_hook() and friends.
+ * They are only locked inside critical sections (or from the tick interrupt,
+ * which cannot preempt a critical section), so the lock is never contended.
+ */
+pub type ApplicationHook = Option<Arc<dyn Fn() + Send + Sync>>;
+
+#[cfg(feature = "configUSE_IDLE_HOOK")]
+lazy_static! {
+    pub static ref IDLE_HOOK: Mutex<ApplicationHook> = Mutex::new(None);
+}
+
+#[cfg(feature = "configUSE_TICK_HOOK")]
+lazy_static! {
+    pub static ref TICK_HOOK: Mutex<ApplicationHook> = Mutex::new(None);
+}
+
+#[cfg(feature = "configUSE_MALLOC_FAILED_HOOK")]
+lazy_static! {
+    pub static ref MALLOC_FAILED_HOOK: Mutex<ApplicationHook> = Mutex::new(None);
+}
+
+/* Unlike the other hooks, the stack overflow hook is told which task overflowed
+its stack. */
+pub type StackOverflowHook = Option<Arc<dyn Fn(TaskHandle) + Send + Sync>>;
+
+#[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+lazy_static! {
+    pub static ref STACK_OVERFLOW_HOOK: Mutex<StackOverflowHook> = Mutex::new(None);
+}
+
+/* The task panic hook is told which task panicked, and is given the payload of
+the panic. */
+pub type TaskPanicHook = Option<Arc<dyn Fn(TaskHandle, &(dyn Any + Send)) + Send + Sync>>;
+
+lazy_static! {
+    pub static ref TASK_PANIC_HOOK: Mutex<TaskPanicHook> = Mutex::new(None);
+}
+
 #[cfg(feature = "INCLUDE_vTaskDelete")]
 lazy_static! {
     // Tasks that have been deleted - but their memory not yet freed.
@@ -64,11 +119,11 @@ pub static mut SCHEDULER_SUSPENDED: UBaseType = 0;
 
 /*< Holds the value of a timer/counter the last time a task was switched in. */
 #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-pub static mut TASK_SWITCHED_IN_TIME: u32 = 0;
+pub static mut TASK_SWITCHED_IN_TIME: u64 = 0;
 
 /*< Holds the total amount of execution time as defined by the run time counter clock. */
 #[cfg(feature = "configGENERATE_RUN_TIME_STATS")]
-pub static mut TOTAL_RUN_TIME: u32 = 0;
+pub static mut TOTAL_RUN_TIME: u64 = 0;
 
 #[cfg(feature = "INCLUDE_vTaskDelete")]
 pub static mut DELETED_TASKS_WAITING_CLEAN_UP: UBaseType = 0;
@@ -343,10 +398,18 @@ macro_rules! get_current_task_delay_aborted {
 }
 /* ---------- End of global variable setters and getters -----------*/
 
+/* Call the stack overflow hook if the stack of the task being swapped out is
+currently overflowed, or looks like it might have overflowed in the past. */
 #[macro_export]
 macro_rules! taskCHECK_FOR_STACK_OVERFLOW {
     () => {
-        // This macro does nothing.
+        #[cfg(feature = "configCHECK_FOR_STACK_OVERFLOW")]
+        {
+            let current_task = get_current_task_handle!();
+            if current_task.stack_overflowed() {
+                crate::kernel::call_stack_overflow_hook(current_task);
+            }
+        }
     };
 }
 
@@ -355,13 +418,18 @@ macro_rules! switch_delayed_lists {
     () => {
         /* pxDelayedTaskList and pxOverflowDelayedTaskList are switched when the tick
         count overflows. */
-        // TODO: tasks.c 239
-        unsafe {
+        assert!(crate::list::list_is_empty(&DELAYED_TASK_LIST));
+        {
             let mut delayed = DELAYED_TASK_LIST.write().unwrap();
             let mut overflowed = OVERFLOW_DELAYED_TASK_LIST.write().unwrap();
-            let tmp = (*delayed).clone();
-            *delayed = (*overflowed).clone();
-            *overflowed = tmp;
+            std::mem::swap(&mut *delayed, &mut *overflowed);
+        }
+        /* Only the list contents were swapped, so the items still record the
+        overflow list as their container. */
+        for item in crate::list::get_list_items(&DELAYED_TASK_LIST) {
+            item.write().unwrap().set_container(&DELAYED_TASK_LIST);
         }
+        set_num_of_overflows!(get_num_of_overflows!() + 1);
+        crate::task_control::reset_next_task_unblock_time();
     };
 }
@@ -0,0 +1,465 @@
+// task_notify.rs - Direct to task notifications.
+// Functions defined in this file are adapted from tasks.c.
+//
+// Each task has a 32-bit notification value and a notification state.  A
+// notification sent to a task updates its value and, if the task was blocked
+// waiting for a notification, moves it straight back to the ready list.  This
+// makes notifications a lighter alternative to binary semaphores, counting
+// semaphores and event groups when there is a single receiving task.
+use crate::list;
+use crate::port::*;
+use crate::projdefs::{pdFALSE, FreeRtosError};
+use crate::task_control::*;
+use crate::task_global::*;
+use crate::*;
+
+/// Actions that can be performed when a task is notified.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NotifyAction {
+    /// Notify the task without updating its notify value.
+    NoAction,
+    /// Set bits in the task's notification value.
+    SetBits,
+    /// Increment the task's notification value.
+    Increment,
+    /// Set the task's notification value to a specific value even if the
+    /// previous value has not yet been read by the task.
+    SetValueWithOverwrite,
+    /// Set the task's notification value if the previous value has been read
+    /// by the task.
+    SetValueWithoutOverwrite,
+}
+
+impl TaskHandle {
+    /// # Description
+    /// Send a notification to the task, updating its notification value
+    /// according to `action`.  If the task was blocked waiting for a
+    /// notification it is unblocked.
+    ///
+    /// * C implementation: tasks.c 4343-4444
+    ///
+    /// # Arguments:
+    /// `value` - Data that can be sent with the notification.  How the data is
+    /// used depends on the value of `action`.
+    ///
+    /// `action` - Specifies how the notification updates the task's
+    /// notification value, if at all.
+    ///
+    /// # Return:
+    /// `FreeRtosError::NotificationPending` if `action` is
+    /// `SetValueWithoutOverwrite` and the task already had a notification
+    /// pending (the value is not updated in that case), Ok otherwise.
+    pub fn notify(&self, value: u32, action: NotifyAction) -> Result<(), FreeRtosError> {
+        let mut ret_val = Ok(());
+        let mut yield_required = false;
+
+        taskENTER_CRITICAL!();
+        {
+            let original_notify_state = self.get_notify_state();
+            self.set_notify_state(taskNOTIFICATION_RECEIVED);
+
+            if !self.update_notified_value(value, action, original_notify_state) {
+                /* The value could not be written to the task. */
+                ret_val = Err(FreeRtosError::NotificationPending);
+            }
+
+            traceTASK_NOTIFY!();
+
+            /* If the task is in the blocked state specifically to wait for a
+            notification then unblock it now. */
+            if original_notify_state == taskWAITING_NOTIFICATION {
+                list::list_remove(self.get_state_list_item());
+                self.add_task_to_ready_list().unwrap();
+
+                /* The task should not have been on an event list. */
+                assert!(list::get_list_item_container(&self.get_event_list_item()).is_none());
+
+                {
+                    #![cfg(feature = "configUSE_TICKLESS_IDLE")]
+                    /* If a task is blocked waiting for a notification then
+                    the next task unblock time might be set to the blocked
+                    task's time out time.  Reset it here to ensure it is updated
+                    at the earliest possible time. */
+                    reset_next_task_unblock_time();
+                }
+
+                if self.get_priority() > get_current_task_priority!() {
+                    /* The notified task has a priority above the currently
+                    executing task so a yield is required. */
+                    yield_required = true;
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        }
+        taskEXIT_CRITICAL!();
+
+        if yield_required {
+            taskYIELD_IF_USING_PREEMPTION!();
+        }
+
+        ret_val
+    }
+
+    /// # Description
+    /// Notify the task by incrementing its notification value, which makes
+    /// the notification behave like giving a counting semaphore.  Use
+    /// `task_notify_take()` in the receiving task.
+    ///
+    /// * C implementation: task.h 1925
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// Nothing
+    pub fn notify_give(&self) {
+        /* Incrementing the value never fails. */
+        self.notify(0, NotifyAction::Increment).unwrap();
+    }
+
+    /// # Description
+    /// A version of notify() that can be called from an ISR.
+    ///
+    /// * C implementation: tasks.c 4449-4568
+    ///
+    /// # Arguments:
+    /// `value` - Data that can be sent with the notification.
+    ///
+    /// `action` - Specifies how the notification updates the task's
+    /// notification value, if at all.
+    ///
+    /// # Return:
+    /// The first value is the same as the return value of notify().  The
+    /// second value is true if sending the notification unblocked a task with
+    /// a priority higher than the running task, in which case a context switch
+    /// should be requested before the interrupt exits.
+    pub fn notify_from_isr(
+        &self,
+        value: u32,
+        action: NotifyAction,
+    ) -> (Result<(), FreeRtosError>, bool) {
+        let mut ret_val = Ok(());
+        let higher_priority_task_woken;
+
+        let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+        {
+            let original_notify_state = self.get_notify_state();
+            self.set_notify_state(taskNOTIFICATION_RECEIVED);
+
+            if !self.update_notified_value(value, action, original_notify_state) {
+                /* The value could not be written to the task. */
+                ret_val = Err(FreeRtosError::NotificationPending);
+            }
+
+            traceTASK_NOTIFY_FROM_ISR!();
+
+            higher_priority_task_woken = self.unblock_from_isr(original_notify_state);
+        }
+        portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+
+        (ret_val, higher_priority_task_woken)
+    }
+
+    /// # Description
+    /// A version of notify_give() that can be called from an ISR.
+    ///
+    /// * C implementation: tasks.c 4573-4657
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// true if giving the notification unblocked a task with a priority higher
+    /// than the running task, in which case a context switch should be
+    /// requested before the interrupt exits.
+    pub fn notify_give_from_isr(&self) -> bool {
+        let higher_priority_task_woken;
+
+        let saved_interrupt_status = portSET_INTERRUPT_MASK_FROM_ISR!();
+        {
+            let original_notify_state = self.get_notify_state();
+            self.set_notify_state(taskNOTIFICATION_RECEIVED);
+
+            /* 'Giving' is equivalent to incrementing a count in a counting
+            semaphore. */
+            self.set_notified_value(self.get_notified_value().wrapping_add(1));
+
+            traceTASK_NOTIFY_GIVE_FROM_ISR!();
+
+            higher_priority_task_woken = self.unblock_from_isr(original_notify_state);
+        }
+        portCLEAR_INTERRUPT_MASK_FROM_ISR!(saved_interrupt_status);
+
+        higher_priority_task_woken
+    }
+
+    /// # Description
+    /// Clear a pending notification of the task, without changing its
+    /// notification value.
+    ///
+    /// * C implementation: tasks.c 4662-4686
+    ///
+    /// # Arguments:
+    /// Nothing
+    ///
+    /// # Return:
+    /// true if the task had a notification pending (and it was cleared),
+    /// otherwise false.
+    pub fn notify_state_clear(&self) -> bool {
+        let ret_val;
+
+        taskENTER_CRITICAL!();
+        {
+            if self.get_notify_state() == taskNOTIFICATION_RECEIVED {
+                self.set_notify_state(taskNOT_WAITING_NOTIFICATION);
+                ret_val = true;
+            } else {
+                ret_val = false;
+            }
+        }
+        taskEXIT_CRITICAL!();
+
+        ret_val
+    }
+
+    /* Apply `action` to the notification value of the task.  Returns false if
+    the value could not be written.  Must be called with interrupts masked. */
+    fn update_notified_value(
+        &self,
+        value: u32,
+        action: NotifyAction,
+        original_notify_state: u8,
+    ) -> bool {
+        match action {
+            NotifyAction::SetBits => {
+                self.set_notified_value(self.get_notified_value() | value);
+            }
+            NotifyAction::Increment => {
+                self.set_notified_value(self.get_notified_value().wrapping_add(1));
+            }
+            NotifyAction::SetValueWithOverwrite => {
+                self.set_notified_value(value);
+            }
+            NotifyAction::SetValueWithoutOverwrite => {
+                if original_notify_state != taskNOTIFICATION_RECEIVED {
+                    self.set_notified_value(value);
+                } else {
+                    return false;
+                }
+            }
+            NotifyAction::NoAction => {
+                /* The task is being notified without its notify value being
+                updated. */
+            }
+        }
+        true
+    }
+
+    /* Unblock the task from an ISR if it was waiting for a notification.
+    Returns true if a context switch is required. */
+    fn unblock_from_isr(&self, original_notify_state: u8) -> bool {
+        /* If the task is in the blocked state specifically to wait for a
+        notification then unblock it now. */
+        if original_notify_state != taskWAITING_NOTIFICATION {
+            return false;
+        }
+
+        /* The task should not have been on an event list. */
+        assert!(list::get_list_item_container(&self.get_event_list_item()).is_none());
+
+        if get_scheduler_suspended!() == pdFALSE as UBaseType {
+            list::list_remove(self.get_state_list_item());
+            self.add_task_to_ready_list().unwrap();
+        } else {
+            /* The delayed and ready lists cannot be accessed, so hold
+            this task pending until the scheduler is resumed. */
+            list::list_insert_end(&PENDING_READY_LIST, self.get_event_list_item());
+        }
+
+        if self.get_priority() > get_current_task_priority!() {
+            /* Mark that a yield is pending in case the user is not
+            using the "higher priority task woken" return value of an ISR
+            safe FreeRTOS function. */
+            set_yield_pending!(true);
+            true
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+            false
+        }
+    }
+}
+
+/// # Description
+/// Wait for a notification, treating the notification value of the calling
+/// task like the count of a counting semaphore (or a binary semaphore if
+/// `clear_count_on_exit` is true).
+///
+/// * C implementation: tasks.c 4195-4257
+///
+/// # Arguments:
+/// `clear_count_on_exit` - If true the notification value is cleared to zero
+/// on exit, otherwise it is decremented.
+///
+/// `ticks_to_wait` - The maximum amount of time (in ticks) the calling task
+/// should remain in the Blocked state to wait for the notification value to
+/// become non-zero.
+///
+/// # Return:
+/// The task's notification value before it is cleared or decremented.  0 if
+/// the call timed out.
+pub fn task_notify_take(clear_count_on_exit: bool, ticks_to_wait: TickType) -> u32 {
+    let current_task = get_current_task_handle!();
+    let mut should_block = false;
+    let ret_val;
+
+    taskENTER_CRITICAL!();
+    {
+        /* Only block if the notification count is not already non-zero. */
+        if current_task.get_notified_value() == 0 {
+            /* Mark this task as waiting for a notification. */
+            current_task.set_notify_state(taskWAITING_NOTIFICATION);
+
+            if ticks_to_wait > 0 {
+                add_current_task_to_delayed_list(ticks_to_wait, true);
+                traceTASK_NOTIFY_TAKE_BLOCK!();
+                should_block = true;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+    taskEXIT_CRITICAL!();
+
+    if should_block {
+        portYIELD_WITHIN_API!();
+    }
+
+    taskENTER_CRITICAL!();
+    {
+        traceTASK_NOTIFY_TAKE!();
+        ret_val = current_task.get_notified_value();
+
+        if ret_val != 0 {
+            if clear_count_on_exit {
+                current_task.set_notified_value(0);
+            } else {
+                current_task.set_notified_value(ret_val - 1);
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        current_task.set_notify_state(taskNOT_WAITING_NOTIFICATION);
+    }
+    taskEXIT_CRITICAL!();
+
+    ret_val
+}
+
+/// # Description
+/// Wait for a notification to be pending on the calling task, optionally
+/// clearing bits of its notification value on entry and on exit.
+///
+/// * C implementation: tasks.c 4263-4338
+///
+/// # Arguments:
+/// `bits_to_clear_on_entry` - Bits that are cleared in the notification
+/// value before waiting, if no notification is pending on entry.
+///
+/// `bits_to_clear_on_exit` - Bits that are cleared in the notification value
+/// before returning, if a notification was received.
+///
+/// `ticks_to_wait` - The maximum amount of time (in ticks) to wait for a
+/// notification.
+///
+/// # Return:
+/// `Ok(value)` with the notification value (before the bits in
+/// `bits_to_clear_on_exit` are cleared) if a notification was received, or
+/// `Err(FreeRtosError::Timeout)` if the call timed out, or
+/// `Err(FreeRtosError::DelayAborted)` if the wait was cut short by
+/// task_abort_delay().
+pub fn task_notify_wait(
+    bits_to_clear_on_entry: u32,
+    bits_to_clear_on_exit: u32,
+    ticks_to_wait: TickType,
+) -> Result<u32, FreeRtosError> {
+    let current_task = get_current_task_handle!();
+    let mut should_block = false;
+    let ret_val;
+
+    taskENTER_CRITICAL!();
+    {
+        /* Only block if a notification is not already pending. */
+        if current_task.get_notify_state() != taskNOTIFICATION_RECEIVED {
+            /* Clear bits in the task's notification value as bits may get
+            set by the notifying task or interrupt.  This can be used to
+            clear the value to zero. */
+            current_task
+                .set_notified_value(current_task.get_notified_value() & !bits_to_clear_on_entry);
+
+            /* Mark this task as waiting for a notification. */
+            current_task.set_notify_state(taskWAITING_NOTIFICATION);
+
+            if ticks_to_wait > 0 {
+                add_current_task_to_delayed_list(ticks_to_wait, true);
+                traceTASK_NOTIFY_WAIT_BLOCK!();
+                should_block = true;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+    taskEXIT_CRITICAL!();
+
+    if should_block {
+        portYIELD_WITHIN_API!();
+    }
+
+    taskENTER_CRITICAL!();
+    {
+        traceTASK_NOTIFY_WAIT!();
+
+        /* Output the current notification value, which may or may not
+        have changed. */
+        let notified_value = current_task.get_notified_value();
+
+        /* If the notify state is not taskNOTIFICATION_RECEIVED then the task
+        unblocked because of a timeout, or was suspended while it waited.
+        Otherwise either the task never entered the blocked state (because a
+        notification was already pending) or the task unblocked because of a
+        notification. */
+        if current_task.get_notify_state() != taskNOTIFICATION_RECEIVED {
+            /* A notification was not received.  Either the block time
+            expired or the wait was aborted with task_abort_delay().  The
+            flag is only reset the next time the task blocks, so it is stale
+            unless the task blocked here. */
+            let mut delay_aborted = false;
+            {
+                #![cfg(feature = "INCLUDE_xTaskAbortDelay")]
+                delay_aborted = should_block && current_task.get_delay_aborted();
+            }
+            if delay_aborted {
+                ret_val = Err(FreeRtosError::DelayAborted);
+            } else {
+                ret_val = Err(FreeRtosError::Timeout);
+            }
+        } else {
+            /* A notification was already pending or a notification was
+            received while the task was waiting. */
+            current_task.set_notified_value(notified_value & !bits_to_clear_on_exit);
+            ret_val = Ok(notified_value);
+        }
+
+        current_task.set_notify_state(taskNOT_WAITING_NOTIFICATION);
+    }
+    taskEXIT_CRITICAL!();
+
+    ret_val
+}
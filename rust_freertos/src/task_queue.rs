@@ -1,11 +1,12 @@
 use crate::list;
-use crate::list::ListLink;
+use crate::list::{ItemLink, ListLink};
 use crate::port::*;
 // use crate::kernel::*;
 use crate::projdefs::pdFALSE;
 use crate::task_control::*;
 use crate::task_global::*;
 use crate::*;
+use std::sync::Arc;
 
 /*
  * The item value of the event list item is normally used to hold the priority of
@@ -69,12 +70,31 @@ pub fn task_missed_yield() {
 pub struct time_out {
     overflow_count: BaseType,
     time_on_entering: TickType,
+    delay_aborted: bool,
+}
+
+impl time_out {
+    /// Whether the last call to task_check_for_timeout() reported a timeout
+    /// because task_abort_delay() was called on the waiting task, rather
+    /// than because the block time expired.
+    pub fn delay_aborted(&self) -> bool {
+        self.delay_aborted
+    }
 }
 
 pub fn task_set_time_out_state(pxtimeout: &mut time_out) {
     // assert! ( pxtimeout );
     pxtimeout.overflow_count = get_num_of_overflows!();
     pxtimeout.time_on_entering = get_tick_count!();
+    pxtimeout.delay_aborted = false;
+
+    {
+        #![cfg(feature = "INCLUDE_xTaskAbortDelay")]
+        /* A new wait is starting, so forget an abort that was left over from
+        a previous wait that did not go through task_check_for_timeout(),
+        e.g. task_notify_wait(). */
+        get_current_task_handle!().set_delay_aborted(false);
+    }
 }
 
 pub fn task_check_for_timeout(pxtimeout: &mut time_out, ticks_to_wait: &mut TickType) -> bool {
@@ -103,22 +123,24 @@ pub fn task_check_for_timeout(pxtimeout: &mut time_out, ticks_to_wait: &mut Tick
         }
 
         if cfglock1 && unwrapped_cur.get_delay_aborted() {
+            /* The delay was aborted, which is not the same as a time out,
+            but has the same result. */
             unwrapped_cur.set_delay_aborted(false);
+            pxtimeout.delay_aborted = true;
             xreturn = true;
-        }
-
-        if cfglock2 && *ticks_to_wait == portMAX_DELAY {
+        } else if cfglock2 && *ticks_to_wait == portMAX_DELAY {
+            /* If INCLUDE_vTaskSuspend is set to 1 and the block time
+            specified is the maximum block time then the task should block
+            indefinitely, and therefore never time out. */
             xreturn = false;
-        }
-
-        if get_num_of_overflows!() != pxtimeout.overflow_count
+        } else if get_num_of_overflows!() != pxtimeout.overflow_count
             && const_tick_count >= pxtimeout.time_on_entering
         {
             trace!("IF");
             xreturn = true;
-        } else if const_tick_count - pxtimeout.time_on_entering < *ticks_to_wait {
+        } else if const_tick_count.wrapping_sub(pxtimeout.time_on_entering) < *ticks_to_wait {
             trace!("ELSE IF");
-            *ticks_to_wait -= const_tick_count - pxtimeout.time_on_entering;
+            *ticks_to_wait -= const_tick_count.wrapping_sub(pxtimeout.time_on_entering);
             task_set_time_out_state(pxtimeout);
             xreturn = false;
         } else {
@@ -151,6 +173,120 @@ pub fn task_place_on_event_list(event_list: &ListLink, ticks_to_wait: TickType)
     trace!("ADD SUCCEEDED");
 }
 
+#[cfg(feature = "configUSE_TIMERS")]
+pub fn task_place_on_event_list_restricted(
+    event_list: &ListLink,
+    ticks_to_wait: TickType,
+    wait_indefinitely: bool,
+) {
+    /* This function should not be called by application code hence the
+    'Restricted' in its name.  It is not part of the public API.  It is
+    designed for use by kernel code, and has special calling requirements -
+    it should be called with the scheduler suspended. */
+
+    /* Place the event list item of the TCB in the appropriate event list.
+    In this case it is assume that this is the only task that is going to
+    be waiting on this event list, so the faster list_insert_end() function
+    can be used in place of list_insert(). */
+    let unwrapped_cur = get_current_task_handle!();
+    list::list_insert_end(event_list, unwrapped_cur.get_event_list_item());
+
+    /* If the task should block indefinitely then set the block time to a
+    value that will be recognised as an indefinite delay inside the
+    add_current_task_to_delayed_list() function. */
+    let ticks_to_wait = if wait_indefinitely {
+        portMAX_DELAY
+    } else {
+        ticks_to_wait
+    };
+
+    traceTASK_DELAY_UNTIL!(get_tick_count!() + ticks_to_wait);
+    add_current_task_to_delayed_list(ticks_to_wait, wait_indefinitely);
+}
+
+pub fn task_place_on_unordered_event_list(
+    event_list: &ListLink,
+    item_value: TickType,
+    ticks_to_wait: TickType,
+) {
+    /* THIS FUNCTION MUST BE CALLED WITH THE SCHEDULER SUSPENDED.  It is used by
+    the event groups implementation. */
+    assert!(get_scheduler_suspended!() != pdFALSE as UBaseType);
+
+    /* Store the item value in the event list item.  It is safe to access the
+    event list item here as interrupts won't access the event list item of a
+    task that is not in the Blocked state. */
+    let unwrapped_cur = get_current_task_handle!();
+    list::set_list_item_value(
+        &unwrapped_cur.get_event_list_item(),
+        item_value | taskEVENT_LIST_ITEM_VALUE_IN_USE,
+    );
+
+    /* Place the event list item of the TCB at the end of the appropriate event
+    list.  It is safe to access the event list here because it is part of an
+    event group implementation - and interrupts don't access event groups
+    directly (instead they access them indirectly by pending function calls to
+    the task level). */
+    list::list_insert_end(event_list, unwrapped_cur.get_event_list_item());
+
+    add_current_task_to_delayed_list(ticks_to_wait, true);
+}
+
+pub fn task_remove_from_unordered_event_list(
+    event_list_item: &ItemLink,
+    item_value: TickType,
+) -> bool {
+    /* THIS FUNCTION MUST BE CALLED WITH THE SCHEDULER SUSPENDED.  It is used by
+    the event flags implementation. */
+    assert!(get_scheduler_suspended!() != pdFALSE as UBaseType);
+
+    /* Store the new item value in the event list. */
+    list::set_list_item_value(
+        event_list_item,
+        item_value | taskEVENT_LIST_ITEM_VALUE_IN_USE,
+    );
+
+    /* Remove the event list form the event flag.  Interrupts do not access
+    event flags. */
+    let unblocked_tcb = list::get_list_item_owner(event_list_item);
+    list::list_remove(Arc::clone(event_list_item));
+
+    /* Remove the task from the delayed list and add it to the ready list.  The
+    scheduler is suspended so interrupts will not be accessing the ready
+    lists. */
+    list::list_remove(unblocked_tcb.get_state_list_item());
+    unblocked_tcb.add_task_to_ready_list().unwrap();
+
+    if unblocked_tcb.get_priority() > get_current_task_priority!() {
+        /* Return true if the task removed from the event list has
+        a higher priority than the calling task.  This allows
+        the calling task to know if it should force a context
+        switch now. */
+
+        /* Mark that a yield is pending in case the user is not using the
+        "xHigherPriorityTaskWoken" parameter to an ISR safe FreeRTOS function. */
+        set_yield_pending!(true);
+        true
+    } else {
+        false
+    }
+}
+
+pub fn task_reset_event_item_value() -> TickType {
+    let unwrapped_cur = get_current_task_handle!();
+    let event_list_item = unwrapped_cur.get_event_list_item();
+    let ret_val = list::get_list_item_value(&event_list_item);
+
+    /* Reset the event list item to its normal value - so it can be used with
+    queues and semaphores. */
+    list::set_list_item_value(
+        &event_list_item,
+        configMAX_PRIORITIES!() as TickType - unwrapped_cur.get_priority() as TickType,
+    );
+
+    ret_val
+}
+
 #[cfg(feature = "configUSE_MUTEXES")]
 pub fn task_increment_mutex_held_count() -> Option<TaskHandle> {
     /* If xSemaphoreCreateMutex() is called before any tasks have been created
@@ -212,6 +348,9 @@ pub fn task_priority_inherit(mutex_holder: Option<TaskHandle>) {
                 /* Inherit the priority before being moved into the new list. */
                 task.set_priority(current_task_priority);
                 task.add_task_to_ready_list().unwrap();
+            } else {
+                /* Just inherit the priority. */
+                task.set_priority(current_task_priority);
             }
         } else {
             mtCOVERAGE_TEST_MARKER!();
@@ -22,7 +22,7 @@ use std::mem::*;
 ///  of controlling the frequency of a periodic task as the path taken through the
 ///  code, as well as other task and interrupt activity, will effect the frequency
 ///  at which vTaskDelay() gets called and therefore the time at which the task
-///  next executes.  See vTaskDelayUntil() for an alternative API function designed
+///  next executes.  See task_delay_until() for an alternative API function designed
 ///  to facilitate fixed frequency execution.  It does this by specifying an
 ///  absolute time (rather than a relative time) at which the calling task should
 ///  unblock.
@@ -47,7 +47,7 @@ pub fn task_delay(ticks_to_delay: TickType) {
             add_current_task_to_delayed_list(ticks_to_delay, false);
         }
 
-        already_yielded = task_resume_all();
+        already_yielded = task_resume_all().unwrap();
     } else {
         mtCOVERAGE_TEST_MARKER!();
     }
@@ -58,3 +58,103 @@ pub fn task_delay(ticks_to_delay: TickType) {
         mtCOVERAGE_TEST_MARKER!();
     }
 }
+
+///  Delay a task until a specified time.  This function can be used by periodic
+///  tasks to ensure a constant execution frequency.
+///
+///  This function differs from task_delay() in one important aspect:  task_delay()
+///  will cause a task to block for the specified number of ticks from the time
+///  task_delay() is called.  It is therefore difficult to use task_delay() by
+///  itself to generate a fixed execution frequency as the time between a task
+///  starting to execute and that task calling task_delay() may not be fixed [the
+///  task may take a different path though the code between calls, or may get
+///  interrupted or preempted a different number of times each time it executes].
+///
+///  Whereas task_delay() specifies a wake time relative to the time at which the
+///  function is called, task_delay_until() specifies the absolute (exact) time at
+///  which it wishes to unblock.
+///
+///  INCLUDE_vTaskDelayUntil must be defined as 1 for this function to be available.
+///
+/// * C implementation: tasks.c 1140-1217
+///
+/// # Arguments:
+///  `previous_wake_time` Holds the time at which the task was last unblocked.
+///  It must be initialised with the current tick count prior to its first use
+///  (see kernel::task_get_tick_count()).  Following this the variable is
+///  automatically updated within task_delay_until().
+///
+///  `time_increment` The cycle time period.  The task will be unblocked at time
+///  *previous_wake_time + time_increment.  Calling task_delay_until() with the
+///  same time_increment parameter value will cause the task to execute with a
+///  fixed interface period.
+///
+/// * Return:
+///  true if the task was delayed.  false means the wake time had already passed
+///  when the function was called, i.e. the task missed its deadline and returns
+///  immediately.  previous_wake_time is advanced by time_increment either way.
+///
+#[cfg(feature = "INCLUDE_vTaskDelayUntil")]
+pub fn task_delay_until(previous_wake_time: &mut TickType, time_increment: TickType) -> bool {
+    let mut should_delay = false;
+
+    assert!(time_increment > 0);
+    assert!(get_scheduler_suspended!() == 0);
+
+    task_suspend_all();
+    {
+        /* Minor optimisation.  The tick count cannot change in this
+        block. */
+        let const_tick_count = get_tick_count!();
+
+        /* Generate the tick time at which the task wants to wake. */
+        let time_to_wake = previous_wake_time.wrapping_add(time_increment);
+
+        if const_tick_count < *previous_wake_time {
+            /* The tick count has overflowed since this function was
+            lasted called.  In this case the only time we should ever
+            actually delay is if the wake time has also overflowed,
+            and the wake time is greater than the tick time.  When this
+            is the case it is as if neither time had overflowed. */
+            if time_to_wake < *previous_wake_time && time_to_wake > const_tick_count {
+                should_delay = true;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        } else {
+            /* The tick time has not overflowed.  In this case we will
+            delay if either the wake time has overflowed, and/or the
+            tick time is less than the wake time. */
+            if time_to_wake < *previous_wake_time || time_to_wake > const_tick_count {
+                should_delay = true;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        }
+
+        /* Update the wake time ready for the next call. */
+        *previous_wake_time = time_to_wake;
+
+        if should_delay {
+            traceTASK_DELAY_UNTIL!(time_to_wake);
+
+            /* add_current_task_to_delayed_list() needs the block time, not
+            the time to wake, so subtract the current tick count.  A wake
+            time that has overflowed ends up in OVERFLOW_DELAYED_TASK_LIST. */
+            add_current_task_to_delayed_list(time_to_wake.wrapping_sub(const_tick_count), false);
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+    let already_yielded = task_resume_all().unwrap();
+
+    /* Force a reschedule if task_resume_all has not already done so, we may
+    have put ourselves to sleep. */
+    if !already_yielded {
+        portYIELD_WITHIN_API!();
+    } else {
+        mtCOVERAGE_TEST_MARKER!();
+    }
+
+    should_delay
+}
@@ -0,0 +1,964 @@
+// timers.rs - Software timers and the timer daemon task.
+// Functions defined in this file are adapted from timers.c.
+//
+// Timers are not serviced from the tick interrupt. Instead every operation on a
+// timer is posted as a command to TIMER_QUEUE, which is drained by the timer
+// daemon task ("Tmr Svc"). The daemon task also keeps the active timers sorted
+// by expiry time and calls their callbacks when they expire.
+use crate::kernel;
+use crate::port::*;
+use crate::projdefs::FreeRtosError;
+use crate::queue_api::Queue;
+use crate::sync::RwLock;
+use crate::task_control::{TaskHandle, TCB};
+use crate::*;
+use std::sync::Arc;
+
+/* IDs for commands that can be sent/received on the timer queue.  These are to
+be used solely through the methods that make up the public software timer API,
+as defined below.  The ExecuteCallback* commands carry a pended function call
+rather than a timer command. */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TimerCommand {
+    ExecuteCallbackFromIsr,
+    ExecuteCallback,
+    StartDontTrace,
+    #[default]
+    Start,
+    Reset,
+    Stop,
+    ChangePeriod,
+    Delete,
+    StartFromIsr,
+    ResetFromIsr,
+    StopFromIsr,
+    ChangePeriodFromIsr,
+}
+
+/// The callback executed by the timer daemon task when a timer expires.
+/// The callback receives the handle of the timer that expired.
+pub type TimerCallback = Arc<dyn Fn(&TimerHandle) + Send + Sync>;
+
+/// A function deferred to the timer daemon task by `timer_pend_function_call()`.
+pub type PendedFunction = Arc<dyn Fn() + Send + Sync>;
+
+/* The definition of messages that can be sent and received on the timer
+queue.  Timer commands carry a timer and a value, pended function calls carry
+only the function to execute. */
+#[derive(Clone, Default)]
+struct DaemonTaskMessage {
+    message_id: TimerCommand,
+    message_value: TickType,
+    timer: Option<TimerHandle>,
+    function: Option<PendedFunction>,
+}
+
+/// The definition of the timers themselves.
+pub struct TimerDefinition {
+    /* Text name.  This is not used by the kernel, it is included simply to
+    make debugging easier. */
+    timer_name: String,
+    /* How quickly and often the timer expires. */
+    period_in_ticks: TickType,
+    /* Set to true if the timer should be automatically restarted once
+    expired.  Set to false if the timer is, in effect, a one-shot timer. */
+    auto_reload: bool,
+    /* The function that will be called when the timer expires. */
+    callback: Option<TimerCallback>,
+    /* The time at which the timer will expire next, used to sort the
+    active timer lists (this is the item value of xTimerListItem in C). */
+    expiry_time: TickType,
+    /* Whether the timer is currently referenced from one of the timer lists. */
+    in_list: bool,
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    timer_number: UBaseType,
+}
+
+pub type Timer = TimerDefinition;
+
+/* The active timer lists are sorted by expiry time, the timer that expires
+first is at the head.  Two lists are used, timers whose expiry time has
+overflowed the current tick count are held in the overflow list. */
+lazy_static! {
+    static ref ACTIVE_TIMER_LIST: RwLock<Vec<TimerHandle>> = RwLock::new(Vec::new());
+    static ref OVERFLOW_TIMER_LIST: RwLock<Vec<TimerHandle>> = RwLock::new(Vec::new());
+
+    /* A queue that is used to send commands to the timer service task. */
+    static ref TIMER_QUEUE: Queue<DaemonTaskMessage> =
+        Queue::new(configTIMER_QUEUE_LENGTH!());
+
+    static ref TIMER_TASK_HANDLE: RwLock<Option<TaskHandle>> = RwLock::new(None);
+}
+
+/* The stack given by set_timer_task_stack(). */
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+lazy_static! {
+    static ref TIMER_TASK_STACK: crate::sync::Mutex<Option<&'static mut [StackType]>> =
+        crate::sync::Mutex::new(None);
+}
+
+impl Default for TimerDefinition {
+    fn default() -> Self {
+        TimerDefinition::new()
+    }
+}
+
+impl TimerDefinition {
+    pub fn new() -> Self {
+        TimerDefinition {
+            timer_name: String::from("Unnamed"),
+            period_in_ticks: 1,
+            auto_reload: false,
+            callback: None,
+            expiry_time: 0,
+            in_list: false,
+            #[cfg(feature = "configUSE_TRACE_FACILITY")]
+            timer_number: 0,
+        }
+    }
+
+    /// * Descrpition:
+    ///   Reset the name of a timer.
+    ///
+    /// # Arguments:
+    ///  `name` A text name that is assigned to the timer.  This is done
+    ///  purely to assist debugging.  The kernel itself only ever references a
+    ///  timer by its handle, and never by its name.
+    ///
+    /// # Return:
+    /// Return a timer with new name.
+    pub fn name(mut self, name: &str) -> Self {
+        self.timer_name = name.to_owned();
+        self
+    }
+
+    /// * Descrpition:
+    ///   Reset the period of a timer.
+    ///
+    /// # Arguments:
+    ///  `period` The timer period.  The time is defined in tick periods so
+    ///  the constant portTICK_PERIOD_MS can be used to convert a time that has
+    ///  been specified in milliseconds.  The timer period must be greater than 0.
+    ///
+    /// # Return:
+    /// Return a timer with new period.
+    pub fn period(mut self, period: TickType) -> Self {
+        self.period_in_ticks = period;
+        self
+    }
+
+    /// * Descrpition:
+    ///   Choose between an auto-reload timer and a one-shot timer.
+    ///
+    /// # Arguments:
+    ///  `auto_reload` If set to true then the timer will expire repeatedly
+    ///  with a frequency set by the period.  If set to false then the timer will
+    ///  be a one-shot timer and enter the dormant state after it expires.
+    ///
+    /// # Return:
+    /// Return a timer with the new reload mode.
+    pub fn auto_reload(mut self, auto_reload: bool) -> Self {
+        self.auto_reload = auto_reload;
+        self
+    }
+
+    /// * Descrpition:
+    ///   Creates a new software timer instance, and returns a handle by which the
+    ///   created software timer can be referenced.
+    ///
+    ///  Timers are created in the dormant state.  The start(), reset(),
+    ///  start_from_isr(), reset_from_isr(), change_period() and
+    ///  change_period_from_isr() API functions can all be used to transition a
+    ///  timer into the active state.
+    ///
+    /// * C implementation: timers.c 279-300
+    ///
+    /// # Arguments:
+    ///  `callback` The function to call when the timer expires.  Callback
+    ///  functions run in the context of the timer daemon task, so they must not
+    ///  block indefinitely.
+    ///
+    /// # Return:
+    ///  The handle of the created timer.
+    pub fn initialise<F>(mut self, callback: F) -> TimerHandle
+    where
+        F: Fn(&TimerHandle) + Send + Sync + 'static,
+    {
+        /* 0 is not a valid value for period. */
+        assert!(self.period_in_ticks > 0);
+
+        self.callback = Some(Arc::new(callback));
+        self.in_list = false;
+        let timer = TimerHandle(Arc::new(RwLock::new(self)));
+        traceTIMER_CREATE!(&timer);
+        timer
+    }
+}
+
+///  Type by which software timers are referenced.  Like `TaskHandle`, several
+///  handles may refer to the same timer, so the definition is wrapped in an
+///  `Arc<RwLock<_>>`.
+#[derive(Clone)]
+pub struct TimerHandle(Arc<RwLock<TimerDefinition>>);
+
+impl PartialEq for TimerHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl TimerHandle {
+    /// Returns the name that was assigned to the timer when it was created.
+    pub fn get_name(&self) -> String {
+        self.0.read().unwrap().timer_name.clone()
+    }
+
+    /// Returns the period of the timer, in ticks.
+    pub fn get_period(&self) -> TickType {
+        self.0.read().unwrap().period_in_ticks
+    }
+
+    /// Returns the time in ticks at which the timer will expire.  If this is
+    /// less than the current tick count then the expiry time has overflowed
+    /// from the current time.
+    pub fn get_expiry_time(&self) -> TickType {
+        self.0.read().unwrap().expiry_time
+    }
+
+    pub fn is_auto_reload(&self) -> bool {
+        self.0.read().unwrap().auto_reload
+    }
+
+    fn set_expiry_time(&self, expiry_time: TickType) {
+        self.0.write().unwrap().expiry_time = expiry_time;
+    }
+
+    fn set_period(&self, period: TickType) {
+        self.0.write().unwrap().period_in_ticks = period;
+    }
+
+    fn set_in_list(&self, in_list: bool) {
+        self.0.write().unwrap().in_list = in_list;
+    }
+
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    pub fn get_timer_number(&self) -> UBaseType {
+        self.0.read().unwrap().timer_number
+    }
+
+    #[cfg(feature = "configUSE_TRACE_FACILITY")]
+    pub fn set_timer_number(&self, timer_number: UBaseType) {
+        self.0.write().unwrap().timer_number = timer_number;
+    }
+
+    /// * Descrpition:
+    ///   Queries a timer to see if it is active or dormant.
+    ///
+    ///  A timer will be dormant if:
+    ///      1) It has been created but not started, or
+    ///      2) It is an expired one-shot timer that has not been restarted.
+    ///
+    /// * C implementation: timers.c 981-1000
+    ///
+    /// # Return:
+    ///  false if the timer is dormant, true otherwise.
+    pub fn is_timer_active(&self) -> bool {
+        let is_active;
+        /* Is the timer in the list of active timers? */
+        taskENTER_CRITICAL!();
+        {
+            is_active = self.0.read().unwrap().in_list;
+        }
+        taskEXIT_CRITICAL!();
+        is_active
+    }
+
+    /// * Descrpition:
+    ///   Starts a timer that was previously created.  If the timer had already
+    ///   been started and was already in the active state, then start() has
+    ///   equivalent functionality to reset().
+    ///
+    ///  The timer will expire `period` ticks after start() was called, at which
+    ///  point its callback will be executed by the timer daemon task.
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` Specifies the time, in ticks, that the calling task
+    ///  should be held in the Blocked state to wait for the start command to be
+    ///  successfully sent to the timer command queue, should the queue already
+    ///  be full.  It is ignored if start() is called before the scheduler is
+    ///  started.
+    ///
+    /// # Return:
+    ///  Ok(()) if the command was successfully sent to the timer command queue.
+    pub fn start(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
+        timer_generic_command(
+            self,
+            TimerCommand::Start,
+            kernel::task_get_tick_count(),
+            ticks_to_wait,
+        )
+    }
+
+    /// * Descrpition:
+    ///   Re-starts a timer.  If the timer is already active its expiry time is
+    ///   recalculated to be relative to when reset() was called.  If the timer
+    ///   was dormant then reset() has equivalent functionality to start().
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` See start().
+    ///
+    /// # Return:
+    ///  Ok(()) if the command was successfully sent to the timer command queue.
+    pub fn reset(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
+        timer_generic_command(
+            self,
+            TimerCommand::Reset,
+            kernel::task_get_tick_count(),
+            ticks_to_wait,
+        )
+    }
+
+    /// * Descrpition:
+    ///   Stops a timer that was previously started.
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` See start().
+    ///
+    /// # Return:
+    ///  Ok(()) if the command was successfully sent to the timer command queue.
+    pub fn stop(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
+        timer_generic_command(self, TimerCommand::Stop, 0, ticks_to_wait)
+    }
+
+    /// * Descrpition:
+    ///   Changes the period of a timer.  If the timer was dormant it is started,
+    ///   and will expire `new_period` ticks after the command is processed.
+    ///
+    /// # Arguments:
+    ///  `new_period` The new period for the timer, in ticks.  Must be greater
+    ///  than 0.
+    ///
+    ///  `ticks_to_wait` See start().
+    ///
+    /// # Return:
+    ///  Ok(()) if the command was successfully sent to the timer command queue.
+    pub fn change_period(
+        &self,
+        new_period: TickType,
+        ticks_to_wait: TickType,
+    ) -> Result<(), FreeRtosError> {
+        timer_generic_command(self, TimerCommand::ChangePeriod, new_period, ticks_to_wait)
+    }
+
+    /// * Descrpition:
+    ///   Deletes a timer.  The timer is stopped, and the timer daemon task drops
+    ///   its reference to it.  Once the last handle is dropped the callback
+    ///   closure is freed.
+    ///
+    /// # Arguments:
+    ///  `ticks_to_wait` See start().
+    ///
+    /// # Return:
+    ///  Ok(()) if the command was successfully sent to the timer command queue.
+    pub fn delete(&self, ticks_to_wait: TickType) -> Result<(), FreeRtosError> {
+        timer_generic_command(self, TimerCommand::Delete, 0, ticks_to_wait)
+    }
+
+    /// A version of start() that can be called from an interrupt service routine.
+    ///
+    /// # Return:
+    /// * `Result` - Ok(()) if the command was sent to the timer command queue.
+    /// * `bool` - pxHigherPriorityTaskWoken is changed to be a return value. It is
+    ///   true if sending the command caused the timer daemon task to unblock, and the
+    ///   daemon task has a priority higher than the interrupted task.
+    pub fn start_from_isr(&self) -> (Result<(), FreeRtosError>, bool) {
+        timer_generic_command_from_isr(
+            self,
+            TimerCommand::StartFromIsr,
+            kernel::task_get_tick_count_from_isr(),
+        )
+    }
+
+    /// A version of reset() that can be called from an interrupt service routine.
+    /// See start_from_isr() for the return value.
+    pub fn reset_from_isr(&self) -> (Result<(), FreeRtosError>, bool) {
+        timer_generic_command_from_isr(
+            self,
+            TimerCommand::ResetFromIsr,
+            kernel::task_get_tick_count_from_isr(),
+        )
+    }
+
+    /// A version of stop() that can be called from an interrupt service routine.
+    /// See start_from_isr() for the return value.
+    pub fn stop_from_isr(&self) -> (Result<(), FreeRtosError>, bool) {
+        timer_generic_command_from_isr(self, TimerCommand::StopFromIsr, 0)
+    }
+
+    /// A version of change_period() that can be called from an interrupt service
+    /// routine. See start_from_isr() for the return value.
+    pub fn change_period_from_isr(
+        &self,
+        new_period: TickType,
+    ) -> (Result<(), FreeRtosError>, bool) {
+        timer_generic_command_from_isr(self, TimerCommand::ChangePeriodFromIsr, new_period)
+    }
+
+    fn call_callback(&self) {
+        /* Clone the callback out of the timer so the lock is not held while it
+        runs - the callback may well operate on its own timer. */
+        let callback = self.0.read().unwrap().callback.clone();
+        if let Some(callback) = callback {
+            callback(self);
+        }
+    }
+}
+
+/// * Descrpition:
+///   Creates the timer daemon task.  Called by task_start_scheduler() when
+///   configUSE_TIMERS is enabled.
+///
+/// * C implementation: timers.c 259-293
+///
+/// # Return:
+///  The handle of the timer daemon task, or an error if the task could not be
+///  created.
+pub fn create_timer_task() -> Result<TaskHandle, FreeRtosError> {
+    /* Create the queue now rather than when the first command is sent, so
+    nothing is allocated for it once the scheduler is running. */
+    lazy_static::initialize(&TIMER_QUEUE);
+
+    let tcb = TCB::new()
+        .name("Tmr Svc")
+        .priority(configTIMER_TASK_PRIORITY!())
+        .stacksize(configTIMER_TASK_STACK_DEPTH!());
+
+    #[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+    let timer_task_handle = match TIMER_TASK_STACK.lock().unwrap().take() {
+        Some(stack) => tcb.initialise_with_stack(timer_task, stack)?,
+        None => tcb.initialise(timer_task)?,
+    };
+    #[cfg(not(feature = "configSUPPORT_STATIC_ALLOCATION"))]
+    let timer_task_handle = tcb.initialise(timer_task)?;
+
+    *TIMER_TASK_HANDLE.write().unwrap() = Some(timer_task_handle.clone());
+    Ok(timer_task_handle)
+}
+
+/// * Descrpition:
+///  Give the timer daemon task a stack, so it is created without
+///  port_malloc().  Must be called before task_start_scheduler(), otherwise
+///  the task allocates its stack as any other task does.
+///
+/// * C implementation: vApplicationGetTimerTaskMemory() in timers.h
+///
+/// # Arguments:
+///  `stack` The stack of the timer daemon task.  On the POSIX port it must
+///  hold at least port::portMINIMAL_STACK_BYTES.
+#[cfg(feature = "configSUPPORT_STATIC_ALLOCATION")]
+pub fn set_timer_task_stack(stack: &'static mut [StackType]) {
+    *TIMER_TASK_STACK.lock().unwrap() = Some(stack);
+}
+
+/// * Descrpition:
+///   Returns the handle of the timer daemon task.  It is not valid to call this
+///   function before the scheduler has been started.
+///
+/// * C implementation: timers.c 460-466
+pub fn timer_get_timer_daemon_task_handle() -> TaskHandle {
+    /* If xTimerGetTimerDaemonTaskHandle() is called before the scheduler has been
+    started, then xTimerTaskHandle will be NULL. */
+    TIMER_TASK_HANDLE
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| panic!("Timer daemon task has not been created"))
+}
+
+/// * Descrpition:
+///   Used to defer the execution of a function to the timer daemon task.
+///
+/// * C implementation: timers.c 1059-1081
+///
+/// # Arguments:
+///  `function` The function to execute from the timer daemon task.
+///
+///  `ticks_to_wait` The time the calling task should remain in the Blocked
+///  state waiting for space to become available on the timer queue.
+///
+/// # Return:
+///  Ok(()) if the function was successfully posted to the timer command queue.
+#[cfg(feature = "INCLUDE_xTimerPendFunctionCall")]
+pub fn timer_pend_function_call<F>(
+    function: F,
+    ticks_to_wait: TickType,
+) -> Result<(), FreeRtosError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    /* The timer queue is created on first use, so the function can be posted
+    before the scheduler is started.  It runs once the timer daemon task
+    does. */
+    let message = DaemonTaskMessage {
+        message_id: TimerCommand::ExecuteCallback,
+        function: Some(Arc::new(function)),
+        ..Default::default()
+    };
+
+    let ret_val = TIMER_QUEUE.send_to_back(message, ticks_to_wait);
+    tracePEND_FUNC_CALL!(function, 0, 0, ret_val);
+    ret_val
+}
+
+/// * Descrpition:
+///   Used from application interrupt service routines to defer the execution of
+///   a function to the timer daemon task.  This is how FreeRTOS keeps
+///   non-deterministic operations, such as walking an event list, out of
+///   interrupt context.
+///
+/// * C implementation: timers.c 1035-1052
+///
+/// # Arguments:
+///  `function` The function to execute from the timer daemon task.
+///
+/// # Return:
+/// * `Result` - Ok(()) if the function was posted to the timer command queue.
+/// * `bool` - true if the timer daemon task was unblocked and has a priority
+///   higher than the interrupted task.
+#[cfg(feature = "INCLUDE_xTimerPendFunctionCall")]
+pub fn timer_pend_function_call_from_isr<F>(function: F) -> (Result<(), FreeRtosError>, bool)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let message = DaemonTaskMessage {
+        message_id: TimerCommand::ExecuteCallbackFromIsr,
+        function: Some(Arc::new(function)),
+        ..Default::default()
+    };
+
+    let ret_val = TIMER_QUEUE.send_to_back_from_isr(message);
+    tracePEND_FUNC_CALL_FROM_ISR!(function, 0, 0, ret_val);
+    ret_val
+}
+
+/* Send a command to the timer daemon task from a task. C implementation:
+timers.c 417-458 */
+fn timer_generic_command(
+    timer: &TimerHandle,
+    command_id: TimerCommand,
+    optional_value: TickType,
+    ticks_to_wait: TickType,
+) -> Result<(), FreeRtosError> {
+    /* Send a message to the timer service task to perform a particular action
+    on a particular timer definition. */
+    let message = DaemonTaskMessage {
+        message_id: command_id,
+        message_value: optional_value,
+        timer: Some(timer.clone()),
+        function: None,
+    };
+
+    /* If the scheduler is not running the timer daemon task cannot be
+    blocked on, so don't block on the queue either. */
+    let ticks_to_wait = match kernel::task_get_scheduler_state() {
+        kernel::SchedulerState::Running => ticks_to_wait,
+        _ => 0,
+    };
+
+    let ret_val = TIMER_QUEUE.send_to_back(message, ticks_to_wait);
+    traceTIMER_COMMAND_SEND!(timer, command_id, optional_value, ret_val);
+    ret_val
+}
+
+/* Send a command to the timer daemon task from an interrupt. */
+fn timer_generic_command_from_isr(
+    timer: &TimerHandle,
+    command_id: TimerCommand,
+    optional_value: TickType,
+) -> (Result<(), FreeRtosError>, bool) {
+    let message = DaemonTaskMessage {
+        message_id: command_id,
+        message_value: optional_value,
+        timer: Some(timer.clone()),
+        function: None,
+    };
+
+    let ret_val = TIMER_QUEUE.send_to_back_from_isr(message);
+    traceTIMER_COMMAND_SEND!(timer, command_id, optional_value, ret_val.0);
+    ret_val
+}
+
+/* The timer service task (daemon).  Timer functionality is controlled by this
+task.  Other tasks communicate with the timer service task using the
+TIMER_QUEUE queue. C implementation: timers.c 538-572 */
+fn timer_task() {
+    /* The tick count the last time the timer daemon task sampled it. */
+    let mut last_time: TickType = 0;
+
+    loop {
+        /* Query the timers list to see if it contains any timers, and if so,
+        obtain the time at which the next timer will expire. */
+        let (next_expire_time, list_was_empty) = get_next_expire_time();
+
+        /* If a timer has expired, process it.  Otherwise, block this task
+        until either a timer does expire, or a command is received. */
+        process_timer_or_block_task(next_expire_time, list_was_empty, &mut last_time);
+
+        /* Empty the command queue. */
+        process_received_commands(&mut last_time);
+    }
+}
+
+/* An active timer has reached its expire time.  Reload the timer if it is an
+auto reload timer, then call its callback. C implementation: timers.c 498-536 */
+fn process_expired_timer(next_expire_time: TickType, time_now: TickType) {
+    /* Remove the timer from the list of active timers.  A check has already
+    been performed to ensure the list is not empty. */
+    let timer = ACTIVE_TIMER_LIST.write().unwrap().remove(0);
+    timer.set_in_list(false);
+    traceTIMER_EXPIRED!(&timer);
+
+    /* If the timer is an auto reload timer then calculate the next
+    expiry time and re-insert the timer in the list of active timers. */
+    if timer.is_auto_reload() {
+        /* The timer is inserted into a list using a time relative to anything
+        other than the current time.  It will therefore be inserted into the
+        correct list relative to the time this task thinks it is now. */
+        if insert_timer_in_active_list(
+            &timer,
+            next_expire_time.wrapping_add(timer.get_period()),
+            time_now,
+            next_expire_time,
+        ) {
+            /* The timer expired before it was added to the active timer
+            list.  Reload it now.  */
+            let result =
+                timer_generic_command(&timer, TimerCommand::StartDontTrace, next_expire_time, 0);
+            assert!(result.is_ok());
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    } else {
+        mtCOVERAGE_TEST_MARKER!();
+    }
+
+    /* Call the timer callback. */
+    timer.call_callback();
+}
+
+/* Called by the timer service task to either process an expired timer or
+block to wait for the next timer to expire or a command to arrive.
+C implementation: timers.c 574-632 */
+fn process_timer_or_block_task(
+    next_expire_time: TickType,
+    list_was_empty: bool,
+    last_time: &mut TickType,
+) {
+    kernel::task_suspend_all();
+    {
+        /* Obtain the time now to make an assessment as to whether the timer
+        has expired or not.  If obtaining the time causes the lists to switch
+        then don't process this timer as any timers that remained in the list
+        when the lists were switched will have been processed within the
+        sample_time_now() function. */
+        let (time_now, timer_lists_were_switched) = sample_time_now(last_time);
+        if !timer_lists_were_switched {
+            /* The tick count has not overflowed, has the timer expired? */
+            if !list_was_empty && next_expire_time <= time_now {
+                kernel::task_resume_all().unwrap();
+                process_expired_timer(next_expire_time, time_now);
+            } else {
+                /* The tick count has not overflowed, and the next expire
+                time has not been reached yet.  This task should therefore
+                block to wait for the next expire time or a command to be
+                received - whichever comes first.  The following line cannot
+                be reached unless next_expire_time > time_now, except in the
+                case when the current timer list is empty. */
+                let list_was_empty = if list_was_empty {
+                    /* The current timer list is empty - is the overflow list
+                    also empty? */
+                    OVERFLOW_TIMER_LIST.read().unwrap().is_empty()
+                } else {
+                    false
+                };
+
+                TIMER_QUEUE.wait_for_message_restricted(
+                    next_expire_time.wrapping_sub(time_now),
+                    list_was_empty,
+                );
+
+                if !kernel::task_resume_all().unwrap() {
+                    /* Yield to wait for either a command to arrive, or the
+                    block time to expire.  If a command arrived between the
+                    critical section being exited and this yield then the yield
+                    will not cause the task to block. */
+                    portYIELD_WITHIN_API!();
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            }
+        } else {
+            kernel::task_resume_all().unwrap();
+        }
+    }
+}
+
+/* If the active timer list contains any active timers then return the expire
+time of the timer that will expire first and set list_was_empty to false.  If
+the list contains no timers then return 0 and set list_was_empty to true.
+C implementation: timers.c 634-658 */
+fn get_next_expire_time() -> (TickType, bool) {
+    /* Timers are listed in expiry time order, with the head of the list
+    referencing the task that will expire first.  Obtain the time at which
+    the timer with the nearest expiry time will expire.  If there are no
+    active timers then just set the next expire time to 0.  That will cause
+    this task to unblock when the tick count overflows, at which point the
+    timer lists will be switched and the next expiry time can be
+    re-assessed.  */
+    match ACTIVE_TIMER_LIST.read().unwrap().first() {
+        Some(timer) => (timer.get_expiry_time(), false),
+        None => (0, true),
+    }
+}
+
+/* If the tick count has overflowed since this function was last called then
+switch the timer lists.  `last_time` is the tick count the last call sampled,
+it is updated to the current one.  Returns the current tick count, and whether
+the lists were switched. C implementation: timers.c 660-681 */
+fn sample_time_now(last_time: &mut TickType) -> (TickType, bool) {
+    let time_now = kernel::task_get_tick_count();
+
+    let timer_lists_were_switched = if time_now < *last_time {
+        switch_timer_lists();
+        true
+    } else {
+        false
+    };
+
+    *last_time = time_now;
+
+    (time_now, timer_lists_were_switched)
+}
+
+/* Insert the timer into either the active or the overflow list, depending on
+its next expiry time.  Returns true if the timer should be processed straight
+away instead. C implementation: timers.c 683-722 */
+fn insert_timer_in_active_list(
+    timer: &TimerHandle,
+    next_expiry_time: TickType,
+    time_now: TickType,
+    command_time: TickType,
+) -> bool {
+    let mut process_timer_now = false;
+
+    timer.set_expiry_time(next_expiry_time);
+
+    if next_expiry_time <= time_now {
+        /* Has the expiry time elapsed between the command to start/reset a
+        timer was issued, and the time the command was processed? */
+        if time_now.wrapping_sub(command_time) >= timer.get_period() {
+            /* The time between a command being issued and the command being
+            processed actually exceeds the timers period.  */
+            process_timer_now = true;
+        } else {
+            insert_timer_in_list(&OVERFLOW_TIMER_LIST, timer);
+        }
+    } else {
+        if time_now < command_time && next_expiry_time >= command_time {
+            /* If, since the command was issued, the tick count has overflowed
+            but the expiry time has not, then the timer must have already passed
+            its expiry time and should be processed immediately. */
+            process_timer_now = true;
+        } else {
+            insert_timer_in_list(&ACTIVE_TIMER_LIST, timer);
+        }
+    }
+
+    process_timer_now
+}
+
+/* Insert the timer in expiry time order.  A timer is placed after any timers
+that expire at the same time, just as list_insert() does. */
+fn insert_timer_in_list(list: &RwLock<Vec<TimerHandle>>, timer: &TimerHandle) {
+    let expiry_time = timer.get_expiry_time();
+    let mut list = list.write().unwrap();
+    let position = list
+        .iter()
+        .position(|t| t.get_expiry_time() > expiry_time)
+        .unwrap_or(list.len());
+    list.insert(position, timer.clone());
+    timer.set_in_list(true);
+}
+
+/* Remove the timer from whichever timer list it is in, if any. */
+fn remove_timer_from_lists(timer: &TimerHandle) {
+    ACTIVE_TIMER_LIST.write().unwrap().retain(|t| t != timer);
+    OVERFLOW_TIMER_LIST.write().unwrap().retain(|t| t != timer);
+    timer.set_in_list(false);
+}
+
+/* Called by the timer service task to interpret and process a command it
+received on the timer queue. C implementation: timers.c 724-869 */
+fn process_received_commands(last_time: &mut TickType) {
+    while let Ok(message) = TIMER_QUEUE.receive(0) {
+        {
+            #![cfg(feature = "INCLUDE_xTimerPendFunctionCall")]
+            /* ExecuteCallback commands are pended function calls rather than
+            timer commands. */
+            if message.message_id == TimerCommand::ExecuteCallback
+                || message.message_id == TimerCommand::ExecuteCallbackFromIsr
+            {
+                /* The timer uses the callback structure to hold the function
+                that is to be called. */
+                if let Some(function) = message.function {
+                    /* Call the function. */
+                    function();
+                }
+                continue;
+            } else {
+                mtCOVERAGE_TEST_MARKER!();
+            }
+        }
+
+        /* All other commands are timer commands rather than pended function
+        calls. */
+        let timer = match message.timer {
+            Some(timer) => timer,
+            None => continue,
+        };
+
+        /* The messages uses the timer field to determine which timer is
+        being manipulated.  If it is already in a list, remove it. */
+        if timer.is_timer_active() {
+            remove_timer_from_lists(&timer);
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+
+        traceTIMER_COMMAND_RECEIVED!(&timer, message.message_id, message.message_value);
+
+        /* In this case the second return value is not used as the lists are
+        switched before any more commands are processed. */
+        let (time_now, _) = sample_time_now(last_time);
+
+        match message.message_id {
+            TimerCommand::Start
+            | TimerCommand::StartFromIsr
+            | TimerCommand::Reset
+            | TimerCommand::ResetFromIsr
+            | TimerCommand::StartDontTrace => {
+                /* Start or restart a timer. */
+                if insert_timer_in_active_list(
+                    &timer,
+                    message.message_value.wrapping_add(timer.get_period()),
+                    time_now,
+                    message.message_value,
+                ) {
+                    /* The timer expired before it was added to the active
+                    timer list.  Process it now. */
+                    timer.call_callback();
+                    traceTIMER_EXPIRED!(&timer);
+
+                    if timer.is_auto_reload() {
+                        let result = timer_generic_command(
+                            &timer,
+                            TimerCommand::StartDontTrace,
+                            message.message_value.wrapping_add(timer.get_period()),
+                            0,
+                        );
+                        assert!(result.is_ok());
+                    } else {
+                        mtCOVERAGE_TEST_MARKER!();
+                    }
+                } else {
+                    mtCOVERAGE_TEST_MARKER!();
+                }
+            }
+
+            TimerCommand::Stop | TimerCommand::StopFromIsr => {
+                /* The timer has already been removed from the active list.
+                There is nothing to do here. */
+            }
+
+            TimerCommand::ChangePeriod | TimerCommand::ChangePeriodFromIsr => {
+                timer.set_period(message.message_value);
+                assert!(timer.get_period() > 0);
+
+                /* The new period does not really have a reference, and can
+                be longer or shorter than the old one.  The command time is
+                therefore set to the current time, and as the period cannot
+                be zero the next expiry time can only be in the future,
+                meaning (unlike for the Start case above) there is no fail
+                case that needs to be handled here. */
+                insert_timer_in_active_list(
+                    &timer,
+                    time_now.wrapping_add(timer.get_period()),
+                    time_now,
+                    time_now,
+                );
+            }
+
+            TimerCommand::Delete => {
+                /* The timer has already been removed from the active list,
+                the daemon task's reference is dropped at the end of this
+                iteration. */
+            }
+
+            _ => { /* Don't expect to get here. */ }
+        }
+    }
+}
+
+/* The tick count has overflowed.  The timer lists must be switched.  If there
+are any timers still referenced from the current timer list then they must
+have expired and should be processed before the lists are switched.
+C implementation: timers.c 871-928 */
+fn switch_timer_lists() {
+    loop {
+        let timer = {
+            let mut active_list = ACTIVE_TIMER_LIST.write().unwrap();
+            if active_list.is_empty() {
+                break;
+            }
+            active_list.remove(0)
+        };
+        let next_expire_time = timer.get_expiry_time();
+
+        /* Remove the timer from the list. */
+        timer.set_in_list(false);
+        traceTIMER_EXPIRED!(&timer);
+
+        /* Execute its callback, then send a command to restart the timer if
+        it is an auto-reload timer.  It cannot be restarted here as the lists
+        have not yet been switched. */
+        timer.call_callback();
+
+        if timer.is_auto_reload() {
+            /* Calculate the reload value, and if the reload value results in
+            the timer going into the same timer list then it has already expired
+            and the timer should be re-inserted into the current list so it is
+            processed again within this loop.  Otherwise a command should be sent
+            to restart the timer to ensure it is only inserted into a list after
+            the lists have been swapped. */
+            let reload_time = next_expire_time.wrapping_add(timer.get_period());
+            if reload_time > next_expire_time {
+                timer.set_expiry_time(reload_time);
+                insert_timer_in_list(&ACTIVE_TIMER_LIST, &timer);
+            } else {
+                let result = timer_generic_command(
+                    &timer,
+                    TimerCommand::StartDontTrace,
+                    next_expire_time,
+                    0,
+                );
+                assert!(result.is_ok());
+            }
+        } else {
+            mtCOVERAGE_TEST_MARKER!();
+        }
+    }
+
+    let mut active_list = ACTIVE_TIMER_LIST.write().unwrap();
+    let mut overflow_list = OVERFLOW_TIMER_LIST.write().unwrap();
+    std::mem::swap(&mut *active_list, &mut *overflow_list);
+}
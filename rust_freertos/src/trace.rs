@@ -463,6 +463,71 @@ macro_rules! traceTASK_NOTIFY_GIVE_FROM_ISR {
     () => {};
 }
 
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_CREATE {
+    ($pxStreamBuffer: expr, $xIsMessageBuffer: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_DELETE {
+    ($xStreamBuffer: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_RESET {
+    ($xStreamBuffer: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceBLOCKING_ON_STREAM_BUFFER_SEND {
+    ($xStreamBuffer: expr) => {
+        trace!(
+            "Blocking task {} because it cannot write to a stream buffer.",
+            get_current_task_handle!().get_name()
+        );
+    };
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_SEND {
+    ($xStreamBuffer: expr, $xBytesSent: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_SEND_FAILED {
+    ($xStreamBuffer: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_SEND_FROM_ISR {
+    ($xStreamBuffer: expr, $xBytesSent: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceBLOCKING_ON_STREAM_BUFFER_RECEIVE {
+    ($xStreamBuffer: expr) => {
+        trace!(
+            "Blocking task {} because it cannot read from a stream buffer.",
+            get_current_task_handle!().get_name()
+        );
+    };
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_RECEIVE {
+    ($xStreamBuffer: expr, $xReceivedLength: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_RECEIVE_FAILED {
+    ($xStreamBuffer: expr) => {};
+}
+
+#[macro_export]
+macro_rules! traceSTREAM_BUFFER_RECEIVE_FROM_ISR {
+    ($xStreamBuffer: expr, $xReceivedLength: expr) => {};
+}
+
 #[macro_export]
 macro_rules! mtCOVERAGE_TEST_MARKER {
     () => {};